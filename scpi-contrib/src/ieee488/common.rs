@@ -91,7 +91,9 @@ where
         _params: Parameters,
         mut response: ResponseUnit,
     ) -> Result<()> {
-        response.data(device.ese()).finish()
+        response
+            .data(device.register_format().data(device.ese()))
+            .finish()
     }
 }
 
@@ -116,7 +118,7 @@ where
     ) -> Result<()> {
         let esr = device.esr();
         device.set_esr(0);
-        response.data(esr).finish()
+        response.data(device.register_format().data(esr)).finish()
     }
 }
 
@@ -276,7 +278,9 @@ where
         _params: Parameters,
         mut response: ResponseUnit,
     ) -> Result<()> {
-        response.data(device.sre()).finish()
+        response
+            .data(device.register_format().data(device.sre()))
+            .finish()
     }
 }
 
@@ -303,7 +307,7 @@ where
         if context.mav {
             stb |= StatusBit::Mav.mask();
         }
-        response.data(stb).finish()
+        response.data(device.register_format().data(stb)).finish()
     }
 }
 
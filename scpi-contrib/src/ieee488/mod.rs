@@ -1,13 +1,19 @@
 //! Contains IEEE 488.2 parser and mandatory commands
 //!
 
-use scpi::error::Result;
+use scpi::{
+    error::{ErrorCode, Result},
+    parser::{
+        format::{Binary, Hex, Octal},
+        response::{Formatter, ResponseData},
+    },
+};
 
 pub mod common;
 pub mod trg;
 
 pub mod prelude {
-    pub use super::{EventStatusBit, StatusBit, IEEE4882};
+    pub use super::{EventStatusBit, RegisterFormat, StatusBit, IEEE4882};
 }
 
 /// Event status/enable register bits
@@ -64,6 +70,52 @@ impl StatusBit {
     }
 }
 
+/// Response format of status register queries, selected with `FORMat:SREGister`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, scpi_derive::ScpiEnum)]
+pub enum RegisterFormat {
+    /// <NR1>
+    #[default]
+    #[scpi(mnemonic = b"ASCii")]
+    Ascii,
+    /// <HEXADECIMAL NUMERIC RESPONSE DATA>, e.g. `#H1F`
+    #[scpi(mnemonic = b"HEXadecimal")]
+    Hexadecimal,
+    /// <OCTAL NUMERIC RESPONSE DATA>, e.g. `#Q37`
+    #[scpi(mnemonic = b"OCTal")]
+    Octal,
+    /// <BINARY NUMERIC RESPONSE DATA>, e.g. `#B11111`
+    #[scpi(mnemonic = b"BINary")]
+    Binary,
+}
+
+impl RegisterFormat {
+    /// Register value formatted as `self`
+    pub fn data<T>(self, value: T) -> RegisterData<T> {
+        RegisterData(value, self)
+    }
+}
+
+/// A status register value formatted according to a [RegisterFormat]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RegisterData<T>(pub T, pub RegisterFormat);
+
+impl<T> ResponseData for RegisterData<T>
+where
+    T: ResponseData + Copy,
+    Hex<T>: ResponseData,
+    Octal<T>: ResponseData,
+    Binary<T>: ResponseData,
+{
+    fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+        match self.1 {
+            RegisterFormat::Ascii => self.0.format_response_data(formatter),
+            RegisterFormat::Hexadecimal => Hex(self.0).format_response_data(formatter),
+            RegisterFormat::Octal => Octal(self.0).format_response_data(formatter),
+            RegisterFormat::Binary => Binary(self.0).format_response_data(formatter),
+        }
+    }
+}
+
 pub trait IEEE4882 {
     /// Read Status byte register
     fn stb(&self) -> u8 {
@@ -94,6 +146,23 @@ pub trait IEEE4882 {
     /// Set the ESE register
     fn set_ese(&mut self, value: u8);
 
+    /// Response format of status register queries, see
+    /// [FormSregCommand](crate::scpi1999::format::FormSregCommand).
+    fn register_format(&self) -> RegisterFormat {
+        RegisterFormat::Ascii
+    }
+    /// Set the status register response format.
+    ///
+    /// Only [RegisterFormat::Ascii] is accepted unless overridden together with
+    /// [Self::register_format].
+    fn set_register_format(&mut self, format: RegisterFormat) -> Result<()> {
+        if format == RegisterFormat::Ascii {
+            Ok(())
+        } else {
+            Err(ErrorCode::IllegalParameterValue.into())
+        }
+    }
+
     /// # *TST
     /// Executed when a `*TST` command is issued.
     /// See [crate::ieee488::common::TstCommand] for details.
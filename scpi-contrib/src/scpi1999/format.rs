@@ -0,0 +1,203 @@
+//! # FORMat Subsystem
+//! The FORMat subsystem sets a data format for transferring numeric and array information.
+//! This data format is used for both command and response data by those commands that are
+//! specifically designated to be affected by the FORMat subsystem. The designation is either
+//! given as part of a command description, or in the definition of block or array data used by
+//! a command.
+//!
+//! Use [Format::array_data] to respond with array data in the selected format.
+use scpi::{
+    cmd_both,
+    error::{ErrorCode, Result},
+    parser::response::{ArrayData, ArrayElement, ByteOrder, DataFormat},
+    tree::prelude::*,
+};
+
+use crate::ieee488::IEEE4882;
+pub use crate::ieee488::{RegisterData, RegisterFormat};
+
+/// Device state of the FORMat subsystem
+pub trait Format {
+    /// Selected `FORMat[:DATA]`
+    fn data_format(&self) -> DataFormat;
+
+    /// Set `FORMat[:DATA]`
+    fn set_data_format(&mut self, format: DataFormat);
+
+    /// Selected `FORMat:BORDer`
+    fn byte_order(&self) -> ByteOrder;
+
+    /// Set `FORMat:BORDer`
+    fn set_byte_order(&mut self, order: ByteOrder);
+
+    /// Array response data in the selected format and byte order
+    fn array_data<'a, T>(&self, data: &'a [T]) -> ArrayData<'a, T>
+    where
+        T: ArrayElement,
+    {
+        ArrayData::new(data)
+            .format(self.data_format())
+            .byte_order(self.byte_order())
+    }
+}
+
+#[derive(Debug, Clone, Copy, scpi_derive::ScpiEnum)]
+enum DataType {
+    #[scpi(mnemonic = b"ASCii")]
+    Ascii,
+    #[scpi(mnemonic = b"INTeger")]
+    Integer,
+    #[scpi(mnemonic = b"REAL")]
+    Real,
+}
+
+#[derive(Debug, Clone, Copy, scpi_derive::ScpiEnum)]
+enum ByteOrderType {
+    #[scpi(mnemonic = b"NORMal")]
+    Normal,
+    #[scpi(mnemonic = b"SWAPped")]
+    Swapped,
+}
+
+///## 9.2 \[:DATA\] <type>\[,<length>\]
+///> `FORMat[:DATA]` The data format is specified by <type>, the first parameter. The second
+///> parameter specifies the <length> of the data; ASCii, INTeger or REAL.
+///
+/// Supported formats are `ASCii` (length is ignored), `INTeger,16|32` (defaults to 32) and
+/// `REAL,32|64` (defaults to 64). The query responds with `<type>,<length>`.
+pub struct FormDataCommand;
+
+impl<D> Command<D> for FormDataCommand
+where
+    D: Device + Format,
+{
+    cmd_both!();
+
+    fn event(&self, device: &mut D, _context: &mut Context, mut params: Parameters) -> Result<()> {
+        let typ: DataType = params.next_data()?;
+        let length: Option<u8> = params.next_optional_data()?;
+        let format = match (typ, length) {
+            (DataType::Ascii, _) => DataFormat::Ascii,
+            (DataType::Integer, Some(16)) => DataFormat::Int16,
+            (DataType::Integer, Some(32) | None) => DataFormat::Int32,
+            (DataType::Real, Some(32)) => DataFormat::Real32,
+            (DataType::Real, Some(64) | None) => DataFormat::Real64,
+            _ => return Err(ErrorCode::IllegalParameterValue.into()),
+        };
+        device.set_data_format(format);
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        device: &mut D,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let (typ, length) = match device.data_format() {
+            DataFormat::Ascii => (DataType::Ascii, 0u8),
+            DataFormat::Int16 => (DataType::Integer, 16),
+            DataFormat::Int32 => (DataType::Integer, 32),
+            DataFormat::Real32 => (DataType::Real, 32),
+            DataFormat::Real64 => (DataType::Real, 64),
+        };
+        response.data(typ).data(length).finish()
+    }
+}
+
+///## 9.1 BORDer NORMal|SWAPped
+///> `FORMat:BORDer` controls whether binary data is transferred in normal or swapped byte
+///> order. NORMal is the byte order specified in IEEE 488.2, most significant byte first.
+///> SWAPped is the reverse, least significant byte first.
+pub struct FormBorderCommand;
+
+impl<D> Command<D> for FormBorderCommand
+where
+    D: Device + Format,
+{
+    cmd_both!();
+
+    fn event(&self, device: &mut D, _context: &mut Context, mut params: Parameters) -> Result<()> {
+        let order = match params.next_data()? {
+            ByteOrderType::Normal => ByteOrder::Normal,
+            ByteOrderType::Swapped => ByteOrder::Swapped,
+        };
+        device.set_byte_order(order);
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        device: &mut D,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let order = match device.byte_order() {
+            ByteOrder::Normal => ByteOrderType::Normal,
+            ByteOrder::Swapped => ByteOrderType::Swapped,
+        };
+        response.data(order).finish()
+    }
+}
+
+///## 9.3 SREGister ASCii|BINary|HEXadecimal|OCTal
+///> `FORMat:SREGister` determines the format of the response for queries of the status
+///> registers, i.e. STATus:...:EVENt?, CONDition?, ENABle?, NTRansition? and PTRansition?
+///> as well as the IEEE 488.2 common queries *ESE?, *ESR?, *SRE? and *STB?.
+///
+/// The selection is stored with [IEEE4882::set_register_format].
+pub struct FormSregCommand;
+
+impl<D> Command<D> for FormSregCommand
+where
+    D: Device + IEEE4882,
+{
+    cmd_both!();
+
+    fn event(&self, device: &mut D, _context: &mut Context, mut params: Parameters) -> Result<()> {
+        device.set_register_format(params.next_data()?)
+    }
+
+    fn query(
+        &self,
+        device: &mut D,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        response.data(device.register_format()).finish()
+    }
+}
+
+/// Create a `FORMat:` tree branch with `[:DATA]`, `BORDer` and `SREGister` commands.
+#[macro_export]
+macro_rules! scpi_format {
+    ($($node:expr),*) => {
+        scpi::tree::prelude::Branch {
+            name: b"FORMat",
+            default: false,
+            sub: &[
+                scpi::tree::prelude::Leaf {
+                    name: b"DATA",
+                    default: true,
+                    handler: &$crate::scpi1999::format::FormDataCommand,
+                },
+                scpi::tree::prelude::Leaf {
+                    name: b"BORDer",
+                    default: false,
+                    handler: &$crate::scpi1999::format::FormBorderCommand,
+                },
+                scpi::tree::prelude::Leaf {
+                    name: b"SREGister",
+                    default: false,
+                    handler: &$crate::scpi1999::format::FormSregCommand,
+                },
+                $(
+                    $node
+                ),*
+            ],
+        }
+    };
+}
@@ -17,6 +17,7 @@ mod numeric;
 pub use numeric::{NumericBuilder, NumericValue, NumericValueDefaults, NumericValueQuery};
 
 // Subsystems
+pub mod format;
 //pub mod input;
 //pub mod measurement;
 //pub mod output;
@@ -28,6 +29,7 @@ pub mod system;
 
 pub mod prelude {
     pub use super::{
+        format::{Format, RegisterFormat},
         status::{operation::Operation, questionable::Questionable},
         EventRegister, GetEventRegister, ScpiDevice,
     };
@@ -20,6 +20,7 @@ use scpi::{cmd_both, cmd_nquery, cmd_qonly, error::Result, tree::prelude::*};
 use core::marker::PhantomData;
 
 use super::{BitFlags, EventRegisterName, GetEventRegister, ScpiDevice};
+use crate::ieee488::IEEE4882;
 
 pub mod operation;
 pub mod questionable;
@@ -64,7 +65,7 @@ impl<T> Default for EventCommand<T> {
 impl<D, T> Command<D> for EventCommand<T>
 where
     T: EventRegisterName,
-    D: Device + GetEventRegister<T>,
+    D: Device + IEEE4882 + GetEventRegister<T>,
 {
     cmd_qonly!();
 
@@ -76,7 +77,11 @@ where
         mut response: ResponseUnit,
     ) -> Result<()> {
         response
-            .data(core::mem::replace(&mut device.register_mut().event, 0) & 0x7FFFu16)
+            .data(
+                device
+                    .register_format()
+                    .data(core::mem::replace(&mut device.register_mut().event, 0) & 0x7FFFu16),
+            )
             .finish()
     }
 }
@@ -100,7 +105,7 @@ impl<T> Default for ConditionCommand<T> {
 impl<D, T> Command<D> for ConditionCommand<T>
 where
     T: EventRegisterName,
-    D: Device + GetEventRegister<T>,
+    D: Device + IEEE4882 + GetEventRegister<T>,
 {
     cmd_qonly!();
 
@@ -113,7 +118,11 @@ where
     ) -> Result<()> {
         //Always return first error (NoError if empty)
         response
-            .data(device.register().condition & 0x7FFFu16)
+            .data(
+                device
+                    .register_format()
+                    .data(device.register().condition & 0x7FFFu16),
+            )
             .finish()
     }
 }
@@ -137,7 +146,7 @@ impl<T> Default for EnableCommand<T> {
 impl<D, T> Command<D> for EnableCommand<T>
 where
     T: EventRegisterName,
-    D: Device + GetEventRegister<T>,
+    D: Device + IEEE4882 + GetEventRegister<T>,
 {
     cmd_both!();
 
@@ -153,7 +162,13 @@ where
         _params: Parameters,
         mut response: ResponseUnit,
     ) -> Result<()> {
-        response.data(device.register().enable & 0x7FFFu16).finish()
+        response
+            .data(
+                device
+                    .register_format()
+                    .data(device.register().enable & 0x7FFFu16),
+            )
+            .finish()
     }
 }
 
@@ -176,7 +191,7 @@ impl<T> Default for NTransitionCommand<T> {
 impl<D, T> Command<D> for NTransitionCommand<T>
 where
     T: EventRegisterName,
-    D: Device + GetEventRegister<T>,
+    D: Device + IEEE4882 + GetEventRegister<T>,
 {
     cmd_both!();
 
@@ -193,7 +208,11 @@ where
         mut response: ResponseUnit,
     ) -> Result<()> {
         response
-            .data(device.register().ntr_filter & 0x7FFFu16)
+            .data(
+                device
+                    .register_format()
+                    .data(device.register().ntr_filter & 0x7FFFu16),
+            )
             .finish()
     }
 }
@@ -217,7 +236,7 @@ impl<T> Default for PTransitionCommand<T> {
 impl<D, T> Command<D> for PTransitionCommand<T>
 where
     T: EventRegisterName,
-    D: Device + GetEventRegister<T>,
+    D: Device + IEEE4882 + GetEventRegister<T>,
 {
     cmd_both!();
 
@@ -234,7 +253,11 @@ where
         mut response: ResponseUnit,
     ) -> Result<()> {
         response
-            .data(device.register().ptr_filter & 0x7FFFu16)
+            .data(
+                device
+                    .register_format()
+                    .data(device.register().ptr_filter & 0x7FFFu16),
+            )
             .finish()
     }
 }
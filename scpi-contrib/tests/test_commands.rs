@@ -7,7 +7,8 @@ use scpi::prelude::*;
 use scpi::{cmd_nquery, cmd_qonly, tree::prelude::*};
 use scpi_contrib::{
     ieee488_cls, ieee488_ese, ieee488_esr, ieee488_idn, ieee488_opc, ieee488_rst, ieee488_sre,
-    ieee488_stb, ieee488_tst, ieee488_wai, scpi1999::prelude::*, scpi_status, scpi_system,
+    ieee488_stb, ieee488_tst, ieee488_wai, scpi1999::prelude::*, scpi_format, scpi_status,
+    scpi_system,
 };
 
 mod util;
@@ -32,6 +33,7 @@ const IEEE488_TREE: Node<TestDevice> = Branch {
         ieee488_wai!(),
         scpi_status!(),
         scpi_system!(),
+        scpi_format!(),
         Leaf {
             name: b"*ERR",
             default: false,
@@ -57,6 +59,11 @@ const IEEE488_TREE: Node<TestDevice> = Branch {
             default: false,
             handler: &EventCommand {},
         },
+        Leaf {
+            name: b"*TRACE",
+            default: false,
+            handler: &TraceCommand {},
+        },
     ],
 };
 
@@ -151,6 +158,22 @@ impl Command<TestDevice> for EventCommand {
     }
 }
 
+struct TraceCommand {}
+
+impl Command<TestDevice> for TraceCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        device: &mut TestDevice,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        response.data(device.array_data(&[1.5f32, -2.0])).finish()
+    }
+}
+
 #[test]
 fn test_qonly() {
     let mut dev = TestDevice::new();
@@ -447,3 +470,63 @@ fn test_stat_questionable() {
     .unwrap();
     assert_eq!(res.as_slice(), b"0;32767;0\n");
 }
+
+#[test]
+fn test_format() {
+    let mut dev = TestDevice::new();
+
+    let res =
+        util::test_execute_str(&IEEE488_TREE, b"form?;:form:bord?;*trace?", &mut dev).unwrap();
+    assert_eq!(res.as_slice(), b"ASC,0;NORM;1.5,-2.0\n");
+
+    let res = util::test_execute_str(
+        &IEEE488_TREE,
+        b"form:data real,32;bord swap;data?;bord?;*trace?",
+        &mut dev,
+    )
+    .unwrap();
+    assert_eq!(
+        res.as_slice(),
+        b"REAL,32;SWAP;#18\x00\x00\xc0\x3f\x00\x00\x00\xc0\n"
+    );
+
+    let res = util::test_execute_str(
+        &IEEE488_TREE,
+        b"form:bord norm;:form int;:form?;*trace?",
+        &mut dev,
+    )
+    .unwrap();
+    assert_eq!(
+        res.as_slice(),
+        b"INT,32;#18\x00\x00\x00\x01\xff\xff\xff\xfe\n"
+    );
+
+    let res = util::test_execute_str(&IEEE488_TREE, b"form int,8", &mut dev);
+    assert_eq!(res, Err(ErrorCode::IllegalParameterValue.into()));
+    let res = util::test_execute_str(&IEEE488_TREE, b"form pack", &mut dev);
+    assert_eq!(res, Err(ErrorCode::IllegalParameterValue.into()));
+}
+
+#[test]
+fn test_format_sreg() {
+    let mut dev = TestDevice::new();
+
+    let res = util::test_execute_str(
+        &IEEE488_TREE,
+        b"form:sreg?;sreg hex;sreg?;*ese 32;*ese?;*oper #H1234;:stat:oper:cond?",
+        &mut dev,
+    )
+    .unwrap();
+    assert_eq!(res.as_slice(), b"ASC;HEX;#H20;#H1234\n");
+
+    let res = util::test_execute_str(
+        &IEEE488_TREE,
+        b"form:sreg oct;*sre?;:form:sreg bin;*stb?;:stat:oper:enab 5;enab?;:form:sreg asc;*esr?",
+        &mut dev,
+    )
+    .unwrap();
+    assert_eq!(res.as_slice(), b"#Q0;#B0;#B101;0\n");
+
+    let res = util::test_execute_str(&IEEE488_TREE, b"form:sreg dec", &mut dev);
+    assert_eq!(res, Err(ErrorCode::IllegalParameterValue.into()));
+}
@@ -1,6 +1,10 @@
 use std::{collections::VecDeque, path::Path};
 
-use scpi::{error::Result, tree::prelude::*};
+use scpi::{
+    error::Result,
+    parser::response::{ByteOrder, DataFormat},
+    tree::prelude::*,
+};
 use serde::Deserialize;
 
 use scpi_contrib::{ieee488::prelude::*, scpi1999::prelude::*};
@@ -31,6 +35,12 @@ pub(crate) struct TestDevice {
     pub questionable: EventRegister,
     /// Error queue
     pub errors: VecDeque<Error>,
+    /// FORMat:DATA
+    pub data_format: DataFormat,
+    /// FORMat:BORDer
+    pub byte_order: ByteOrder,
+    /// FORMat:SREGister
+    pub register_format: RegisterFormat,
 }
 
 impl TestDevice {
@@ -42,6 +52,9 @@ impl TestDevice {
             operation: Default::default(),
             questionable: Default::default(),
             errors: Default::default(),
+            data_format: Default::default(),
+            byte_order: Default::default(),
+            register_format: Default::default(),
         }
     }
 }
@@ -101,6 +114,15 @@ impl IEEE4882 for TestDevice {
         self.ese = value;
     }
 
+    fn register_format(&self) -> RegisterFormat {
+        self.register_format
+    }
+
+    fn set_register_format(&mut self, format: RegisterFormat) -> Result<()> {
+        self.register_format = format;
+        Ok(())
+    }
+
     fn tst(&mut self) -> Result<()> {
         Ok(())
     }
@@ -156,6 +178,24 @@ impl GetEventRegister<Operation> for TestDevice {
     }
 }
 
+impl Format for TestDevice {
+    fn data_format(&self) -> DataFormat {
+        self.data_format
+    }
+
+    fn set_data_format(&mut self, format: DataFormat) {
+        self.data_format = format;
+    }
+
+    fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    fn set_byte_order(&mut self, order: ByteOrder) {
+        self.byte_order = order;
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 struct Record {
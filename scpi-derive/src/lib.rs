@@ -8,85 +8,277 @@
 
 extern crate proc_macro;
 
-use quote::{quote, quote_spanned};
-use syn::{parse_macro_input, Data, DeriveInput, LitByteStr, LitInt};
+mod mnemonic;
+
+use quote::quote;
+#[cfg(feature = "_private")]
+use syn::LitInt;
+use syn::{parse_macro_input, Data, DeriveInput, Ident, LitByteStr};
 
 /// Derive the necessary logic to convert a enum to and from a mnemonic.
 ///
-/// For each variant we look into the attributes looking for entry of the form #[scpi(mnemonic=b""")]
+/// For each variant we look into the attributes looking for entries of the form
+/// `#[scpi(mnemonic = b"MNEMonic")]`. A variant may have several mnemonics, the first one is
+/// returned by `mnemonic()` and the others are accepted as aliases.
+///
+/// Responses use the short form of the mnemonic unless the enum is marked with
+/// `#[scpi(response = long)]`.
+///
+/// Variants whose mnemonics can match the same input are rejected.
+///
+/// One variant with a single unnamed field may be marked `#[scpi(numeric)]` instead of having
+/// a mnemonic. It is parsed from non-character program data by the `TryFrom<Token>` of the
+/// field and formatted by its `ResponseData`. `#[scpi(fallback)]` also gets character data
+/// matching no mnemonic, e.g. for a `bool` field accepting `ON|OFF`. Its `mnemonic()` and
+/// `response_mnemonic()` are empty.
 #[proc_macro_derive(ScpiEnum, attributes(scpi))]
 pub fn derive_scpi_enum(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     // Parse the input tokens into a syntax tree.
     let input = parse_macro_input!(input as DeriveInput);
 
+    scpi_enum(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Form of the mnemonic used in responses
+enum ResponseForm {
+    Short,
+    Long,
+}
+
+/// Variant parsed from program data through the `TryFrom<Token>` of its field
+enum Payload {
+    /// `#[scpi(numeric)]`, from any data except character data
+    Numeric,
+    /// `#[scpi(fallback)]`, from any data not matching a mnemonic
+    Fallback,
+}
+
+fn scpi_enum(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     // Used in the quasi-quotation below as `#name`.
-    let name = input.ident;
+    let name = &input.ident;
 
     let variants = match input.data {
         Data::Enum(ref data) => &data.variants,
-        _ => panic!("Can only derive enum!"),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "ScpiEnum can only be derived for enums",
+            ))
+        }
     };
 
+    // Look for #[scpi(response = short|long)] on the enum
+    let mut response_form = ResponseForm::Short;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("scpi"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("response") {
+                let form: Ident = meta.value()?.parse()?;
+                response_form = if form == "short" {
+                    ResponseForm::Short
+                } else if form == "long" {
+                    ResponseForm::Long
+                } else {
+                    return Err(syn::Error::new_spanned(form, "expected `short` or `long`"));
+                };
+                Ok(())
+            } else {
+                Err(meta.error("unsupported scpi attribute"))
+            }
+        })?;
+    }
+
     let mut from_mnemonic_matches = Vec::new();
     let mut to_mnemonic_matches = Vec::new();
+    let mut response_matches = Vec::new();
+
+    // Mnemonics of the previous variants, to check for ambiguity
+    let mut previous: Vec<(&Ident, LitByteStr)> = Vec::new();
+
+    // The numeric or fallback variant and its field type
+    let mut payload: Option<(Payload, &Ident, &syn::Type)> = None;
 
     // Iter over the enum variants
     for variant in variants {
         let variant_name = &variant.ident;
 
-        // Iter over the attributes (#[repr]) of the variant
-        for attr in variant.attrs.iter() {
-            // We are interested only in 'scpi' attributes
-            if attr.path().is_ident("scpi") {
-                attr.parse_nested_meta(|meta| {
-
-                    // For scpi attributes we look for a mnemonic name value pair as literal byte string
-                    if meta.path.is_ident("mnemonic") {
-                        let mnemonic: LitByteStr = meta.value()?.parse()?;
-
-                        // We build a token stream to implement the enum creation from a mnemonic
-                        let x = match &variant.fields {
-                            syn::Fields::Unnamed(x) if x.unnamed.len() == 1 => quote! {
-                                x if scpi::parser::mnemonic_match(#mnemonic, x) => Some(#name::#variant_name(Default::default()))
-                            },
-                            syn::Fields::Unit => quote! {
-                                x if scpi::parser::mnemonic_match(#mnemonic, x) => Some(#name::#variant_name)
-                            },
-                            _ => quote_spanned! {
-                                variant_name.span() => compile_error!("Variant must be unit or single unnamed field implementing default")
-                            },
-                        };
-                        from_mnemonic_matches.push(x);
-
-                        // We build a token stream to implement the enum conversion to a mnemonic
-                        let mnemonic_return = LitByteStr::new(&mnemonic.value(), variant_name.span());
-
-                        let x2 = match &variant.fields {
-                            syn::Fields::Unnamed(x) if x.unnamed.len() == 1 => quote! {
-                                #name::#variant_name(..) => #mnemonic_return
-                            },
-                            syn::Fields::Unit => quote! {
-                                #name::#variant_name => #mnemonic_return
-                            },
-                            _ => quote_spanned! {
-                                variant_name.span() => compile_error!("Variant must be unit or single unnamed field implementing default")
-                            },
-                        };
-                        to_mnemonic_matches.push(x2);
-                    };
+        // Collect every #[scpi(mnemonic = b"...")] of the variant
+        let mut mnemonics: Vec<LitByteStr> = Vec::new();
+        let mut kind = None;
+        for attr in variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("scpi"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("mnemonic") {
+                    let mnemonic: LitByteStr = meta.value()?.parse()?;
+                    let value = mnemonic.value();
+                    if value.is_empty()
+                        || !value
+                            .iter()
+                            .all(|c| c.is_ascii_alphanumeric() || *c == b'_')
+                    {
+                        return Err(syn::Error::new(
+                            mnemonic.span(),
+                            "mnemonic must be ASCII letters, digits or underscores",
+                        ));
+                    }
+                    mnemonics.push(mnemonic);
+                    Ok(())
+                } else if meta.path.is_ident("numeric") {
+                    kind = Some(Payload::Numeric);
+                    Ok(())
+                } else if meta.path.is_ident("fallback") {
+                    kind = Some(Payload::Fallback);
                     Ok(())
-                }).unwrap()
+                } else {
+                    Err(meta.error("unsupported scpi attribute"))
+                }
+            })?;
+        }
+
+        if let Some(kind) = kind {
+            let field = match &variant.fields {
+                syn::Fields::Unnamed(x) if x.unnamed.len() == 1 && mnemonics.is_empty() => {
+                    &x.unnamed[0].ty
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        variant_name,
+                        "A numeric or fallback variant must have a single unnamed field and no mnemonic",
+                    ))
+                }
+            };
+            if payload.is_some() {
+                return Err(syn::Error::new_spanned(
+                    variant_name,
+                    "Only one numeric or fallback variant is allowed",
+                ));
+            }
+            payload = Some((kind, variant_name, field));
+
+            // It has no mnemonic, the value is formatted instead
+            to_mnemonic_matches.push(quote! {
+                #name::#variant_name(..) => b""
+            });
+            response_matches.push(quote! {
+                #name::#variant_name(..) => b""
+            });
+            continue;
+        }
+
+        let Some(mnemonic) = mnemonics.first() else {
+            return Err(syn::Error::new_spanned(
+                variant_name,
+                "Variant must have a #[scpi(mnemonic = b\"...\")] attribute",
+            ));
+        };
+
+        // Two variants matching the same input would depend on declaration order
+        for alias in &mnemonics {
+            for (other_name, other) in &previous {
+                if let Some(input) = mnemonic::ambiguity(&alias.value(), &other.value()) {
+                    return Err(syn::Error::new(
+                        alias.span(),
+                        format!(
+                            "mnemonic is ambiguous with variant `{other_name}`, both match `{input}`"
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let (construct, pattern) = match &variant.fields {
+            syn::Fields::Unnamed(x) if x.unnamed.len() == 1 => (
+                quote! { #name::#variant_name(Default::default()) },
+                quote! { #name::#variant_name(..) },
+            ),
+            syn::Fields::Unit => (
+                quote! { #name::#variant_name },
+                quote! { #name::#variant_name },
+            ),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant_name,
+                    "Variant must be unit or single unnamed field implementing default",
+                ))
             }
+        };
+
+        // We build a token stream to implement the enum creation from a mnemonic
+        for alias in &mnemonics {
+            from_mnemonic_matches.push(quote! {
+                x if scpi::parser::mnemonic_match(#alias, x) => Some(#construct)
+            });
         }
+
+        // We build a token stream to implement the enum conversion to a mnemonic
+        to_mnemonic_matches.push(quote! {
+            #pattern => #mnemonic
+        });
+
+        let response = match response_form {
+            ResponseForm::Short => mnemonic::short_form(&mnemonic.value()),
+            ResponseForm::Long => mnemonic::long_form(&mnemonic.value()),
+        };
+        let response = LitByteStr::new(&response, mnemonic.span());
+        response_matches.push(quote! {
+            #pattern => #response
+        });
+
+        previous.extend(mnemonics.iter().map(|alias| (variant_name, alias.clone())));
     }
 
+    // Non-character data and unknown mnemonics go to the numeric or fallback variant
+    let token = quote! { scpi::parser::tokenizer::Token };
+    let (unmatched, other, format_response) = match payload {
+        Some((kind, variant_name, field)) => {
+            let parse = quote! {
+                <#field as TryFrom<#token<'a>>>::try_from(value).map(#name::#variant_name)
+            };
+            let unmatched = match kind {
+                Payload::Numeric => quote! {
+                    Err(scpi::error::ErrorCode::IllegalParameterValue.into())
+                },
+                Payload::Fallback => parse.clone(),
+            };
+            let format_response = quote! {
+                fn format_response(
+                    &self,
+                    formatter: &mut dyn scpi::parser::response::Formatter,
+                ) -> scpi::error::Result<()> {
+                    match self {
+                        #name::#variant_name(value) => {
+                            scpi::parser::response::ResponseData::format_response_data(value, formatter)
+                        }
+                        other => formatter.push_str(
+                            <Self as scpi::option::ScpiEnum>::response_mnemonic(other),
+                        ),
+                    }
+                }
+            };
+            (unmatched, parse, format_response)
+        }
+        None => (
+            quote! { Err(scpi::error::ErrorCode::IllegalParameterValue.into()) },
+            quote! { Err(scpi::error::ErrorCode::DataTypeError.into()) },
+            quote! {},
+        ),
+    };
+
     // Generated the impl from the collected token streams
-    let expanded = quote! {
+    Ok(quote! {
         // The generated impl.
         impl scpi::option::ScpiEnum for #name {
             fn from_mnemonic(s: &[u8]) -> Option<#name> {
                 match s {
-                    #(#from_mnemonic_matches),*,
+                    #(#from_mnemonic_matches,)*
                     _ => None
                 }
             }
@@ -96,24 +288,41 @@ pub fn derive_scpi_enum(input: proc_macro::TokenStream) -> proc_macro::TokenStre
                     #(#to_mnemonic_matches),*
                 }
             }
+
+            fn response_mnemonic(&self) -> &'static [u8] {
+                match self {
+                    #(#response_matches),*
+                }
+            }
+
+            #format_response
         }
 
+        impl scpi::parser::response::ResponseData for #name {
+            fn format_response_data(
+                &self,
+                formatter: &mut dyn scpi::parser::response::Formatter,
+            ) -> scpi::error::Result<()> {
+                <Self as scpi::option::ScpiEnum>::format_response(self, formatter)
+            }
+        }
 
         impl<'a> TryFrom<scpi::parser::tokenizer::Token<'a>> for #name {
             type Error = scpi::error::Error;
 
             fn try_from(value: scpi::parser::tokenizer::Token<'a>) -> scpi::error::Result<Self> {
-                if let scpi::parser::tokenizer::Token::CharacterProgramData(s) = value {
-                    <Self as scpi::option::ScpiEnum>::from_mnemonic(s).ok_or(scpi::error::ErrorCode::IllegalParameterValue.into())
-                } else {
-                    Err(scpi::error::ErrorCode::DataTypeError.into())
+                match value {
+                    #token::CharacterProgramData(s) => {
+                        match <Self as scpi::option::ScpiEnum>::from_mnemonic(s) {
+                            Some(x) => Ok(x),
+                            None => #unmatched,
+                        }
+                    }
+                    value => #other,
                 }
             }
         }
-    };
-
-    // Hand the output tokens back to the compiler.
-    proc_macro::TokenStream::from(expanded)
+    })
 }
 
 /// Internal macro for scpi crate use only.
@@ -0,0 +1,169 @@
+//! Mnemonic matching at compile time.
+//!
+//! Mirrors `scpi::parser::mnemonic_match`, which this crate can't depend on, to find variants
+//! whose mnemonics accept the same input.
+
+/// Same as `scpi::parser::mnemonic_compare`
+fn compare(mnemonic: &[u8], s: &[u8]) -> bool {
+    let mut optional = true;
+    mnemonic.len() >= s.len() && {
+        let mut s_iter = s.iter();
+        mnemonic.iter().all(|m| {
+            let x = s_iter.next();
+            if m.is_ascii_lowercase() && x.is_some() {
+                optional = false;
+            }
+            x.map_or(
+                !(m.is_ascii_uppercase() || m.is_ascii_digit()) && optional,
+                |x| m.eq_ignore_ascii_case(x),
+            )
+        })
+    }
+}
+
+/// Split "ABC123" into ("ABC", "123")
+fn split_index(mnemonic: &[u8]) -> Option<(&[u8], &[u8])> {
+    let last = mnemonic.iter().rposition(|p| !p.is_ascii_digit())?;
+    if last == mnemonic.len() - 1 {
+        None
+    } else {
+        Some(mnemonic.split_at(last + 1))
+    }
+}
+
+/// Same as `scpi::parser::mnemonic_match`
+fn matches(mnemonic: &[u8], s: &[u8]) -> bool {
+    compare(mnemonic, s)
+        || match (split_index(mnemonic), split_index(s)) {
+            (None, None) => false,
+            (Some((m, index)), None) => compare(m, s) && index == b"1",
+            (None, Some((x, index))) => compare(mnemonic, x) && index == b"1",
+            (Some((m, index1)), Some((x, index2))) => compare(m, x) && index1 == index2,
+        }
+}
+
+/// Short form of a mnemonic without numeric suffix, e.g. `MEASure` => `MEAS`
+fn short(mnemonic: &[u8]) -> Vec<u8> {
+    mnemonic
+        .iter()
+        .take_while(|c| !c.is_ascii_lowercase())
+        .copied()
+        .collect()
+}
+
+/// Short and long form accepted by [compare]
+fn forms(mnemonic: &[u8]) -> Vec<Vec<u8>> {
+    let long = mnemonic.to_ascii_uppercase();
+    let short = short(mnemonic);
+    if short != long && compare(mnemonic, &short) {
+        vec![short, long]
+    } else {
+        vec![long]
+    }
+}
+
+/// Every input accepted by [matches], in uppercase
+fn inputs(mnemonic: &[u8]) -> Vec<Vec<u8>> {
+    let mut inputs = forms(mnemonic);
+    match split_index(mnemonic) {
+        Some((base, index)) => {
+            for form in forms(base) {
+                if index == b"1" {
+                    inputs.push(form.clone());
+                }
+                inputs.push([&form[..], index].concat());
+            }
+        }
+        None => {
+            for form in forms(mnemonic) {
+                inputs.push([&form[..], b"1"].concat());
+            }
+        }
+    }
+    inputs
+}
+
+/// Returns an input matched by both mnemonics, if any
+pub(crate) fn ambiguity(a: &[u8], b: &[u8]) -> Option<String> {
+    inputs(a)
+        .into_iter()
+        .find(|s| matches(b, s))
+        .map(|s| String::from_utf8_lossy(&s).into_owned())
+}
+
+/// Short form used in responses, keeping the numeric suffix, e.g. `INTernal2` => `INT2`
+pub(crate) fn short_form(mnemonic: &[u8]) -> Vec<u8> {
+    match split_index(mnemonic) {
+        Some((base, index)) => [&short(base)[..], index].concat(),
+        None => short(mnemonic),
+    }
+}
+
+/// Long form used in responses, e.g. `INTernal2` => `INTERNAL2`
+pub(crate) fn long_form(mnemonic: &[u8]) -> Vec<u8> {
+    mnemonic.to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(inputs: Vec<Vec<u8>>) -> Vec<String> {
+        inputs
+            .into_iter()
+            .map(|s| String::from_utf8(s).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_inputs() {
+        assert_eq!(
+            strings(inputs(b"MEASure")),
+            ["MEAS", "MEASURE", "MEAS1", "MEASURE1"]
+        );
+        // A suffix of 1 may be left out
+        let channel = strings(inputs(b"CHANnel1"));
+        for s in ["CHAN", "CHANNEL", "CHAN1", "CHANNEL1"] {
+            assert!(channel.iter().any(|x| x == s), "{s}");
+        }
+        let internal = strings(inputs(b"INTernal2"));
+        assert!(internal.iter().any(|x| x == "INT2"));
+        assert!(internal.iter().all(|x| x.ends_with('2')));
+        // Every input is accepted by the mnemonic itself
+        for mnemonic in [&b"MEASure"[..], b"CHANnel1", b"INTernal2", b"L125", b"*IDN"] {
+            for input in inputs(mnemonic) {
+                assert!(matches(mnemonic, &input), "{input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_ambiguity() {
+        assert_eq!(ambiguity(b"CURRent", b"CURR"), Some("CURR".to_string()));
+        assert_eq!(ambiguity(b"CHANnel", b"CHANnel1"), Some("CHAN".to_string()));
+        assert_eq!(ambiguity(b"L125", b"L125"), Some("L125".to_string()));
+        // Distinct mnemonics and suffixes
+        for (a, b) in [
+            (&b"INTernal"[..], &b"EXTernal"[..]),
+            (b"MINimum", b"MAXimum"),
+            (b"CHANnel1", b"CHANnel2"),
+            (b"ASCii", b"ASCii2"),
+            (b"L125", b"L12"),
+            (b"BINary", b"BIN2"),
+        ] {
+            assert_eq!(ambiguity(a, b), None, "{a:?} {b:?}");
+            assert_eq!(ambiguity(b, a), None, "{b:?} {a:?}");
+        }
+    }
+
+    #[test]
+    fn test_response_forms() {
+        assert_eq!(short_form(b"MEASure"), b"MEAS");
+        assert_eq!(short_form(b"ASCii2"), b"ASC2");
+        assert_eq!(short_form(b"INTernal10"), b"INT10");
+        assert_eq!(short_form(b"L125"), b"L125");
+        assert_eq!(short_form(b"*IDN"), b"*IDN");
+        assert_eq!(long_form(b"ASCii2"), b"ASCII2");
+        assert_eq!(long_form(b"L125"), b"L125");
+    }
+}
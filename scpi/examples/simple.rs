@@ -55,8 +55,21 @@ fn main() {
         let res = MYTREE.run(&command, &mut device, &mut context, &mut response);
 
         // Print response
-        if let Ok(_) = res {
-            stdout.write(&response).unwrap();
+        match res {
+            Ok(_) => {
+                stdout.write(&response).unwrap();
+            }
+            // Point out where the error is
+            Err(err) => {
+                if let Some(span) = err.get_span() {
+                    eprintln!("{}", String::from_utf8_lossy(&command));
+                    eprintln!(
+                        "{}{}",
+                        " ".repeat(span.start),
+                        "^".repeat(span.len().max(1))
+                    );
+                }
+            }
         }
     }
 }
@@ -0,0 +1,34 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+extern crate scpi;
+
+use scpi::parser::{canonical::write_canonical, tokenizer::Tokenizer};
+
+fn canonical(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    write_canonical(Tokenizer::new(data), &mut out).ok()?;
+    Some(out)
+}
+
+fuzz_target!(|input: (&[u8], f64, u8)| {
+    let (data, x, spelling) = input;
+    if let Some(out) = canonical(data) {
+        // Canonical form must parse and be its own canonical form
+        assert_eq!(canonical(&out).as_ref(), Some(&out));
+    }
+
+    // Different spellings of the same value are written the same
+    if x.is_finite() {
+        let plain = format!("X {x}");
+        let other = match spelling % 3 {
+            0 => format!("X {x:E}"),
+            1 => format!("X {x:+}"),
+            _ if plain.contains('.') => format!("{plain}0"),
+            _ => format!("{plain}.0"),
+        };
+        if let (Some(a), Some(b)) = (canonical(plain.as_bytes()), canonical(other.as_bytes())) {
+            assert_eq!(a, b, "{plain} != {other}");
+        }
+    }
+});
@@ -0,0 +1,32 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+extern crate scpi;
+
+use scpi::parser::tokenizer::{Token, Tokenizer};
+use std::convert::TryFrom;
+
+fuzz_target!(|input: (u32, u16, i8, u8)| {
+    let (mantissa, fraction, exponent, ws) = input;
+
+    // Insert white space before/after the exponent character and after its sign
+    let pick = |bit: u8| if ws & (1 << bit) != 0 { " " } else { "" };
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let s = format!(
+        "{mantissa}.{fraction}{}E{}{sign}{}{}",
+        pick(0),
+        pick(1),
+        pick(2),
+        exponent.unsigned_abs()
+    );
+
+    let mut tokenizer = Tokenizer::new_params(s.as_bytes());
+    let token = tokenizer.next().unwrap().unwrap();
+    assert!(matches!(token, Token::DecimalNumericProgramData(_)));
+    assert!(tokenizer.next().is_none());
+
+    // Must parse to the same value as without white space
+    let expected: f64 = s.replace(' ', "").parse().unwrap();
+    let value = f64::try_from(token).unwrap();
+    assert_eq!(value, expected);
+});
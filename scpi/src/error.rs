@@ -5,28 +5,133 @@
 use core::fmt::Display;
 
 /// A SCPI error
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+///
+/// Two errors are equal if their code and extended message are equal, the [Span] is not compared.
+#[derive(Debug, Copy, Clone)]
 pub struct Error(
     /// Error code
     ErrorCode,
     /// Optional additional error information
-    Option<&'static [u8]>,
+    Option<Extended>,
+    /// Optional location of the error within the program message
+    Option<Span>,
 );
 
+/// A byte range within a program message
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct Span {
+    /// Offset of first byte
+    pub start: usize,
+    /// Offset after the last byte
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a new span
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Number of bytes in span
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Returns true if the span covers no bytes, e.g. the location of a missing parameter
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<Span> for core::ops::Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.get_extended() == other.get_extended()
+    }
+}
+
+impl Eq for Error {}
+
+/// Maximum length of a formatted extended error message.
+/// Longer messages are truncated.
+#[cfg(feature = "extended-buffer")]
+pub const EXTENDED_CAPACITY: usize = 64;
+
+/// Extended error message
+#[derive(Debug, Copy, Clone)]
+enum Extended {
+    Static(&'static [u8]),
+    #[cfg(feature = "extended-buffer")]
+    Inline([u8; EXTENDED_CAPACITY], u8),
+}
+
+impl Extended {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Extended::Static(msg) => msg,
+            #[cfg(feature = "extended-buffer")]
+            Extended::Inline(buf, len) => &buf[..*len as usize],
+        }
+    }
+}
+
+/// Writes into a extended message buffer, truncating anything that doesn't fit.
+#[cfg(feature = "extended-buffer")]
+struct ExtendedWriter<'a>(&'a mut [u8; EXTENDED_CAPACITY], &'a mut u8);
+
+#[cfg(feature = "extended-buffer")]
+impl<'a> core::fmt::Write for ExtendedWriter<'a> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let len = *self.1 as usize;
+        let mut n = s.len().min(EXTENDED_CAPACITY - len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.0[len..len + n].copy_from_slice(&s.as_bytes()[..n]);
+        *self.1 += n as u8;
+        if n < s.len() {
+            Err(core::fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let code = self.get_code();
         let msg = core::str::from_utf8(self.get_message()).unwrap_or("<invalid utf8>");
 
+        // Quotes within the string are doubled like in <STRING RESPONSE DATA>
+        write!(f, "{code},\"")?;
+        write_quoted(f, msg)?;
         if let Some(ext) = self.get_extended() {
             let ext = core::str::from_utf8(ext).unwrap_or("<invalid utf8>");
-            write!(f, "{code},\"{msg};{ext}\"")
-        } else {
-            write!(f, "{code},\"{msg}\"")
+            f.write_str(";")?;
+            write_quoted(f, ext)?;
         }
+        f.write_str("\"")
     }
 }
 
+fn write_quoted(f: &mut core::fmt::Formatter<'_>, s: &str) -> core::fmt::Result {
+    for (i, part) in s.split('"').enumerate() {
+        if i > 0 {
+            f.write_str("\"\"")?;
+        }
+        f.write_str(part)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
 impl Default for Error {
     fn default() -> Self {
         Self::new(ErrorCode::NoError)
@@ -39,17 +144,60 @@ pub type Result<T> = core::result::Result<T, Error>;
 impl Error {
     /// Create new error with specified error code
     pub fn new(code: ErrorCode) -> Self {
-        Self(code, None)
+        Self(code, None, None)
     }
 
     /// Create new error with a custom (unchecked) code
     pub fn custom(code: i16, desc: &'static [u8]) -> Self {
-        Self(ErrorCode::Custom(code, desc), None)
+        Self(ErrorCode::Custom(code, desc), None, None)
     }
 
     /// Create new error with specified error code with an extended message
     pub fn extended(mut self, msg: &'static [u8]) -> Self {
-        self.1 = Some(msg);
+        self.1 = Some(Extended::Static(msg));
+        self
+    }
+
+    /// Create new error with a formatted extended message.
+    /// Messages longer than [EXTENDED_CAPACITY] are truncated.
+    ///
+    /// ```
+    /// # use scpi::error::{Error, ErrorCode};
+    /// let (value, max) = (12.5, 10.0);
+    /// let err = Error::new(ErrorCode::DataOutOfRange)
+    ///     .extended_fmt(format_args!("Value {value:.1} exceeds max {max:.1}"));
+    /// assert_eq!(err.get_extended(), Some(&b"Value 12.5 exceeds max 10.0"[..]));
+    /// ```
+    #[cfg(feature = "extended-buffer")]
+    pub fn extended_fmt(mut self, args: core::fmt::Arguments) -> Self {
+        let mut buf = [0u8; EXTENDED_CAPACITY];
+        let mut len = 0u8;
+        // Error only means that the message was truncated
+        let _ = core::fmt::write(&mut ExtendedWriter(&mut buf, &mut len), args);
+        self.1 = Some(Extended::Inline(buf, len));
+        self
+    }
+
+    /// Create new error with a copy of an extended message.
+    /// Messages longer than [EXTENDED_CAPACITY] are truncated.
+    #[cfg(feature = "extended-buffer")]
+    pub fn extended_copy(mut self, msg: &[u8]) -> Self {
+        let mut buf = [0u8; EXTENDED_CAPACITY];
+        let len = msg.len().min(EXTENDED_CAPACITY);
+        buf[..len].copy_from_slice(&msg[..len]);
+        self.1 = Some(Extended::Inline(buf, len as u8));
+        self
+    }
+
+    /// Set the location of the error within the program message
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.2 = Some(span);
+        self
+    }
+
+    /// Set the location of the error if not already set
+    pub fn or_span(mut self, span: Span) -> Self {
+        self.2 = self.2.or(Some(span));
         self
     }
 
@@ -64,8 +212,13 @@ impl Error {
     }
 
     /// Get extended message of error
-    pub fn get_extended(&self) -> Option<&'static [u8]> {
-        self.1
+    pub fn get_extended(&self) -> Option<&[u8]> {
+        self.1.as_ref().map(Extended::as_bytes)
+    }
+
+    /// Get location of the error within the program message, if known
+    pub fn get_span(&self) -> Option<Span> {
+        self.2
     }
 
     /**
@@ -1089,6 +1242,18 @@ impl ErrorQueue for alloc::vec::Vec<Error> {
     }
 }
 
+#[cfg(all(test, feature = "std"))]
+mod test_std_error {
+    use super::*;
+
+    #[test]
+    fn test_std_error() {
+        let err: std::boxed::Box<dyn std::error::Error> =
+            std::boxed::Box::new(Error::new(ErrorCode::SyntaxError));
+        assert_eq!(err.to_string(), "-102,\"Syntax error\"");
+    }
+}
+
 #[cfg(all(test, feature = "arrayvec"))]
 mod test_arrayvec_error_queue {
     use super::*;
@@ -1100,7 +1265,11 @@ mod test_arrayvec_error_queue {
         errors.push_back_error(Error::custom(1, b"Error").extended(b"Extended"));
         assert_eq!(
             errors.pop_front_error(),
-            Some(Error(ErrorCode::Custom(1, b"Error"), Some(b"Extended")))
+            Some(Error(
+                ErrorCode::Custom(1, b"Error"),
+                Some(Extended::Static(b"Extended")),
+                None
+            ))
         );
     }
 
@@ -1138,3 +1307,65 @@ mod test_arrayvec_error_queue {
         );
     }
 }
+
+#[cfg(all(test, feature = "extended-buffer"))]
+mod test_extended_buffer {
+    use super::*;
+
+    extern crate std;
+    use std::{format, vec::Vec};
+
+    #[test]
+    fn test_extended_fmt() {
+        let err = Error::new(ErrorCode::DataOutOfRange)
+            .extended_fmt(format_args!("Value {} exceeds max {}", 12.5, 10.0));
+        assert_eq!(err.get_extended(), Some(&b"Value 12.5 exceeds max 10"[..]));
+        assert_eq!(
+            format!("{err}"),
+            "-222,\"Data out of range;Value 12.5 exceeds max 10\""
+        );
+        // Compares equal to a static message
+        assert_eq!(
+            err,
+            ErrorCode::DataOutOfRange.extended(b"Value 12.5 exceeds max 10")
+        );
+    }
+
+    #[test]
+    fn test_extended_quotes() {
+        use crate::parser::response::ResponseData;
+
+        let err = Error::new(ErrorCode::IllegalParameterValue).extended_copy(b"channel \"A\" bad");
+        assert_eq!(
+            format!("{err}"),
+            "-224,\"Illegal parameter value;channel \"\"A\"\" bad\""
+        );
+        let mut buf = Vec::new();
+        err.format_response_data(&mut buf).unwrap();
+        assert_eq!(
+            buf,
+            b"-224,\"Illegal parameter value;channel \"\"A\"\" bad\""
+        );
+    }
+
+    #[test]
+    fn test_extended_truncate() {
+        let long = [b'x'; EXTENDED_CAPACITY + 10];
+        let err = Error::new(ErrorCode::ExecutionError).extended_copy(&long);
+        assert_eq!(err.get_extended(), Some(&long[..EXTENDED_CAPACITY]));
+
+        let err = Error::new(ErrorCode::ExecutionError)
+            .extended_fmt(format_args!("{}\u{e5}", "x".repeat(EXTENDED_CAPACITY - 1)));
+        assert_eq!(err.get_extended().unwrap().len(), EXTENDED_CAPACITY - 1);
+    }
+
+    #[test]
+    fn test_extended_queue() {
+        let mut errors: Vec<Error> = Vec::new();
+        errors.push_back_error(
+            Error::new(ErrorCode::IllegalParameterValue).extended_fmt(format_args!("CH{}", 3)),
+        );
+        let err = errors.pop_front_error().unwrap();
+        assert_eq!(err.get_extended(), Some(&b"CH3"[..]));
+    }
+}
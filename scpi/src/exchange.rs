@@ -0,0 +1,268 @@
+//! IEEE 488.2 message exchange protocol
+//!
+//! [MessageExchange] sits between a transport and a [Node] tree. It collects received bytes
+//! into program messages, executes them and holds their responses in an output queue until
+//! the controller reads them, see IEEE 488.2 chapter 6.
+//!
+//! The protocol violations it detects are reported to [Device::handle_error]:
+//! * `-410 Query INTERRUPTED`, a new program message was received before the response to
+//!   the previous one had been read. The unread response is discarded.
+//! * `-420 Query UNTERMINATED`, the controller tried to read a response but no complete
+//!   query has been received.
+//! * `-430 Query DEADLOCKED`, a response doesn't fit the output queue. Since the controller can
+//!   only read it once the whole program message has been executed neither side can proceed.
+//!   Execution stops and the response is discarded.
+//! * `-363 Input buffer overrun`, a program message doesn't fit the input buffer. The rest of
+//!   the message is discarded.
+//!
+//! ```
+//! # use scpi::{cmd_qonly, error::Result, tree::prelude::*, Leaf, Root};
+//! # use scpi::exchange::MessageExchange;
+//! # struct MyDevice;
+//! # impl Device for MyDevice { fn handle_error(&mut self, _err: Error) {} }
+//! # struct IdnCommand;
+//! # impl Command<MyDevice> for IdnCommand {
+//! #     cmd_qonly!();
+//! #     fn query(&self, _: &mut MyDevice, _: &mut Context, _: Parameters, mut response: ResponseUnit) -> Result<()> {
+//! #         response.data(b"ACME".as_slice()).finish()
+//! #     }
+//! # }
+//! # const TREE: &Node<MyDevice> = &Root![Leaf!(b"*IDN" => &IdnCommand)];
+//! let (mut input, mut output) = ([0u8; 64], [0u8; 64]);
+//! let mut exchange = MessageExchange::new(&mut input, &mut output);
+//! let (mut device, mut context) = (MyDevice, Context::default());
+//!
+//! exchange.receive(TREE, &mut device, &mut context, b"*IDN?\n", false);
+//! assert!(context.mav());
+//!
+//! let mut buf = [0u8; 4];
+//! let n = exchange.send(&mut device, &mut context, &mut buf);
+//! assert_eq!(&buf[..n], b"\"ACM");
+//! let n = exchange.send(&mut device, &mut context, &mut buf);
+//! assert_eq!(&buf[..n], b"E\"\n");
+//! assert!(!context.mav());
+//! ```
+
+use crate::error::{Error, ErrorCode};
+use crate::parser::message::message_len;
+use crate::parser::response::{Formatter, SliceFormatter};
+use crate::parser::tokenizer::Tokenizer;
+use crate::tree::Node;
+use crate::{Context, Device};
+
+/// State of the [MessageExchange]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum State {
+    /// Waiting for a program message
+    Idle,
+    /// Part of a program message has been received
+    Read,
+    /// A response is waiting to be read by the controller
+    Send,
+}
+
+/// Input buffer, output queue and protocol state of a message exchange.
+///
+/// See the [module](self) documentation.
+#[derive(Debug)]
+pub struct MessageExchange<'a> {
+    input: &'a mut [u8],
+    input_len: usize,
+    // Skipping the rest of a message which didn't fit
+    discard: bool,
+    // Terminator bytes seen at the end of the data skipped so far
+    discard_matched: usize,
+    output: SliceFormatter<'a>,
+    output_pos: usize,
+}
+
+impl<'a> MessageExchange<'a> {
+    /// Receive program messages into `input` and queue responses in `output`
+    pub fn new(input: &'a mut [u8], output: &'a mut [u8]) -> Self {
+        Self {
+            input,
+            input_len: 0,
+            discard: false,
+            discard_matched: 0,
+            output: SliceFormatter::new(output),
+            output_pos: 0,
+        }
+    }
+
+    /// Current state
+    pub fn state(&self) -> State {
+        if self.mav() {
+            State::Send
+        } else if self.input_len > 0 || self.discard {
+            State::Read
+        } else {
+            State::Idle
+        }
+    }
+
+    /// Returns true if the output queue contains a response (Message AVailable)
+    pub fn mav(&self) -> bool {
+        self.output_pos < self.output.len()
+    }
+
+    /// Process bytes received from the controller, executing every program message completed
+    /// by them. Set `end` if the last byte was sent with END, completing the program message.
+    ///
+    /// Errors are reported to [Device::handle_error] and [Context::mav] is kept up to date.
+    pub fn receive<D>(
+        &mut self,
+        tree: &Node<D>,
+        device: &mut D,
+        context: &mut Context,
+        mut data: &[u8],
+        end: bool,
+    ) where
+        D: Device,
+    {
+        while !data.is_empty() {
+            if self.discard {
+                data = self.skip_message(data, context);
+                continue;
+            }
+            if self.input_len == self.input.len() {
+                // More of the message arrived than fits
+                device.handle_error(Error::new(ErrorCode::InputBufferOverrun));
+                self.discard_matched = partial_terminator(self.input, context);
+                self.input_len = 0;
+                self.discard = true;
+                continue;
+            }
+            if self.input_len == 0 {
+                self.start_message(device);
+            }
+
+            let n = data.len().min(self.input.len() - self.input_len);
+            self.input[self.input_len..self.input_len + n].copy_from_slice(&data[..n]);
+            self.input_len += n;
+            data = &data[n..];
+
+            while let Some(len) = message_len(&self.input[..self.input_len], context.terminator) {
+                self.execute(tree, device, context, len);
+                if self.input_len > 0 {
+                    self.start_message(device);
+                }
+            }
+        }
+
+        if end {
+            if self.input_len > 0 {
+                self.execute(tree, device, context, self.input_len);
+            }
+            self.discard = false;
+            self.discard_matched = 0;
+        }
+        context.mav = self.mav();
+    }
+
+    /// Send queued response bytes to the controller, returning the number of bytes written
+    /// into `buf`. The whole response message has been sent once [Self::mav] returns false.
+    ///
+    /// Reports `-420 Query UNTERMINATED` and returns 0 if there is nothing to send.
+    pub fn send<D>(&mut self, device: &mut D, context: &mut Context, buf: &mut [u8]) -> usize
+    where
+        D: Device,
+    {
+        if !self.mav() {
+            device.handle_error(Error::new(ErrorCode::QueryUnterminated));
+            return 0;
+        }
+        let pending = &self.output.as_slice()[self.output_pos..];
+        let n = pending.len().min(buf.len());
+        buf[..n].copy_from_slice(&pending[..n]);
+        self.output_pos += n;
+        if !self.mav() {
+            self.clear_output();
+        }
+        context.mav = self.mav();
+        n
+    }
+
+    /// Device clear (e.g. IEEE 488.1 DCL/SDC), discards the input buffer and output queue.
+    pub fn clear(&mut self, context: &mut Context) {
+        self.input_len = 0;
+        self.discard = false;
+        self.discard_matched = 0;
+        self.clear_output();
+        context.mav = false;
+    }
+
+    fn clear_output(&mut self) {
+        self.output.clear();
+        self.output_pos = 0;
+    }
+
+    /// A new program message is starting
+    fn start_message<D>(&mut self, device: &mut D)
+    where
+        D: Device,
+    {
+        if self.mav() {
+            self.clear_output();
+            device.handle_error(Error::new(ErrorCode::QueryInterrupted));
+        }
+    }
+
+    /// Skip to the end of a message which didn't fit the input buffer. The terminator may be
+    /// split across several calls.
+    fn skip_message<'d>(&mut self, data: &'d [u8], context: &Context) -> &'d [u8] {
+        let terminator = context.terminator.as_bytes();
+        if terminator.is_empty() {
+            return &[];
+        }
+        for (i, &b) in data.iter().enumerate() {
+            self.discard_matched = if b == terminator[self.discard_matched] {
+                self.discard_matched + 1
+            } else if b == terminator[0] {
+                1
+            } else {
+                0
+            };
+            if self.discard_matched == terminator.len() {
+                self.discard = false;
+                self.discard_matched = 0;
+                return &data[i + 1..];
+            }
+        }
+        &[]
+    }
+
+    /// Execute the first `len` bytes of the input buffer
+    fn execute<D>(&mut self, tree: &Node<D>, device: &mut D, context: &mut Context, len: usize)
+    where
+        D: Device,
+    {
+        // Any previous response was discarded when the message started
+        self.clear_output();
+        context.mav = false;
+        let mut tokenizer = Tokenizer::new(&self.input[..len])
+            .terminator(context.terminator)
+            .config(context.config);
+        let mut res = tree.run_tokens(device, context, &mut tokenizer, &mut self.output);
+        if self.output.is_truncated() {
+            // The response can only be read once the message has been executed
+            res = Err(Error::new(ErrorCode::QueryDeadlocked));
+        }
+        if let Err(err) = res {
+            // Like Node::run_messages, a failed message doesn't respond
+            self.clear_output();
+            device.handle_error(err);
+        }
+        self.input.copy_within(len..self.input_len, 0);
+        self.input_len -= len;
+        context.mav = self.mav();
+    }
+}
+
+/// Number of bytes at the end of `input` which start the terminator
+fn partial_terminator(input: &[u8], context: &Context) -> usize {
+    let terminator = context.terminator.as_bytes();
+    (1..terminator.len())
+        .rev()
+        .find(|&n| input.ends_with(&terminator[..n]))
+        .unwrap_or(0)
+}
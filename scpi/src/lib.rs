@@ -49,6 +49,8 @@
 //!
 //! User commands will often use functions which may return an error, these should mostly be propagated down to the parser by rusts `?` operator.
 //!
+//! Errors carry the location within the program message where they were found when possible, see [error::Error::get_span].
+//!
 //!
 //! # Limitations and differences
 //! * Overlapping commands are not supported, [Github issue](https://github.com/Atmelfan/scpi-rs/issues/23).
@@ -68,9 +70,13 @@ extern crate alloc;
 extern crate std as alloc;
 
 use crate::error::Error;
+use crate::parser::message::Terminator;
+use crate::parser::response::Precision;
+use crate::parser::ParserConfig;
 use core::any::Any;
 
 pub mod error;
+pub mod exchange;
 pub mod option;
 pub mod parser;
 pub mod tree;
@@ -124,6 +130,12 @@ pub mod units {
 pub trait Device {
     /// Called when the parser encounters a syntax error or a command handler returns an error.
     fn handle_error(&mut self, err: Error);
+
+    /// Precision of reals in query responses which aren't formatted with one of their own, see
+    /// [ResponseUnit::precision](crate::parser::response::ResponseUnit::precision).
+    fn precision(&self) -> Precision {
+        Precision::Shortest
+    }
 }
 
 /// Context in which to execute a message.
@@ -132,6 +144,8 @@ pub trait Device {
 #[derive(Debug)]
 pub struct Context<'a> {
     /// Does output buffer contain data?
+    ///
+    /// Kept up to date by [exchange::MessageExchange].
     pub mav: bool,
 
     /// User context data.
@@ -142,6 +156,12 @@ pub struct Context<'a> {
     /// For example: User authentication information if the call comes from an authenticated interface
     /// or port number if the call comes from a serial port.
     pub user: &'a dyn Any,
+
+    /// Terminator of program and response messages, NL unless the source requires otherwise.
+    pub terminator: Terminator,
+
+    /// How strictly program messages are parsed.
+    pub config: ParserConfig,
 }
 
 impl<'a> Default for Context<'a> {
@@ -156,12 +176,19 @@ impl<'a> Context<'a> {
         Context {
             mav: false,
             user: &(),
+            terminator: Terminator::default(),
+            config: ParserConfig::default(),
         }
     }
 
     // Create a new context with user data
     pub fn new_with_user(user: &'a dyn Any) -> Self {
-        Context { mav: false, user }
+        Context {
+            mav: false,
+            user,
+            terminator: Terminator::default(),
+            config: ParserConfig::default(),
+        }
     }
 
     /// Get user context data.
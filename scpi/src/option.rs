@@ -1,5 +1,8 @@
 //! Support for SCPI style enums.
 
+use crate::error::Result;
+use crate::parser::response::Formatter;
+
 /// Common trait for getting an enum variant fom its `MNEMonic` and back.
 ///
 /// Usually derived by [scpi_derive::ScpiEnum] like below:
@@ -21,8 +24,58 @@
 /// assert_eq!(MyEnum::Binary.short_form(), b"BIN");
 /// ```
 ///
+/// A variant may have several mnemonics, the first one is returned by [ScpiEnum::mnemonic] and
+/// the rest are accepted as aliases. Responses use the short form unless the enum is marked
+/// with `#[scpi(response = long)]`:
+/// ```
+/// # use crate::scpi::option::ScpiEnum;
+/// #[derive(Copy, Clone, PartialEq, Debug, scpi_derive::ScpiEnum)]
+/// #[scpi(response = long)]
+/// enum Source {
+///     #[scpi(mnemonic = b"INTernal", mnemonic = b"IMMediate")]
+///     Internal,
+///     #[scpi(mnemonic = b"EXTernal")]
+///     External,
+/// }
+///
+/// assert_eq!(Source::from_mnemonic(b"imm"), Some(Source::Internal));
+/// assert_eq!(Source::Internal.response_mnemonic(), b"INTERNAL");
+/// ```
+///
+/// Parameters like `RANGe AUTO|<value>` can have one variant marked `#[scpi(numeric)]`
+/// instead of a mnemonic, parsed from non-character program data by the `TryFrom<Token>` of its
+/// field. A `#[scpi(fallback)]` variant also gets character data matching no mnemonic, e.g.
+/// `ON|OFF` for a `bool`. Either is formatted through the `ResponseData` of its field, so
+/// [ScpiEnum::format_response] rather than a mnemonic round-trips it.
+/// ```
+/// # use scpi::{option::ScpiEnum, parser::tokenizer::Token};
+/// #[derive(Copy, Clone, PartialEq, Debug, scpi_derive::ScpiEnum)]
+/// enum Range {
+///     #[scpi(mnemonic = b"AUTO")]
+///     Auto,
+///     #[scpi(numeric)]
+///     Value(f32),
+/// }
 ///
+/// assert_eq!(Range::try_from(Token::CharacterProgramData(b"auto")), Ok(Range::Auto));
+/// assert_eq!(
+///     Range::try_from(Token::DecimalNumericProgramData(b"2.5")),
+///     Ok(Range::Value(2.5))
+/// );
+/// ```
 ///
+/// Variants whose mnemonics can match the same input fail to compile, since which one is
+/// picked would depend on declaration order:
+/// ```compile_fail
+/// #[derive(scpi_derive::ScpiEnum)]
+/// enum Ambiguous {
+///     #[scpi(mnemonic = b"CURRent")]
+///     Current,
+///     // Both match `CURR`
+///     #[scpi(mnemonic = b"CURR")]
+///     Curr,
+/// }
+/// ```
 pub trait ScpiEnum
 where
     Self: Sized,
@@ -31,11 +84,15 @@ where
     fn from_mnemonic(s: &[u8]) -> Option<Self>;
 
     /// Return full `MNEMonic` of enum variant.
+    ///
+    /// Empty for a derived `numeric` or `fallback` variant, which has no mnemonic of its own.
+    /// Passing it to [ScpiEnum::from_mnemonic] does not return the variant, use
+    /// [ScpiEnum::format_response] to respond with its value instead.
     fn mnemonic(&self) -> &'static [u8];
 
     /// Get the mnemonic short form
     ///
-    /// Example: 'MNEMonic' would return 'MNEM'
+    /// Example: 'MNEMonic' would return 'MNEM'. Empty if [ScpiEnum::mnemonic] is.
     fn short_form(&self) -> &'static [u8] {
         let mnemonic = self.mnemonic();
         let len = mnemonic
@@ -44,6 +101,26 @@ where
             .count();
         &mnemonic[..len]
     }
+
+    /// Mnemonic used when the variant is formatted as response data.
+    ///
+    /// Defaults to [ScpiEnum::short_form], derived enums respond with the short form (keeping
+    /// any numeric suffix) or the uppercase long form with `#[scpi(response = long)]`.
+    ///
+    /// Empty for a derived `numeric` or `fallback` variant, see [ScpiEnum::mnemonic].
+    fn response_mnemonic(&self) -> &'static [u8] {
+        self.short_form()
+    }
+
+    /// Format the variant as response data, used by the derived
+    /// [ResponseData](crate::parser::response::ResponseData) implementation. A manual
+    /// implementation of this trait should implement `ResponseData` by calling this too.
+    ///
+    /// Defaults to the [ScpiEnum::response_mnemonic], derived enums format the value of a
+    /// `numeric` or `fallback` variant instead.
+    fn format_response(&self, formatter: &mut dyn Formatter) -> Result<()> {
+        formatter.push_str(self.response_mnemonic())
+    }
 }
 
 #[cfg(test)]
@@ -99,6 +176,133 @@ mod tests {
         assert_eq!(MyEnum::L125.short_form(), b"L125");
     }
 
+    #[derive(Copy, Clone, PartialEq, Debug, scpi_derive::ScpiEnum)]
+    #[scpi(response = long)]
+    enum Source {
+        #[scpi(mnemonic = b"INTernal1", mnemonic = b"IMMediate")]
+        Internal1,
+        #[scpi(mnemonic = b"INTernal2")]
+        Internal2,
+        #[scpi(mnemonic = b"EXTernal")]
+        #[scpi(mnemonic = b"TTL")]
+        External,
+    }
+
+    #[test]
+    fn test_aliases() {
+        assert_eq!(Source::from_mnemonic(b"int"), Some(Source::Internal1));
+        assert_eq!(Source::from_mnemonic(b"immediate"), Some(Source::Internal1));
+        assert_eq!(Source::from_mnemonic(b"int2"), Some(Source::Internal2));
+        assert_eq!(Source::from_mnemonic(b"ext"), Some(Source::External));
+        assert_eq!(Source::from_mnemonic(b"ttl"), Some(Source::External));
+        assert_eq!(Source::Internal1.mnemonic(), b"INTernal1");
+        assert_eq!(Source::External.mnemonic(), b"EXTernal");
+    }
+
+    #[test]
+    fn test_response_mnemonic() {
+        assert_eq!(MyEnum::Binary.response_mnemonic(), b"BIN");
+        assert_eq!(MyEnum::Ascii2.response_mnemonic(), b"ASC2");
+        assert_eq!(MyEnum::L125.response_mnemonic(), b"L125");
+        assert_eq!(Source::Internal2.response_mnemonic(), b"INTERNAL2");
+        assert_eq!(Source::External.response_mnemonic(), b"EXTERNAL");
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug, scpi_derive::ScpiEnum)]
+    enum TriggerSource {
+        #[scpi(mnemonic = b"BUS")]
+        Bus,
+        #[scpi(mnemonic = b"EXTernal")]
+        External,
+        #[scpi(numeric)]
+        Channel(u8),
+    }
+
+    #[derive(Copy, Clone, PartialEq, Debug, scpi_derive::ScpiEnum)]
+    #[scpi(response = long)]
+    enum OutputState {
+        #[scpi(mnemonic = b"TOGGle")]
+        Toggle,
+        #[scpi(fallback)]
+        State(bool),
+    }
+
+    #[test]
+    fn test_numeric() {
+        assert_eq!(
+            TriggerSource::try_from(Token::CharacterProgramData(b"bus")),
+            Ok(TriggerSource::Bus)
+        );
+        assert_eq!(
+            TriggerSource::try_from(Token::DecimalNumericProgramData(b"3")),
+            Ok(TriggerSource::Channel(3))
+        );
+        assert_eq!(
+            TriggerSource::try_from(Token::NonDecimalNumericProgramData(2)),
+            Ok(TriggerSource::Channel(2))
+        );
+        // Errors come from the field
+        assert_eq!(
+            TriggerSource::try_from(Token::DecimalNumericProgramData(b"300")),
+            Err(ErrorCode::DataOutOfRange.into())
+        );
+        assert_eq!(
+            TriggerSource::try_from(Token::CharacterProgramData(b"potato")),
+            Err(ErrorCode::IllegalParameterValue.into())
+        );
+        assert_eq!(
+            TriggerSource::from_mnemonic(b"ext"),
+            Some(TriggerSource::External)
+        );
+        assert_eq!(TriggerSource::Channel(1).mnemonic(), b"");
+        assert_eq!(TriggerSource::Channel(1).response_mnemonic(), b"");
+        assert_eq!(TriggerSource::from_mnemonic(b""), None);
+    }
+
+    #[test]
+    fn test_fallback() {
+        assert_eq!(
+            OutputState::try_from(Token::CharacterProgramData(b"TOGG")),
+            Ok(OutputState::Toggle)
+        );
+        assert_eq!(
+            OutputState::try_from(Token::CharacterProgramData(b"on")),
+            Ok(OutputState::State(true))
+        );
+        assert_eq!(
+            OutputState::try_from(Token::DecimalNumericProgramData(b"0")),
+            Ok(OutputState::State(false))
+        );
+        assert_eq!(
+            OutputState::try_from(Token::CharacterProgramData(b"potato")),
+            Err(ErrorCode::IllegalParameterValue.into())
+        );
+        assert_eq!(OutputState::from_mnemonic(b"on"), None);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_format_response() {
+        extern crate std;
+        use crate::parser::response::ResponseData;
+
+        fn format(data: impl ResponseData) -> std::vec::Vec<u8> {
+            let mut buf = std::vec::Vec::new();
+            data.format_response_data(&mut buf).unwrap();
+            buf
+        }
+        assert_eq!(format(TriggerSource::Bus), b"BUS");
+        assert_eq!(format(TriggerSource::Channel(4)), b"4");
+        assert_eq!(format(OutputState::Toggle), b"TOGGLE");
+        assert_eq!(format(OutputState::State(true)), b"1");
+        assert_eq!(
+            format(crate::parser::format::List(
+                [TriggerSource::Bus, TriggerSource::Channel(4)].iter()
+            )),
+            b"BUS,4"
+        );
+    }
+
     #[test]
     fn test_enum_types() {
         assert_eq!(
@@ -0,0 +1,293 @@
+//! Streaming <ARBITRARY BLOCK PROGRAM DATA>
+//!
+//! Block data is normally returned as a single slice which requires the whole block to be
+//! present in the input buffer. When executing a program message with
+//! [Node::run_stream](crate::tree::Node::run_stream) a block extending past the end of the
+//! buffer is instead read by the command handler through a [BlockReader] while the rest of
+//! the payload is pulled from the transport.
+//!
+//! See IEEE 488.2-1992 7.7.6
+
+use crate::error::{Error, ErrorCode, Result};
+
+/// A source of bytes, typically the transport a program message is received over.
+pub trait Read {
+    /// Read bytes into `buf`, returning how many bytes were read.
+    ///
+    /// Must return `Ok(0)` only when the end of the program message (END) has been reached
+    /// or `buf` is empty.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+}
+
+impl Read for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = buf.len().min(self.len());
+        let (head, tail) = self.split_at(n);
+        buf[..n].copy_from_slice(head);
+        *self = tail;
+        Ok(n)
+    }
+}
+
+/// Reader for the payload of a <ARBITRARY BLOCK PROGRAM DATA> element.
+///
+/// Returned by [Parameters::next_block](crate::parser::parameters::Parameters::next_block) for
+/// both definite (`#<n><length><payload>`) and indefinite (`#0<payload><NL^END>`) blocks.
+/// Any part of the payload not read by the handler is discarded before the rest of the
+/// message is parsed.
+///
+/// ```
+/// # use scpi::parser::block::{BlockReader, Read};
+/// let mut block = BlockReader::new(b"HELLO");
+/// let mut buf = [0u8; 4];
+/// assert_eq!(block.read(&mut buf), Ok(4));
+/// assert_eq!(block.read(&mut buf), Ok(1));
+/// assert_eq!(block.read(&mut buf), Ok(0));
+/// ```
+pub struct BlockReader<'a> {
+    data: &'a [u8],
+    len: Option<usize>,
+    stream: Option<&'a mut dyn BlockSource>,
+}
+
+impl<'a> BlockReader<'a> {
+    /// Create a reader over a block payload which is entirely in memory.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            len: Some(data.len()),
+            stream: None,
+        }
+    }
+
+    /// Create a reader over a payload starting with `data` and continuing in `stream`.
+    pub(crate) fn streamed(
+        data: &'a [u8],
+        len: Option<usize>,
+        stream: &'a mut dyn BlockSource,
+    ) -> Self {
+        Self {
+            data,
+            len,
+            stream: Some(stream),
+        }
+    }
+
+    /// Total length of the payload, or [None] for an indefinite block.
+    pub fn payload_len(&self) -> Option<usize> {
+        self.len
+    }
+}
+
+impl<'a> Read for BlockReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if !self.data.is_empty() {
+            self.data.read(buf)
+        } else if let Some(stream) = &mut self.stream {
+            stream.read(buf)
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+/// Payload of a streamed block remaining in the transport
+pub(crate) trait BlockSource {
+    /// Start reading a block with `remaining` bytes left in the transport
+    /// (or [None] if indefinite) and an optional byte already taken from the buffer.
+    fn begin(&mut self, remaining: Option<usize>, held: Option<u8>);
+
+    /// Read payload bytes, returns `Ok(0)` at the end of the payload.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Payload {
+    /// Bytes left of a definite block
+    Definite(usize),
+    /// A byte read but not yet returned. A held NL is the terminator if followed by END.
+    Indefinite(Option<u8>),
+    /// End of payload
+    Done,
+}
+
+/// Tracks the payload of a streamed block within a transport.
+pub(crate) struct BlockStream<'s> {
+    source: &'s mut dyn Read,
+    payload: Option<Payload>,
+}
+
+impl<'s> BlockStream<'s> {
+    pub(crate) fn new(source: &'s mut dyn Read) -> Self {
+        Self {
+            source,
+            payload: None,
+        }
+    }
+
+    /// Skip any unread payload of the current block, if any.
+    pub(crate) fn finish(&mut self) -> Result<()> {
+        let mut buf = [0u8; 64];
+        while BlockSource::read(self, &mut buf)? > 0 {}
+        self.payload = None;
+        Ok(())
+    }
+
+    /// Read the rest of the program message into `buf`.
+    pub(crate) fn read_rest<'b>(&mut self, buf: &'b mut [u8]) -> Result<&'b [u8]> {
+        let mut len = 0;
+        loop {
+            if len == buf.len() {
+                // Only an overrun if more of the message arrives
+                return match self.source.read(&mut [0u8])? {
+                    0 => Ok(&buf[..len]),
+                    _ => Err(ErrorCode::InputBufferOverrun.into()),
+                };
+            }
+            match self.source.read(&mut buf[len..])? {
+                0 => return Ok(&buf[..len]),
+                n => len += n,
+            }
+        }
+    }
+
+    /// Discard the rest of the program message after an error.
+    pub(crate) fn discard(&mut self) {
+        let mut buf = [0u8; 64];
+        while matches!(self.source.read(&mut buf), Ok(n) if n > 0) {}
+        self.payload = None;
+    }
+}
+
+impl<'s> BlockSource for BlockStream<'s> {
+    fn begin(&mut self, remaining: Option<usize>, held: Option<u8>) {
+        self.payload = Some(match remaining {
+            Some(remaining) => Payload::Definite(remaining),
+            None => Payload::Indefinite(held),
+        });
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.payload {
+                None | Some(Payload::Done) | Some(Payload::Definite(0)) => return Ok(0),
+                Some(Payload::Definite(remaining)) => {
+                    let len = remaining.min(buf.len());
+                    let n = self.source.read(&mut buf[..len])?;
+                    if n == 0 {
+                        // END before the end of the payload
+                        return Err(Error::new(ErrorCode::InvalidBlockData));
+                    }
+                    self.payload = Some(Payload::Definite(remaining - n));
+                    return Ok(n);
+                }
+                Some(Payload::Indefinite(None)) => {
+                    let n = self.source.read(buf)?;
+                    if n == 0 {
+                        // Indefinite block data must be terminated with NL before END
+                        return Err(Error::new(ErrorCode::InvalidBlockData));
+                    }
+                    if buf[n - 1] != b'\n' {
+                        return Ok(n);
+                    }
+                    // Hold back a trailing NL until it's known whether END follows
+                    self.payload = Some(Payload::Indefinite(Some(b'\n')));
+                    if n > 1 {
+                        return Ok(n - 1);
+                    }
+                }
+                Some(Payload::Indefinite(Some(c))) => {
+                    // Look ahead past the held byte
+                    let mut next = [0u8];
+                    let n = if buf.len() > 1 {
+                        self.source.read(&mut buf[1..])?
+                    } else {
+                        self.source.read(&mut next)?
+                    };
+                    if n == 0 {
+                        return if c == b'\n' {
+                            self.payload = Some(Payload::Done);
+                            Ok(0)
+                        } else {
+                            Err(Error::new(ErrorCode::InvalidBlockData))
+                        };
+                    }
+                    buf[0] = c;
+                    if buf.len() == 1 {
+                        self.payload = Some(Payload::Indefinite(Some(next[0])));
+                        return Ok(1);
+                    }
+                    let len = n + 1;
+                    if buf[len - 1] == b'\n' {
+                        self.payload = Some(Payload::Indefinite(Some(b'\n')));
+                        return Ok(len - 1);
+                    }
+                    self.payload = Some(Payload::Indefinite(None));
+                    return Ok(len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(source: &mut dyn BlockSource, chunk: usize) -> Result<([u8; 32], usize)> {
+        let mut out = [0u8; 32];
+        let mut len = 0;
+        loop {
+            match source.read(&mut out[len..len + chunk])? {
+                0 => return Ok((out, len)),
+                n => len += n,
+            }
+        }
+    }
+
+    #[test]
+    fn test_definite() {
+        let mut source = &b"HELLO;*OPC"[..];
+        let mut stream = BlockStream::new(&mut source);
+        stream.begin(Some(5), None);
+        let (out, len) = read_all(&mut stream, 2).unwrap();
+        assert_eq!(&out[..len], b"HELLO");
+        stream.finish().unwrap();
+        assert_eq!(source, b";*OPC");
+    }
+
+    #[test]
+    fn test_definite_short() {
+        let mut source = &b"HEL"[..];
+        let mut stream = BlockStream::new(&mut source);
+        stream.begin(Some(5), None);
+        assert_eq!(stream.finish(), Err(ErrorCode::InvalidBlockData.into()));
+    }
+
+    #[test]
+    fn test_indefinite() {
+        for chunk in 1..4 {
+            let mut source = &b"A\n\nB\n"[..];
+            let mut stream = BlockStream::new(&mut source);
+            stream.begin(None, None);
+            let (out, len) = read_all(&mut stream, chunk).unwrap();
+            assert_eq!(&out[..len], b"A\n\nB");
+        }
+        // NL taken from the buffer followed by END
+        let mut source = &b""[..];
+        let mut stream = BlockStream::new(&mut source);
+        stream.begin(None, Some(b'\n'));
+        assert_eq!(stream.finish(), Ok(()));
+    }
+
+    #[test]
+    fn test_indefinite_unterminated() {
+        let mut source = &b"AB"[..];
+        let mut stream = BlockStream::new(&mut source);
+        stream.begin(None, None);
+        assert_eq!(stream.finish(), Err(ErrorCode::InvalidBlockData.into()));
+    }
+}
@@ -0,0 +1,348 @@
+//! Program message builder
+//!
+//! Builds program messages on the host side, the inverse of
+//! [Parameters](crate::parser::parameters::Parameters). Data is formatted like
+//! [ResponseData] so anything a device can respond with can also be sent to it.
+//!
+//! ```
+//! # use scpi::parser::{builder::{ListItem, MessageBuilder}, format::Hex, message::Terminator};
+//! let mut msg = Vec::new();
+//! MessageBuilder::new(&mut msg)
+//!     .command(b"SOUR:VOLT")
+//!     .suffixed(1.5, b"MV")
+//!     .command(b"SYST:TEXT")
+//!     .string(b"say \"hi\"")
+//!     .data(Hex(0xABu8))
+//!     .query(b"MEAS:VOLT")
+//!     .channel_list(&[ListItem::Single([1]), ListItem::Range([2], [4])])
+//!     .finish(Terminator::Nl)
+//!     .unwrap();
+//! assert_eq!(
+//!     msg,
+//!     b"SOUR:VOLT 1.5MV;SYST:TEXT \"say \"\"hi\"\"\",#HAB;MEAS:VOLT? (@1,2:4)\n"
+//! );
+//! ```
+
+use crate::error::{ErrorCode, Result};
+use crate::parser::format::Arbitrary;
+use crate::parser::message::Terminator;
+use crate::parser::response::{Formatter, ResponseData};
+use crate::parser::tokenizer::{Token, Tokenizer};
+
+/// An element of a numeric or channel list
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ListItem<T> {
+    /// A single value, e.g. `1`
+    Single(T),
+    /// An inclusive range, e.g. `1:5`
+    Range(T, T),
+}
+
+/// Appends program message units to a [Formatter].
+///
+/// Like [ResponseUnit](crate::parser::response::ResponseUnit) the first error is kept and
+/// returned by [Self::finish], later calls do nothing.
+pub struct MessageBuilder<'a> {
+    fmt: &'a mut dyn Formatter,
+    result: Result<()>,
+    has_unit: bool,
+    has_data: bool,
+}
+
+impl<'a> MessageBuilder<'a> {
+    /// Append a program message to `fmt`
+    pub fn new(fmt: &'a mut dyn Formatter) -> Self {
+        Self {
+            fmt,
+            result: Ok(()),
+            has_unit: false,
+            has_data: false,
+        }
+    }
+
+    /// Start a command message unit with `header`, e.g. `SOUR:VOLT` or `*RST`.
+    ///
+    /// Returns [CommandHeaderError](ErrorCode::CommandHeaderError) if `header` is not a
+    /// valid program header.
+    pub fn command(&mut self, header: &[u8]) -> &mut Self {
+        self.unit(header, false)
+    }
+
+    /// Start a query message unit with `header`, the `?` is added.
+    ///
+    /// See [Self::command].
+    pub fn query(&mut self, header: &[u8]) -> &mut Self {
+        self.unit(header, true)
+    }
+
+    fn unit(&mut self, header: &[u8], query: bool) -> &mut Self {
+        let valid = !header.is_empty()
+            && Tokenizer::new(header).all(|tok| {
+                matches!(
+                    tok,
+                    Ok(Token::ProgramMnemonic(_) | Token::HeaderMnemonicSeparator)
+                )
+            });
+        self.result = self.result.and_then(|_| {
+            if !valid {
+                return Err(ErrorCode::CommandHeaderError.into());
+            }
+            if self.has_unit {
+                self.fmt.push_byte(b';')?;
+            }
+            self.fmt.push_str(header)?;
+            if query {
+                self.fmt.push_byte(b'?')?;
+            }
+            Ok(())
+        });
+        self.has_unit = true;
+        self.has_data = false;
+        self
+    }
+
+    /// Append program data, formatted as response data.
+    ///
+    /// Numbers are written as NR1 or NR3, see [crate::parser::format] for non-decimal numerics,
+    /// blocks, character and expression data.
+    ///
+    /// Empty data is skipped like in a response, see [ResponseData].
+    ///
+    /// Data before the first [`Self::command`] or [`Self::query`] has no header, which returns
+    /// [CommandHeaderError](ErrorCode::CommandHeaderError).
+    pub fn data<U>(&mut self, data: U) -> &mut Self
+    where
+        U: ResponseData,
+    {
+        if data.is_empty() {
+            return self;
+        }
+        self.element(|fmt| data.format_response_data(fmt))
+    }
+
+    /// Append a decimal numeric followed by a suffix, e.g. `1.5MV`.
+    ///
+    /// Returns [InvalidSuffix](ErrorCode::InvalidSuffix) if `suffix` is not a valid suffix.
+    pub fn suffixed<U>(&mut self, data: U, suffix: &[u8]) -> &mut Self
+    where
+        U: ResponseData,
+    {
+        self.element(|fmt| {
+            let valid = !suffix.is_empty()
+                && suffix.len() <= 12
+                && suffix
+                    .iter()
+                    .all(|c| c.is_ascii_alphanumeric() || b"/.-".contains(c))
+                && (suffix[0].is_ascii_alphabetic() || suffix[0] == b'/');
+            if !valid {
+                return Err(ErrorCode::InvalidSuffix.into());
+            }
+            data.format_response_data(fmt)?;
+            // Don't mistake the suffix for an exponent
+            if suffix[0].eq_ignore_ascii_case(&b'E') {
+                fmt.push_byte(b' ')?;
+            }
+            fmt.push_str(suffix)
+        })
+    }
+
+    /// Append <STRING PROGRAM DATA>, embedded quotes are doubled.
+    pub fn string(&mut self, s: &[u8]) -> &mut Self {
+        self.element(|fmt| s.format_response_data(fmt))
+    }
+
+    /// Append definite length <ARBITRARY BLOCK PROGRAM DATA>
+    pub fn block(&mut self, data: &[u8]) -> &mut Self {
+        self.element(|fmt| Arbitrary(data).format_response_data(fmt))
+    }
+
+    /// Append a numeric list, e.g. `(1,3:5)`
+    pub fn numeric_list<U>(&mut self, list: &[ListItem<U>]) -> &mut Self
+    where
+        U: ResponseData,
+    {
+        self.element(|fmt| {
+            fmt.push_byte(b'(')?;
+            push_list(list, fmt, |x, fmt| x.format_response_data(fmt))?;
+            fmt.push_byte(b')')
+        })
+    }
+
+    /// Append a channel list of channels with one or more dimensions,
+    /// e.g. `(@1!1,2!1:2!4)`
+    pub fn channel_list<C>(&mut self, list: &[ListItem<C>]) -> &mut Self
+    where
+        C: AsRef<[isize]>,
+    {
+        self.element(|fmt| {
+            fmt.push_str(b"(@")?;
+            push_list(list, fmt, |channel, fmt| {
+                let channel = channel.as_ref();
+                if channel.is_empty() {
+                    return Err(ErrorCode::DataOutOfRange.into());
+                }
+                for (i, x) in channel.iter().enumerate() {
+                    if i > 0 {
+                        fmt.push_byte(b'!')?;
+                    }
+                    x.format_response_data(fmt)?;
+                }
+                Ok(())
+            })?;
+            fmt.push_byte(b')')
+        })
+    }
+
+    fn element(&mut self, f: impl FnOnce(&mut dyn Formatter) -> Result<()>) -> &mut Self {
+        self.result = self.result.and_then(|_| {
+            if !self.has_unit {
+                return Err(ErrorCode::CommandHeaderError.into());
+            }
+            if self.has_data {
+                self.fmt.data_separator()?;
+            } else {
+                self.fmt.header_separator()?;
+            }
+            f(self.fmt)
+        });
+        self.has_data = true;
+        self
+    }
+
+    /// Terminate the program message and return any error
+    pub fn finish(&mut self, terminator: Terminator) -> Result<()> {
+        self.result = self
+            .result
+            .and_then(|_| self.fmt.push_str(terminator.as_bytes()));
+        self.result
+    }
+}
+
+fn push_list<T>(
+    list: &[ListItem<T>],
+    fmt: &mut dyn Formatter,
+    mut item: impl FnMut(&T, &mut dyn Formatter) -> Result<()>,
+) -> Result<()> {
+    for (i, x) in list.iter().enumerate() {
+        if i > 0 {
+            fmt.push_byte(b',')?;
+        }
+        match x {
+            ListItem::Single(x) => item(x, fmt)?,
+            ListItem::Range(begin, end) => {
+                item(begin, fmt)?;
+                fmt.push_byte(b':')?;
+                item(end, fmt)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::parser::expression::{channel_list, numeric_list};
+    use crate::parser::format::{Binary, Octal};
+
+    extern crate std;
+
+    #[test]
+    fn test_headers() {
+        let mut msg = std::vec::Vec::new();
+        MessageBuilder::new(&mut msg)
+            .command(b"*RST")
+            .query(b":SYST:ERR")
+            .command(b"OUTP2")
+            .data(true)
+            .finish(Terminator::CrNl)
+            .unwrap();
+        assert_eq!(msg, b"*RST;:SYST:ERR?;OUTP2 1\r\n");
+
+        for header in [
+            &b""[..],
+            b"SYST ERR",
+            b"VOLT?",
+            b"SYST::ERR",
+            b"1VOLT",
+            b"VOLT;*RST",
+        ] {
+            assert_eq!(
+                MessageBuilder::new(&mut std::vec::Vec::new())
+                    .command(header)
+                    .data(1)
+                    .finish(Terminator::Nl),
+                Err(ErrorCode::CommandHeaderError.into()),
+                "{header:?}"
+            );
+        }
+
+        // Data needs a header
+        let mut msg = std::vec::Vec::new();
+        assert_eq!(
+            MessageBuilder::new(&mut msg)
+                .data(1)
+                .command(b"*RST")
+                .finish(Terminator::Nl),
+            Err(ErrorCode::CommandHeaderError.into())
+        );
+        assert!(msg.is_empty());
+    }
+
+    #[test]
+    fn test_data() {
+        let mut msg = std::vec::Vec::new();
+        MessageBuilder::new(&mut msg)
+            .command(b"X")
+            .data(-1i32)
+            .data(2.5f64)
+            .suffixed(1u8, b"mV")
+            .suffixed(3u8, b"EV")
+            .data(Octal(8u8))
+            .data(Binary(5u8))
+            .string(b"it's \"quoted\"")
+            .block(b"\x00\n;")
+            .numeric_list(&[ListItem::Single(1.5), ListItem::Range(-2.0, 2.0)])
+            .channel_list(&[ListItem::Single([1, 2]), ListItem::Range([3, 1], [3, 4])])
+            .finish(Terminator::End)
+            .unwrap();
+        assert_eq!(
+            msg,
+            b"X -1,2.5,1mV,3 EV,#Q10,#B101,\"it's \"\"quoted\"\"\",#13\x00\n;,(1.5,-2.0:2.0),(@1!2,3!1:3!4)"
+                .to_vec()
+        );
+
+        // Parses back
+        let mut tokens = Tokenizer::new(&msg);
+        let mut data = std::vec::Vec::new();
+        for tok in tokens.by_ref() {
+            match tok.unwrap() {
+                tok if tok.is_data() => data.push(tok),
+                _ => {}
+            }
+        }
+        assert_eq!(data[4], Token::NonDecimalNumericProgramData(8));
+        assert_eq!(data[7], Token::ArbitraryBlockData(b"\x00\n;"));
+        let Token::ExpressionProgramData(list) = data[8] else {
+            panic!()
+        };
+        assert_eq!(numeric_list::NumericList::new(list).count(), 2);
+        let Token::ExpressionProgramData(list) = data[9] else {
+            panic!()
+        };
+        assert_eq!(channel_list::ChannelList::new(list).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut msg = std::vec::Vec::new();
+        let res = MessageBuilder::new(&mut msg)
+            .command(b"X")
+            .suffixed(1, b"1V")
+            .data(2)
+            .finish(Terminator::Nl);
+        assert_eq!(res, Err(Error::new(ErrorCode::InvalidSuffix)));
+        assert_eq!(msg, b"X ");
+    }
+}
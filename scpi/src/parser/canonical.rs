@@ -0,0 +1,389 @@
+//! Canonical program messages
+//!
+//! Writes a [Tokenizer] stream back as SCPI text in a canonical form, useful for logging,
+//! deduplicating or diffing what controllers send:
+//! * Headers and character data in uppercase, optionally expanded to their short or long form
+//!   defined by a command tree (see [Node::write_canonical](crate::tree::Node::write_canonical)).
+//! * No white space except a single space separating the header from its data.
+//! * Decimal numerics normalized so that equal values give equal text (`1.50`, `15E-1` and
+//!   `+1.5` are all `1.5`), suffixes directly following the number unless mistakable for an
+//!   exponent. Integers stay integers, other values are written in the shortest form parsing
+//!   back to the same `f64`.
+//! * Non-decimal numerics in hexadecimal (`#H`).
+//! * Strings in double quotes.
+//! * Blocks as definite length blocks.
+//!
+//! The canonical text tokenizes into the same program message and is its own canonical form.
+//!
+//! ```
+//! # use scpi::parser::{canonical::write_canonical, tokenizer::Tokenizer};
+//! let mut out = Vec::new();
+//! write_canonical(Tokenizer::new(b"syst:err?;  :volt +1.5 e-3 mv , #b101,'it''s'\n"), &mut out)
+//!     .unwrap();
+//! assert_eq!(out, b"SYST:ERR?;:VOLT 0.0015MV,#H5,\"it's\"");
+//! ```
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::parser::format::{Arbitrary, Hex};
+use crate::parser::response::{Formatter, ResponseData};
+use crate::parser::tokenizer::util::{self, mnemonic_split_index};
+use crate::parser::tokenizer::{Token, Tokenizer};
+use crate::tree::Node;
+use crate::Device;
+use lexical_core::FormattedSize;
+
+/// Form of program headers defined by a command tree
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum HeaderForm {
+    /// Only the uppercase part of the mnemonic, e.g. `VOLT`
+    Short,
+    /// The whole mnemonic, e.g. `VOLTAGE`
+    Long,
+}
+
+/// Write the program message in `tokens` to `out` in canonical form.
+/// Headers are written in uppercase as received.
+///
+/// Returns the first error in the program message, `out` holds the message up to that point.
+pub fn write_canonical(tokens: Tokenizer, out: &mut dyn Formatter) -> Result<()> {
+    write_message(tokens, &mut AsReceived, out)
+}
+
+/// Writes program header mnemonics
+pub(crate) trait Headers {
+    /// A new program message unit starts, with a `:` if `root`
+    fn unit(&mut self, root: bool);
+
+    /// Write a program mnemonic
+    fn mnemonic(&mut self, mnemonic: &[u8], out: &mut dyn Formatter) -> Result<()>;
+}
+
+struct AsReceived;
+
+impl Headers for AsReceived {
+    fn unit(&mut self, _root: bool) {}
+
+    fn mnemonic(&mut self, mnemonic: &[u8], out: &mut dyn Formatter) -> Result<()> {
+        push_uppercase(mnemonic, out)
+    }
+}
+
+/// Resolves headers against a command tree, mirroring how [Node::run] traverses it.
+pub(crate) struct TreeHeaders<'a, D> {
+    root: &'a Node<'a, D>,
+    form: HeaderForm,
+    /// Branch relative headers start from, i.e. the branch of the last leaf
+    leaf: &'a Node<'a, D>,
+    /// Branch the next mnemonic is looked up in, [None] if the header is not in the tree
+    branch: Option<&'a Node<'a, D>>,
+    /// Is the current header the first mnemonic of the unit?
+    first: bool,
+}
+
+impl<'a, D> TreeHeaders<'a, D> {
+    pub(crate) fn new(root: &'a Node<'a, D>, form: HeaderForm) -> Self {
+        Self {
+            root,
+            form,
+            leaf: root,
+            branch: Some(root),
+            first: true,
+        }
+    }
+}
+
+impl<'a, D> Headers for TreeHeaders<'a, D>
+where
+    D: Device,
+{
+    fn unit(&mut self, root: bool) {
+        if root {
+            self.leaf = self.root;
+        }
+        self.branch = Some(self.leaf);
+        self.first = true;
+    }
+
+    fn mnemonic(&mut self, mnemonic: &[u8], out: &mut dyn Formatter) -> Result<()> {
+        // Common commands are found in the root without affecting the current branch
+        let common = self.first && mnemonic.starts_with(b"*");
+        if common {
+            self.branch = Some(self.root);
+        }
+        self.first = false;
+
+        let mut branch = self.branch.take();
+        let token = Token::ProgramMnemonic(mnemonic);
+        while let Some(node @ Node::Branch { sub, .. }) = branch {
+            if let Some(child) = sub
+                .iter()
+                .find(|child| token.match_program_header(child.name()))
+            {
+                if !common {
+                    self.leaf = node;
+                }
+                if let Node::Branch { .. } = child {
+                    self.branch = Some(child);
+                }
+                return push_header(child.name(), mnemonic, self.form, out);
+            }
+            // Mnemonic may belong to an omitted default branch
+            branch = sub
+                .iter()
+                .find(|child| matches!(child, Node::Branch { default: true, .. }));
+        }
+        // Not in the tree
+        push_uppercase(mnemonic, out)
+    }
+}
+
+/// Write a header as defined by `name` in the tree, keeping any numeric suffix of `mnemonic`
+fn push_header(
+    name: &[u8],
+    mnemonic: &[u8],
+    form: HeaderForm,
+    out: &mut dyn Formatter,
+) -> Result<()> {
+    for c in name {
+        if form == HeaderForm::Long || !c.is_ascii_lowercase() {
+            out.push_byte(c.to_ascii_uppercase())?;
+        }
+    }
+    match (mnemonic_split_index(name), mnemonic_split_index(mnemonic)) {
+        (None, Some((_, suffix))) => out.push_str(suffix),
+        _ => Ok(()),
+    }
+}
+
+fn push_uppercase(s: &[u8], out: &mut dyn Formatter) -> Result<()> {
+    s.iter()
+        .try_for_each(|c| out.push_byte(c.to_ascii_uppercase()))
+}
+
+/// <DECIMAL NUMERIC PROGRAM DATA> normalized so that equal values are written the same, as an
+/// integer if it is one or the shortest real parsing back to the same `f64` otherwise.
+/// Integers which can't be represented exactly by an `f64` are kept as received.
+/// A leading `+` is only kept if `sign` is set.
+fn push_decimal(s: &[u8], sign: bool, out: &mut dyn Formatter) -> Result<()> {
+    let mut buf = [0u8; util::NRF_BUFFER_SIZE];
+    let s = util::normalize_nrf(s, &mut buf)?;
+    if sign && s.first() == Some(&b'+') {
+        out.push_byte(b'+')?;
+    }
+    let mut num = [b'0'; f64::FORMATTED_SIZE_DECIMAL];
+    let num = if let Ok(x) = lexical_core::parse::<i64>(s) {
+        lexical_core::write(x, &mut num)
+    } else if let Ok(x) = lexical_core::parse::<u64>(s) {
+        lexical_core::write(x, &mut num)
+    } else {
+        match lexical_core::parse::<f64>(s) {
+            // Integral values like integers, these conversions are exact
+            Ok(x) if x >= i64::MIN as f64 && x < i64::MAX as f64 && x as i64 as f64 == x => {
+                lexical_core::write(x as i64, &mut num)
+            }
+            Ok(x) if x >= 0.0 && x < u64::MAX as f64 && x as u64 as f64 == x => {
+                lexical_core::write(x as u64, &mut num)
+            }
+            Ok(x) if x.is_finite() => lexical_core::write(x, &mut num),
+            // Out of range, leave as is
+            _ => s,
+        }
+    };
+    push_uppercase(num, out)
+}
+
+pub(crate) fn write_message(
+    mut tokens: Tokenizer,
+    headers: &mut dyn Headers,
+    out: &mut dyn Formatter,
+) -> Result<()> {
+    let mut unit_start = true;
+    // A number keeps its `+` unless it follows a separator
+    let mut separated = false;
+    while let Some(token) = tokens.next() {
+        let token = token.map_err(|err| Error::new(err).with_span(tokens.span()))?;
+        let sign = !separated;
+        separated = matches!(
+            token,
+            Token::ProgramHeaderSeparator | Token::ProgramDataSeparator
+        );
+        if unit_start {
+            headers.unit(token == Token::HeaderMnemonicSeparator);
+            unit_start = false;
+        }
+        match token {
+            Token::HeaderMnemonicSeparator => out.push_byte(b':')?,
+            Token::HeaderQuerySuffix => out.push_byte(b'?')?,
+            Token::ProgramMessageUnitSeparator => {
+                out.push_byte(b';')?;
+                unit_start = true;
+            }
+            Token::ProgramHeaderSeparator => {
+                // Omit trailing separator
+                if !matches!(
+                    tokens.peek(),
+                    None | Some(Ok(Token::ProgramMessageUnitSeparator))
+                ) {
+                    out.push_byte(b' ')?;
+                }
+            }
+            Token::ProgramDataSeparator => out.push_byte(b',')?,
+            Token::ProgramMnemonic(mnemonic) => headers.mnemonic(mnemonic, out)?,
+            Token::CharacterProgramData(s) => push_uppercase(s, out)?,
+            Token::DecimalNumericProgramData(s) => push_decimal(s, sign, out)?,
+            Token::DecimalNumericSuffixProgramData(s, suffix) => {
+                push_decimal(s, sign, out)?;
+                // Don't mistake suffix for an exponent
+                if suffix.first().map(u8::to_ascii_uppercase) == Some(b'E') {
+                    out.push_byte(b' ')?;
+                }
+                push_uppercase(suffix, out)?;
+            }
+            Token::NonDecimalNumericProgramData(x) => Hex(x).format_response_data(out)?,
+            Token::StringProgramData(s) => {
+                out.push_byte(b'"')?;
+                for c in s.bytes() {
+                    if c == b'"' {
+                        out.push_byte(b'"')?;
+                    }
+                    out.push_byte(c)?;
+                }
+                out.push_byte(b'"')?;
+            }
+            Token::ArbitraryBlockData(data) => Arbitrary(data)
+                .format_response_data(out)
+                .map_err(|_| Error::new(ErrorCode::TooMuchData).with_span(tokens.span()))?,
+            Token::ArbitraryBlockStream(..) => {
+                return Err(Error::new(ErrorCode::TooMuchData).with_span(tokens.span()))
+            }
+            Token::ExpressionProgramData(s) => {
+                out.push_byte(b'(')?;
+                out.push_str(s)?;
+                out.push_byte(b')')?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::tree::command::Todo;
+
+    extern crate std;
+
+    fn canonical(s: &[u8]) -> Result<std::vec::Vec<u8>> {
+        let mut out = std::vec::Vec::new();
+        write_canonical(Tokenizer::new(s), &mut out)?;
+        // Must be its own canonical form
+        let mut again = std::vec::Vec::new();
+        write_canonical(Tokenizer::new(&out), &mut again).unwrap();
+        assert_eq!(out, again);
+        Ok(out)
+    }
+
+    #[test]
+    fn test_headers() {
+        assert_eq!(canonical(b"*rst"), Ok(b"*RST".to_vec()));
+        assert_eq!(
+            canonical(b"sour:volt:lev?  ; curr 1\n"),
+            Ok(b"SOUR:VOLT:LEV?;CURR 1".to_vec())
+        );
+        assert_eq!(
+            canonical(b":meas2? ;:syst:err "),
+            Ok(b":MEAS2?;:SYST:ERR".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_data() {
+        assert_eq!(
+            canonical(b"X +1 , -.5e+3,1.0 E -3 v,ab_1"),
+            Ok(b"X 1,-500,0.001V,AB_1".to_vec())
+        );
+        // Same value, same text
+        assert_eq!(
+            canonical(b"X 1.50,1.5,15E-1,+0.15e+1,1.0,001,1E0"),
+            Ok(b"X 1.5,1.5,1.5,1.5,1,1,1".to_vec())
+        );
+        assert_eq!(
+            canonical(b"X 1e300,-2.5e-7,18446744073709551615,1E400"),
+            Ok(b"X 1.0E300,-2.5E-7,18446744073709551615,1E400".to_vec())
+        );
+        assert_eq!(
+            canonical(b"X 1152921504606846976,1.152921504606846976E18,9007199254740993"),
+            Ok(b"X 1152921504606846976,1152921504606846976,9007199254740993".to_vec())
+        );
+        assert_eq!(
+            canonical(b"X #hFf,#Q7,#b0,#H0"),
+            Ok(b"X #HFF,#H7,#H0,#H0".to_vec())
+        );
+        assert_eq!(
+            canonical(b"X 'a\"b''c',\"\""),
+            Ok(b"X \"a\"\"b'c\",\"\"".to_vec())
+        );
+        assert_eq!(canonical(b"X #0ab\n"), Ok(b"X #12ab".to_vec()));
+        assert_eq!(
+            canonical(b"X #3002\n;,(@1:2, 3)"),
+            Ok(b"X #12\n;,(@1:2, 3)".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_streamed() {
+        // Same output whatever the formatter
+        let message = b"X +1,+2 V;Y? -3";
+        let mut out = crate::parser::response::WriteFormatter::new(std::vec::Vec::new());
+        write_canonical(Tokenizer::new(message), &mut out).unwrap();
+        assert_eq!(Ok(out.into_inner()), canonical(message));
+    }
+
+    struct TreeDevice;
+    crate::tests::fixture_device!(TreeDevice);
+
+    const TREE: Node<TreeDevice> = crate::Root![
+        crate::Leaf!(b"*RST" => &Todo),
+        crate::Branch!(b"SOURce";
+            crate::Branch!(default b"VOLTage";
+                crate::Leaf!(default b"LEVel" => &Todo),
+                crate::Leaf!(b"MODE" => &Todo)
+            ),
+            crate::Leaf!(b"CURRent" => &Todo)
+        ),
+        crate::Branch!(b"TRIGger2";
+            crate::Leaf!(b"DELay" => &Todo)
+        ),
+        crate::Leaf!(b"OUTPut" => &Todo)
+    ];
+
+    fn tree_canonical(s: &[u8], form: HeaderForm) -> std::vec::Vec<u8> {
+        let mut out = std::vec::Vec::new();
+        TREE.write_canonical(Tokenizer::new(s), form, &mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_tree_headers() {
+        let message =
+            b"sour:volt:lev 1;mode?;*rst;:sour:curr 2;:trig2:del;:outp;sour:mode;:unkn:hdr";
+        assert_eq!(
+            tree_canonical(message, HeaderForm::Short),
+            b"SOUR:VOLT:LEV 1;MODE?;*RST;:SOUR:CURR 2;:TRIG2:DEL;:OUTP;SOUR:MODE;:UNKN:HDR"
+        );
+        assert_eq!(
+            tree_canonical(message, HeaderForm::Long),
+            b"SOURCE:VOLTAGE:LEVEL 1;MODE?;*RST;:SOURCE:CURRENT 2;:TRIGGER2:DELAY;:OUTPUT;SOURCE:MODE;\
+              :UNKN:HDR"
+        );
+    }
+
+    #[test]
+    fn test_error() {
+        assert_eq!(
+            canonical(b"X 1,,2"),
+            Err(Error::new(ErrorCode::SyntaxError).with_span(crate::error::Span::new(3, 4)))
+        );
+    }
+}
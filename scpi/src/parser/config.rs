@@ -0,0 +1,68 @@
+//! Parser configuration
+
+/// Selects how strictly program messages are parsed.
+///
+/// Each rule is strict (IEEE 488.2 compliant) when `true`. The [Default] is
+/// [ParserConfig::STRICT], use [ParserConfig::LENIENT] or relax individual rules to accept
+/// messages from sloppy legacy scripts.
+///
+/// Set per source with [Context::config](crate::Context::config):
+/// ```
+/// # use scpi::{Context, parser::ParserConfig};
+/// let mut context = Context::new();
+/// context.config = ParserConfig {
+///     mnemonic_limit: false,
+///     ..ParserConfig::STRICT
+/// };
+/// ```
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ParserConfig {
+    /// Program mnemonics and character data are limited to 12 characters,
+    /// see IEEE 488.2-1992 7.6.1.4.
+    ///
+    /// Returns [ProgramMnemonicTooLong](crate::error::ErrorCode::ProgramMnemonicTooLong) or
+    /// [CharacterDataTooLong](crate::error::ErrorCode::CharacterDataTooLong) if exceeded.
+    pub mnemonic_limit: bool,
+
+    /// <STRING PROGRAM DATA> may only contain ASCII characters.
+    ///
+    /// Returns [InvalidCharacter](crate::error::ErrorCode::InvalidCharacter) for any other
+    /// byte. Strings are passed through as-is otherwise, e.g. UTF-8.
+    pub ascii_strings: bool,
+
+    /// A program header may not be preceded by a `+` sign.
+    ///
+    /// Returns [CommandHeaderError](crate::error::ErrorCode::CommandHeaderError) for e.g.
+    /// `+VOLT 1`, otherwise the sign is ignored.
+    pub header_sign: bool,
+
+    /// A query header (`?`) must be followed by white space, `;` or the end of the message.
+    ///
+    /// Returns [SyntaxError](crate::error::ErrorCode::SyntaxError) for e.g. `MEAS?1`,
+    /// otherwise program data may directly follow the `?`.
+    pub query_separator: bool,
+}
+
+impl ParserConfig {
+    /// All rules are enforced
+    pub const STRICT: Self = Self {
+        mnemonic_limit: true,
+        ascii_strings: true,
+        header_sign: true,
+        query_separator: true,
+    };
+
+    /// No rules are enforced
+    pub const LENIENT: Self = Self {
+        mnemonic_limit: false,
+        ascii_strings: false,
+        header_sign: false,
+        query_separator: false,
+    };
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self::STRICT
+    }
+}
@@ -0,0 +1,573 @@
+//! Deserialize command parameters with [serde]
+//!
+//! [from_parameters] reads the parameters of a command into any type implementing
+//! [Deserialize], instead of calling [Parameters::next_data] for each of them.
+//!
+//! * Structs, tuples and tuple structs read one parameter per field.
+//! * [Option]s are [None] when no more parameters follow, so only trailing parameters can be
+//!   optional.
+//! * Sequences such as `Vec<T>` read parameters until there are none left.
+//! * Unit enum variants are matched against character program data as mnemonics. Use
+//!   `#[serde(rename = "BINary")]` to give a variant its short form, otherwise the variant name
+//!   is the mnemonic (`Volt` has the short form `V`). A newtype, tuple or struct variant reads
+//!   its fields from the following parameters.
+//! * Strings and bytes are read from string or block program data. A string containing
+//!   doubled quotes needs `alloc` to be unescaped into a `String`.
+//!
+//! ```
+//! # use scpi::parser::{de::from_parameters, parameters::Parameters, tokenizer::Tokenizer};
+//! #[derive(Debug, PartialEq, serde::Deserialize)]
+//! enum Function {
+//!     #[serde(rename = "VOLTage")]
+//!     Voltage,
+//!     #[serde(rename = "CURRent")]
+//!     Current,
+//! }
+//!
+//! #[derive(Debug, PartialEq, serde::Deserialize)]
+//! struct Configure<'a> {
+//!     function: Function,
+//!     range: f32,
+//!     label: Option<&'a str>,
+//! }
+//!
+//! let mut tokenizer = Tokenizer::new_params(b"CURR,0.5,'input'");
+//! let config: Configure = from_parameters(&mut Parameters::with(&mut tokenizer)).unwrap();
+//! assert_eq!(
+//!     config,
+//!     Configure {
+//!         function: Function::Current,
+//!         range: 0.5,
+//!         label: Some("input")
+//!     }
+//! );
+//! ```
+//!
+//! Errors are mapped onto SCPI errors: a parameter of the wrong type returns
+//! [DataTypeError](ErrorCode::DataTypeError), a missing one
+//! [MissingParameter](ErrorCode::MissingParameter) and values rejected by the visitor, such as
+//! unknown enum mnemonics, [IllegalParameterValue](ErrorCode::IllegalParameterValue).
+
+use core::{fmt::Display, str};
+
+use serde::de::{
+    self, Deserialize, DeserializeSeed, EnumAccess, Expected, IntoDeserializer, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+
+use crate::error::{Error, ErrorCode, Result};
+
+use super::{format, mnemonic_match, parameters::Parameters, tokenizer::Token};
+
+/// Deserialize `T` from the remaining parameters
+pub fn from_parameters<'a, T>(params: &mut Parameters<'a, '_>) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    T::deserialize(&mut Deserializer::new(params))
+}
+
+/// A [serde::Deserializer] reading command parameters, see the [module](self) documentation.
+pub struct Deserializer<'p, 'a, 'b> {
+    params: &'p mut Parameters<'a, 'b>,
+}
+
+impl<'p, 'a, 'b> Deserializer<'p, 'a, 'b> {
+    /// Deserialize from `params`
+    pub fn new(params: &'p mut Parameters<'a, 'b>) -> Self {
+        Self { params }
+    }
+}
+
+impl<'de> Deserializer<'_, 'de, '_> {
+    fn visit_str<V>(token: Token<'de>, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match token {
+            Token::StringProgramData(s) => match s.as_unescaped() {
+                Some(s) => visitor.visit_borrowed_str(utf8(s)?),
+                #[cfg(feature = "alloc")]
+                None => visitor.visit_string(
+                    alloc::string::String::from_utf8(s.to_owned())
+                        .map_err(|_| Error::new(ErrorCode::StringDataError))?,
+                ),
+                // Unescaping needs an owned buffer
+                #[cfg(not(feature = "alloc"))]
+                None => Err(ErrorCode::StringDataError.into()),
+            },
+            token => visitor.visit_borrowed_str(token.try_into()?),
+        }
+    }
+
+    fn visit_bytes<V>(token: Token<'de>, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match token {
+            Token::StringProgramData(s) => match s.as_unescaped() {
+                Some(s) => visitor.visit_borrowed_bytes(s),
+                #[cfg(feature = "alloc")]
+                None => visitor.visit_byte_buf(s.to_owned()),
+                #[cfg(not(feature = "alloc"))]
+                None => Err(ErrorCode::StringDataError.into()),
+            },
+            token => visitor.visit_borrowed_bytes(format::Arbitrary::try_from(token)?.0),
+        }
+    }
+}
+
+fn utf8(s: &[u8]) -> Result<&str> {
+    str::from_utf8(s).map_err(|_| ErrorCode::StringDataError.into())
+}
+
+macro_rules! deserialize_data {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(self.params.next_data()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'_, 'de, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.params.next_token()? {
+            Token::CharacterProgramData(s) => visitor.visit_borrowed_str(utf8(s)?),
+            Token::NonDecimalNumericProgramData(n) => visitor.visit_u64(n),
+            token @ Token::DecimalNumericProgramData(_) => {
+                let value = f64::try_from(token)?;
+                if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+                    visitor.visit_i64(value as i64)
+                } else {
+                    visitor.visit_f64(value)
+                }
+            }
+            token @ Token::DecimalNumericSuffixProgramData(..) => {
+                visitor.visit_f64(token.try_into()?)
+            }
+            token @ Token::StringProgramData(_) => Deserializer::visit_str(token, visitor),
+            token => Deserializer::visit_bytes(token, visitor),
+        }
+    }
+
+    deserialize_data!(
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64
+    );
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let s: &str = self.params.next_data()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(ErrorCode::IllegalParameterValue.into()),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Deserializer::visit_str(self.params.next_token()?, visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Deserializer::visit_bytes(self.params.next_token()?, visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.params.has_next() {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(Seq {
+            de: self,
+            remaining: None,
+        })
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(Seq {
+            de: self,
+            remaining: Some(len),
+        })
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    /// Program data has no keys
+    fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Err(ErrorCode::DataTypeError.into())
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let format::Character(s) = self.params.next_data()?;
+        let variant = variants
+            .iter()
+            .find(|variant| mnemonic_match(variant.as_bytes(), s))
+            .ok_or(ErrorCode::IllegalParameterValue)?;
+        visitor.visit_enum(Enum { de: self, variant })
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.params.next_token()?;
+        visitor.visit_unit()
+    }
+}
+
+/// Parameters of a struct, tuple or sequence
+struct Seq<'d, 'p, 'a, 'b> {
+    de: &'d mut Deserializer<'p, 'a, 'b>,
+    // Sequences continue until parameters run out
+    remaining: Option<usize>,
+}
+
+impl<'de> SeqAccess<'de> for Seq<'_, '_, 'de, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.remaining {
+            Some(0) => return Ok(None),
+            Some(n) => self.remaining = Some(n - 1),
+            None if !self.de.params.has_next() => return Ok(None),
+            None => {}
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining
+    }
+}
+
+/// A variant matched by its mnemonic, fields follow as parameters
+struct Enum<'d, 'p, 'a, 'b> {
+    de: &'d mut Deserializer<'p, 'a, 'b>,
+    variant: &'static str,
+}
+
+impl<'de> EnumAccess<'de> for Enum<'_, '_, 'de, '_> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant =
+            seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.variant))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Enum<'_, '_, 'de, '_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self.de, len, visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self.de, fields.len(), visitor)
+    }
+}
+
+// With `std` this is `std::error::Error`, implemented in the error module
+#[cfg(not(feature = "std"))]
+impl de::StdError for Error {}
+
+impl de::Error for Error {
+    /// Rejected by the visitor, the message is kept as extended error info if there's room for it
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        let err = Error::new(ErrorCode::IllegalParameterValue);
+        #[cfg(feature = "extended-buffer")]
+        let err = err.extended_fmt(format_args!("{msg}"));
+        #[cfg(not(feature = "extended-buffer"))]
+        let _ = msg;
+        err
+    }
+
+    fn invalid_type(_unexp: Unexpected, _exp: &dyn Expected) -> Self {
+        ErrorCode::DataTypeError.into()
+    }
+
+    fn invalid_value(_unexp: Unexpected, _exp: &dyn Expected) -> Self {
+        ErrorCode::IllegalParameterValue.into()
+    }
+
+    fn invalid_length(_len: usize, _exp: &dyn Expected) -> Self {
+        ErrorCode::MissingParameter.into()
+    }
+
+    fn unknown_variant(_variant: &str, _expected: &'static [&'static str]) -> Self {
+        ErrorCode::IllegalParameterValue.into()
+    }
+
+    fn missing_field(_field: &'static str) -> Self {
+        ErrorCode::MissingParameter.into()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::error::Span;
+    use crate::parser::tokenizer::Tokenizer;
+    use serde::Deserialize;
+
+    extern crate std;
+    use std::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+    fn parse<T>(s: &[u8]) -> Result<T>
+    where
+        T: de::DeserializeOwned,
+    {
+        let mut tokenizer = Tokenizer::new_params(s);
+        from_parameters(&mut Parameters::with(&mut tokenizer))
+    }
+
+    fn error<T>(s: &[u8]) -> Error
+    where
+        T: de::DeserializeOwned,
+    {
+        parse::<T>(s).err().unwrap()
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Mode {
+        #[serde(rename = "VOLTage")]
+        Voltage,
+        #[serde(rename = "CURRent")]
+        Current(f32),
+        #[serde(rename = "RESistance")]
+        Resistance { range: u32, auto: bool },
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Setup {
+        mode: Mode,
+        name: String,
+        channels: Option<(u8, u8)>,
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(parse::<u8>(b"42"), Ok(42));
+        assert_eq!(parse::<i16>(b"#HFF"), Ok(255));
+        assert_eq!(parse::<f64>(b"1.5e3"), Ok(1500.0));
+        assert_eq!(parse::<bool>(b"ON"), Ok(true));
+        assert_eq!(parse::<char>(b"'x'"), Ok('x'));
+        assert_eq!(parse::<(u8, f32)>(b"1,2.5"), Ok((1, 2.5)));
+        assert_eq!(parse::<[u8; 2]>(b"3,4"), Ok([3, 4]));
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(parse::<String>(b"'it''s'"), Ok(String::from("it's")));
+        assert_eq!(parse::<String>(b"#15hello"), Ok(String::from("hello")));
+
+        let mut tokenizer = Tokenizer::new_params(b"\"hello\",#12\x00\xff,'it''s'");
+        let mut params = Parameters::with(&mut tokenizer);
+        assert_eq!(
+            from_parameters::<(&str, &[u8])>(&mut params),
+            Ok(("hello", &[0x00, 0xff][..]))
+        );
+        // Can't borrow an escaped string
+        assert_eq!(
+            from_parameters::<&str>(&mut params),
+            Err(ErrorCode::DataTypeError.into())
+        );
+    }
+
+    #[test]
+    fn test_struct() {
+        assert_eq!(
+            parse::<Setup>(b"VOLT,'a',1,2"),
+            Ok(Setup {
+                mode: Mode::Voltage,
+                name: String::from("a"),
+                channels: Some((1, 2)),
+            })
+        );
+        assert_eq!(
+            parse::<Setup>(b"curr,2.5,'b'"),
+            Ok(Setup {
+                mode: Mode::Current(2.5),
+                name: String::from("b"),
+                channels: None,
+            })
+        );
+        assert_eq!(
+            parse::<Setup>(b"RESISTANCE,100,OFF,'c'"),
+            Ok(Setup {
+                mode: Mode::Resistance {
+                    range: 100,
+                    auto: false
+                },
+                name: String::from("c"),
+                channels: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_sequence() {
+        assert_eq!(parse::<Vec<u8>>(b"1,2,3"), Ok(vec![1, 2, 3]));
+        assert_eq!(parse::<Vec<u8>>(b""), Ok(vec![]));
+        assert_eq!(
+            parse::<(Mode, Vec<(u8, bool)>)>(b"VOLT,1,ON,2,OFF"),
+            Ok((Mode::Voltage, vec![(1, true), (2, false)]))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse::<Setup>(b"VOLT"),
+            Err(Error::new(ErrorCode::MissingParameter).with_span(Span::new(4, 4)))
+        );
+        assert_eq!(error::<Setup>(b"POW,'a'"), ErrorCode::IllegalParameterValue);
+        assert_eq!(error::<Setup>(b"'VOLT','a'"), ErrorCode::DataTypeError);
+        assert_eq!(error::<u8>(b"'1'"), ErrorCode::DataTypeError);
+        assert_eq!(error::<BTreeMap<u8, u8>>(b"1,2"), ErrorCode::DataTypeError);
+    }
+}
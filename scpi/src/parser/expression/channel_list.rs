@@ -13,6 +13,7 @@
 
 use crate::error::{Error, ErrorCode};
 use core::convert::TryFrom;
+use core::ops::RangeInclusive;
 use core::slice::Iter;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -55,11 +56,154 @@ pub enum Token<'a> {
     /// Example: `1!1:2!3` is a range from '1,1' to '2,3' (row-major)
     ChannelRange(ChannelSpec<'a>, ChannelSpec<'a>),
     /// A module-channel, contains a module (numeric or character data) and a sub-channel-list.
+    /// Example: `card1(1:4)` is channels 1 through 4 of module `card1`.
+    /// Use [ChannelList::from_module] to parse the sub-channel-list.
     ModuleChannel(&'a [u8], &'a [u8]),
     /// A character pathname (can be a file, resource etc...)
     PathName(&'a [u8]),
 }
 
+impl<'a> Token<'a> {
+    /// Expand a channel spec or range into every channel address it contains, in row-major order.
+    ///
+    /// `bounds` is the valid range of each dimension, the first dimension being the most
+    /// significant. A range wraps to the start of the next row when reaching the end of the
+    /// bounds, eg `1!3:2!2` with bounds `[1..=2, 1..=4]` yields `1!3, 1!4, 2!1, 2!2`.
+    /// Descending ranges are expanded in descending order.
+    ///
+    /// # Returns
+    /// * `Ok(ChannelRangeIter)` - An iterator over each channel address
+    /// * `Err(InvalidExpression)` - If the token isn't a channel spec or range or has a different dimension than `bounds`
+    /// * `Err(DataOutOfRange)` - If a channel is outside of `bounds`
+    ///
+    /// ```
+    /// # use scpi::parser::expression::channel_list::ChannelList;
+    /// let mut list = ChannelList::new(b"@1!3:2!2").unwrap();
+    /// let range = list.next().unwrap().unwrap();
+    /// let channels: Vec<[isize; 2]> = range.expand(&[1..=2, 1..=4]).unwrap().collect();
+    /// assert_eq!(channels, [[1, 3], [1, 4], [2, 1], [2, 2]]);
+    /// ```
+    pub fn expand<const N: usize>(
+        &self,
+        bounds: &[RangeInclusive<isize>; N],
+    ) -> Result<ChannelRangeIter<N>, Error> {
+        let (begin, end) = match self {
+            Token::ChannelSpec(spec) => (*spec, *spec),
+            Token::ChannelRange(begin, end) => (*begin, *end),
+            _ => {
+                return Err(Error::new(ErrorCode::InvalidExpression)
+                    .extended(b"Expected channel spec or range"))
+            }
+        };
+        ChannelRangeIter::new(begin.try_into()?, end.try_into()?, bounds)
+    }
+}
+
+/// Iterates over every channel address in a channel range in row-major order.
+/// See [Token::expand].
+#[derive(Clone, Debug)]
+pub struct ChannelRangeIter<const N: usize> {
+    min: [isize; N],
+    sizes: [usize; N],
+    next: usize,
+    end: usize,
+    descending: bool,
+    done: bool,
+}
+
+impl<const N: usize> ChannelRangeIter<N> {
+    fn new(
+        begin: [isize; N],
+        end: [isize; N],
+        bounds: &[RangeInclusive<isize>; N],
+    ) -> Result<Self, Error> {
+        let mut min = [0isize; N];
+        let mut sizes = [0usize; N];
+        for (i, bound) in bounds.iter().enumerate() {
+            if bound.is_empty() {
+                return Err(ErrorCode::DataOutOfRange.into());
+            }
+            min[i] = *bound.start();
+            sizes[i] = bound
+                .end()
+                .abs_diff(*bound.start())
+                .checked_add(1)
+                .ok_or(ErrorCode::DataOutOfRange)?;
+        }
+        let mut iter = Self {
+            min,
+            sizes,
+            next: 0,
+            end: 0,
+            descending: false,
+            done: false,
+        };
+        iter.next = iter.index(&begin, bounds)?;
+        iter.end = iter.index(&end, bounds)?;
+        iter.descending = iter.next > iter.end;
+        Ok(iter)
+    }
+
+    /// Row-major index of a channel address
+    fn index(
+        &self,
+        channel: &[isize; N],
+        bounds: &[RangeInclusive<isize>; N],
+    ) -> Result<usize, Error> {
+        let mut index = 0usize;
+        for (i, ch) in channel.iter().enumerate() {
+            if !bounds[i].contains(ch) {
+                return Err(Error::new(ErrorCode::DataOutOfRange).extended(b"Channel out of range"));
+            }
+            index = index
+                .checked_mul(self.sizes[i])
+                .and_then(|index| index.checked_add(ch.abs_diff(self.min[i])))
+                .ok_or(ErrorCode::DataOutOfRange)?;
+        }
+        Ok(index)
+    }
+
+    /// Channel address of a row-major index
+    fn channel(&self, mut index: usize) -> [isize; N] {
+        let mut channel = [0isize; N];
+        for i in (0..N).rev() {
+            channel[i] = self.min[i] + (index % self.sizes[i]) as isize;
+            index /= self.sizes[i];
+        }
+        channel
+    }
+}
+
+impl<const N: usize> Iterator for ChannelRangeIter<N> {
+    type Item = [isize; N];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let channel = self.channel(self.next);
+        if self.next == self.end {
+            self.done = true;
+        } else if self.descending {
+            self.next -= 1;
+        } else {
+            self.next += 1;
+        }
+        Some(channel)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = if self.done {
+            0
+        } else {
+            self.next.abs_diff(self.end) + 1
+        };
+        (len, Some(len))
+    }
+}
+
+impl<const N: usize> ExactSizeIterator for ChannelRangeIter<N> {}
+
 /// Iterates over a channel spec, returning a result for each dimension.
 /// If the iterator encounters a badly formatted value, an error will be returned.
 /// Example: `"1!2!3"` would iterate as `Ok(1),Ok(2),Ok(3)`.
@@ -187,6 +331,22 @@ impl<'a> TryFrom<ChannelSpec<'a>> for (usize, usize, usize) {
     }
 }
 
+impl<'a, const N: usize> TryFrom<ChannelSpec<'a>> for [isize; N] {
+    type Error = Error;
+
+    fn try_from(value: ChannelSpec) -> Result<Self, Self::Error> {
+        if value.dimension() == N {
+            let mut channel = [0isize; N];
+            for (ch, i) in channel.iter_mut().zip(value) {
+                *ch = i?;
+            }
+            Ok(channel)
+        } else {
+            Err(Error::new(ErrorCode::InvalidExpression).extended(b"Unexpected channel dimension"))
+        }
+    }
+}
+
 /// Channel list expression tokenizer
 #[derive(Clone)]
 pub struct ChannelList<'a> {
@@ -216,6 +376,14 @@ impl<'a> ChannelList<'a> {
         }
     }
 
+    /// Create a channel-list tokenizer for the sub-channel-list of a [Token::ModuleChannel]
+    pub fn from_module(list: &'a [u8]) -> Self {
+        ChannelList {
+            chars: list.iter(),
+            first: true,
+        }
+    }
+
     fn read_channel_spec(&mut self) -> Result<(&'a [u8], usize), ErrorCode> {
         let mut dim = 1usize;
         // Read full spec
@@ -245,6 +413,10 @@ impl<'a> ChannelList<'a> {
 
         // Try to read the ending spec
         if let Some(x) = self.chars.clone().next() {
+            // Numeric module
+            if *x == b'(' && dim1 == 1 {
+                return self.read_module_list(begin);
+            }
             if *x == b':' {
                 self.chars.next();
                 let (end, dim2) = self.read_channel_spec()?;
@@ -272,17 +444,49 @@ impl<'a> ChannelList<'a> {
         if let crate::parser::tokenizer::Token::StringProgramData(s) =
             crate::parser::tokenizer::Tokenizer::new(s).read_string_data(x, true)?
         {
-            self.chars.nth(s.len() + 1); //Forward iterator characters
-            Ok(Token::PathName(s))
+            self.chars.nth(s.raw().len() + 1); //Forward iterator characters
+            Ok(Token::PathName(s.raw()))
         } else {
             Err(ErrorCode::InvalidExpression)
         }
     }
 
-    //TODO: Implement channel modules
-    //fn read_channel_module(&mut self, _name: &'a [u8]) -> Result<Token<'a>, Error> {
-    //    unimplemented!()
-    //}
+    fn read_channel_module(&mut self) -> Result<Token<'a>, ErrorCode> {
+        // Read module name
+        let s = self.chars.as_slice();
+        while matches!(self.chars.clone().next(), Some(ch) if ch.is_ascii_alphanumeric() || *ch == b'_')
+        {
+            self.chars.next();
+        }
+        let name = &s[0..s.len() - self.chars.as_slice().len()];
+        if self.chars.clone().next() != Some(&b'(') {
+            return Err(ErrorCode::InvalidExpression);
+        }
+        self.read_module_list(name)
+    }
+
+    fn read_module_list(&mut self, module: &'a [u8]) -> Result<Token<'a>, ErrorCode> {
+        // Consume '('
+        self.chars.next();
+        let s = self.chars.as_slice();
+        // Read until matching ')'
+        let mut depth = 0usize;
+        loop {
+            match self.chars.next() {
+                Some(b')') if depth == 0 => break,
+                Some(b')') => depth -= 1,
+                Some(b'(') => depth += 1,
+                Some(_) => {}
+                None => return Err(ErrorCode::InvalidExpression),
+            }
+        }
+        let list = &s[0..s.len() - self.chars.as_slice().len() - 1];
+        if list.is_empty() {
+            Err(ErrorCode::InvalidExpression)
+        } else {
+            Ok(Token::ModuleChannel(module, list))
+        }
+    }
 }
 
 impl<'a> Iterator for ChannelList<'a> {
@@ -303,6 +507,7 @@ impl<'a> Iterator for ChannelList<'a> {
         Some(match x {
             x if x.is_ascii_digit() || *x == b'+' || *x == b'-' => self.read_channel_range(),
             x if *x == b'"' || *x == b'\'' => self.read_channel_path(*x),
+            x if x.is_ascii_alphabetic() => self.read_channel_module(),
             _ => Err(ErrorCode::InvalidExpression),
         })
     }
@@ -351,4 +556,86 @@ mod tests {
         assert_eq!(expr.next(), Some(Ok(Token::PathName(b"POTATO"))));
         assert_eq!(expr.next(), None);
     }
+
+    #[test]
+    fn test_module_channel() {
+        let mut expr = ChannelList::new(b"@card1(1:4),2(1!1,'PATH'),3").unwrap();
+        assert_eq!(
+            expr.next(),
+            Some(Ok(Token::ModuleChannel(b"card1", b"1:4")))
+        );
+        assert_eq!(
+            expr.next(),
+            Some(Ok(Token::ModuleChannel(b"2", b"1!1,'PATH'")))
+        );
+        assert_eq!(
+            expr.next(),
+            Some(Ok(Token::ChannelSpec(ChannelSpec(b"3", 1))))
+        );
+        assert_eq!(expr.next(), None);
+
+        let mut sub = ChannelList::from_module(b"1!1,'PATH'");
+        assert_eq!(
+            sub.next(),
+            Some(Ok(Token::ChannelSpec(ChannelSpec(b"1!1", 2))))
+        );
+        assert_eq!(sub.next(), Some(Ok(Token::PathName(b"PATH"))));
+        assert_eq!(sub.next(), None);
+
+        // Nested modules
+        let mut expr = ChannelList::new(b"@rack(card1(1))").unwrap();
+        assert_eq!(
+            expr.next(),
+            Some(Ok(Token::ModuleChannel(b"rack", b"card1(1)")))
+        );
+
+        // Errors
+        let mut expr = ChannelList::new(b"@card1(1:4").unwrap();
+        assert_eq!(expr.next(), Some(Err(ErrorCode::InvalidExpression)));
+        let mut expr = ChannelList::new(b"@card1()").unwrap();
+        assert_eq!(expr.next(), Some(Err(ErrorCode::InvalidExpression)));
+        let mut expr = ChannelList::new(b"@card1").unwrap();
+        assert_eq!(expr.next(), Some(Err(ErrorCode::InvalidExpression)));
+    }
+
+    #[test]
+    fn test_expand() {
+        let range = Token::ChannelRange(ChannelSpec(b"1!3", 2), ChannelSpec(b"2!2", 2));
+        let channels: std::vec::Vec<_> = range.expand(&[1..=2, 1..=4]).unwrap().collect();
+        assert_eq!(channels, [[1, 3], [1, 4], [2, 1], [2, 2]]);
+
+        // Descending
+        let range = Token::ChannelRange(ChannelSpec(b"2!1", 2), ChannelSpec(b"1!4", 2));
+        let channels: std::vec::Vec<_> = range.expand(&[1..=2, 1..=4]).unwrap().collect();
+        assert_eq!(channels, [[2, 1], [1, 4]]);
+
+        // Three dimensions
+        let range = Token::ChannelRange(ChannelSpec(b"0!1!0", 3), ChannelSpec(b"1!0!1", 3));
+        let iter = range.expand(&[0..=1, 0..=1, 0..=1]).unwrap();
+        assert_eq!(iter.len(), 4);
+        let channels: std::vec::Vec<_> = iter.collect();
+        assert_eq!(channels, [[0, 1, 0], [0, 1, 1], [1, 0, 0], [1, 0, 1]]);
+
+        // Single channel
+        let spec = Token::ChannelSpec(ChannelSpec(b"5", 1));
+        let channels: std::vec::Vec<_> = spec.expand(&[1..=8]).unwrap().collect();
+        assert_eq!(channels, [[5]]);
+    }
+
+    #[test]
+    fn test_expand_invalid() {
+        let range = Token::ChannelRange(ChannelSpec(b"1!3", 2), ChannelSpec(b"2!5", 2));
+        assert_eq!(
+            range.expand(&[1..=2, 1..=4]).unwrap_err(),
+            Error::new(ErrorCode::DataOutOfRange).extended(b"Channel out of range")
+        );
+        assert_eq!(
+            range.expand(&[1..=2]).unwrap_err(),
+            Error::new(ErrorCode::InvalidExpression).extended(b"Unexpected channel dimension")
+        );
+        assert_eq!(
+            Token::PathName(b"PATH").expand(&[1..=2]).unwrap_err(),
+            Error::new(ErrorCode::InvalidExpression).extended(b"Expected channel spec or range")
+        );
+    }
 }
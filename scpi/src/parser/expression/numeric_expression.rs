@@ -1,7 +1,35 @@
 //! # 8.3.1 Numeric Expression
 //! A numeric expression is a collection of terms which evaluates to a trace, number, array, or
 //! other data element.
+//!
+//! Only expressions evaluating to a number are supported. Operators are evaluated in the order
+//! below, highest precedence first. Binary operators of equal precedence are evaluated left to
+//! right except `^` which is evaluated right to left.
+//! 1. `^`
+//! 2. Unary `+`, `-` and `NOT`
+//! 3. `*`, `/`, `DIV`, `MOD` and `AND`
+//! 4. `+`, `-`, `OR` and `EXOR`
+//!
+//! `AND`, `OR`, `EXOR` and `NOT` operate bitwise on integers. Non-integer exponents are only
+//! supported with the `std` feature.
+//!
+//! ```
+//! # use scpi::parser::expression::numeric_expression::NumericExpression;
+//! let expr = NumericExpression::new(b"3*VOLT_OFFSET+2^4");
+//! let value = expr.evaluate(|name| match name {
+//!     b"VOLT_OFFSET" => Some(0.5),
+//!     _ => None,
+//! });
+//! assert_eq!(value, Ok(17.5));
+//! ```
 
+use crate::error::{Error, ErrorCode};
+use crate::parser::tokenizer::Tokenizer;
+
+/// Maximum nesting of parentheses and unary operators
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum NumericOperator {
     /// `+`
     Add,
@@ -23,6 +51,39 @@ pub enum NumericOperator {
     Exor,
 }
 
+impl NumericOperator {
+    /// Precedence of operator, higher binds tighter.
+    /// `^` binds tighter than unary operators and is handled separately.
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Add | Self::Sub | Self::Or | Self::Exor => 1,
+            Self::Mul | Self::Div | Self::Modulus | Self::And => 2,
+            Self::Exp => 3,
+        }
+    }
+
+    fn apply(&self, lhs: f64, rhs: f64) -> Result<f64, Error> {
+        let value = match self {
+            Self::Add => lhs + rhs,
+            Self::Sub => lhs - rhs,
+            Self::Mul => lhs * rhs,
+            Self::Div | Self::Modulus if rhs == 0.0 => {
+                return Err(
+                    Error::new(ErrorCode::MathErrorInExpression).extended(b"Division by zero")
+                )
+            }
+            Self::Div => lhs / rhs,
+            Self::Modulus => lhs % rhs,
+            Self::Exp => power(lhs, rhs)?,
+            Self::Or => (to_integer(lhs)? | to_integer(rhs)?) as f64,
+            Self::And => (to_integer(lhs)? & to_integer(rhs)?) as f64,
+            Self::Exor => (to_integer(lhs)? ^ to_integer(rhs)?) as f64,
+        };
+        finite(value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum UnaryNumericOperator {
     /// `+`
     Add,
@@ -31,3 +92,353 @@ pub enum UnaryNumericOperator {
     /// `NOT`
     Not,
 }
+
+impl UnaryNumericOperator {
+    fn apply(&self, value: f64) -> Result<f64, Error> {
+        match self {
+            Self::Add => Ok(value),
+            Self::Sub => Ok(-value),
+            Self::Not => Ok(!to_integer(value)? as f64),
+        }
+    }
+}
+
+/// `base^exp`, non-integer exponents require the `std` feature.
+fn power(base: f64, exp: f64) -> Result<f64, Error> {
+    #[cfg(feature = "std")]
+    {
+        Ok(base.powf(exp))
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        // <f32|f64>::powf() doesn't exist in no_std, square and multiply
+        let exp = to_integer(exp)?;
+        let mut base = if exp < 0 { 1.0 / base } else { base };
+        let mut exp = exp.unsigned_abs();
+        let mut value = 1.0;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                value *= base;
+            }
+            base *= base;
+            exp >>= 1;
+        }
+        Ok(value)
+    }
+}
+
+/// Operands of bitwise operators must be integers
+fn to_integer(value: f64) -> Result<i64, Error> {
+    let integer = value as i64;
+    if integer as f64 == value {
+        Ok(integer)
+    } else {
+        Err(Error::new(ErrorCode::MathErrorInExpression).extended(b"Integer operand expected"))
+    }
+}
+
+fn finite(value: f64) -> Result<f64, Error> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(ErrorCode::MathErrorInExpression.into())
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Token<'a> {
+    /// A decimal numeric
+    Numeric(f64),
+    /// A device variable
+    Variable(&'a [u8]),
+    /// A binary operator, `+` and `-` may also be unary.
+    Operator(NumericOperator),
+    /// `NOT`
+    Not,
+    /// `(`
+    OpenParen,
+    /// `)`
+    CloseParen,
+}
+
+/// Numeric expression tokenizer and evaluator
+#[derive(Clone)]
+pub struct NumericExpression<'a> {
+    pub tokenizer: Tokenizer<'a>,
+}
+
+impl<'a> NumericExpression<'a> {
+    pub fn new(s: &'a [u8]) -> NumericExpression<'a> {
+        NumericExpression {
+            tokenizer: Tokenizer::new(s),
+        }
+    }
+
+    /// Evaluate expression.
+    /// Device variables are looked up with `resolve`, if it returns [None] for a variable an
+    /// error with [ErrorCode::ExecExpressionError] is returned.
+    ///
+    /// Syntax errors are returned as [ErrorCode::InvalidExpression] while errors such as
+    /// division by zero or overflow are returned as [ErrorCode::MathErrorInExpression].
+    pub fn evaluate<F>(&self, resolve: F) -> Result<f64, Error>
+    where
+        F: FnMut(&'a [u8]) -> Option<f64>,
+    {
+        let mut evaluator = Evaluator {
+            tokens: self.clone(),
+            peeked: None,
+            resolve,
+            depth: 0,
+        };
+        let value = evaluator.expression(0)?;
+        match evaluator.next()? {
+            None => Ok(value),
+            Some(Token::CloseParen) => Err(invalid(b"Unbalanced parentheses")),
+            Some(_) => Err(invalid(b"Operator expected")),
+        }
+    }
+
+    fn read_token(&mut self) -> Result<Token<'a>, Error> {
+        let s = self.tokenizer.chars.as_slice();
+        let c = self.tokenizer.chars.next().unwrap();
+        Ok(match c {
+            b'+' => Token::Operator(NumericOperator::Add),
+            b'-' => Token::Operator(NumericOperator::Sub),
+            b'*' => Token::Operator(NumericOperator::Mul),
+            b'/' => Token::Operator(NumericOperator::Div),
+            b'^' => Token::Operator(NumericOperator::Exp),
+            b'(' => Token::OpenParen,
+            b')' => Token::CloseParen,
+            x if x.is_ascii_digit() || *x == b'.' => {
+                self.tokenizer.chars = s.iter();
+                let num = self
+                    .tokenizer
+                    .read_nrf()
+                    .map_err(|err| invalid(err.get_message()))?;
+                Token::Numeric(f64::try_from(num).map_err(|err| invalid(err.get_message()))?)
+            }
+            x if x.is_ascii_alphabetic() => {
+                while matches!(self.tokenizer.chars.clone().next(),
+                    Some(ch) if ch.is_ascii_alphanumeric() || *ch == b'_')
+                {
+                    self.tokenizer.chars.next();
+                }
+                let name = &s[..s.len() - self.tokenizer.chars.as_slice().len()];
+                const KEYWORDS: &[(&[u8], Token)] = &[
+                    (b"MOD", Token::Operator(NumericOperator::Modulus)),
+                    (b"DIV", Token::Operator(NumericOperator::Div)),
+                    (b"AND", Token::Operator(NumericOperator::And)),
+                    (b"OR", Token::Operator(NumericOperator::Or)),
+                    (b"EXOR", Token::Operator(NumericOperator::Exor)),
+                    (b"NOT", Token::Not),
+                ];
+                KEYWORDS
+                    .iter()
+                    .find(|(keyword, _)| keyword.eq_ignore_ascii_case(name))
+                    .map_or(Token::Variable(name), |(_, tok)| *tok)
+            }
+            _ => return Err(invalid(b"Invalid character")),
+        })
+    }
+}
+
+impl<'a> Iterator for NumericExpression<'a> {
+    type Item = Result<Token<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        crate::parser::tokenizer::util::skip_ws(&mut self.tokenizer.chars);
+        self.tokenizer.chars.as_slice().first()?;
+        Some(self.read_token())
+    }
+}
+
+fn invalid(msg: &'static [u8]) -> Error {
+    Error::new(ErrorCode::InvalidExpression).extended(msg)
+}
+
+/// Precedence climbing evaluator
+struct Evaluator<'a, F> {
+    tokens: NumericExpression<'a>,
+    peeked: Option<Token<'a>>,
+    resolve: F,
+    depth: usize,
+}
+
+impl<'a, F> Evaluator<'a, F>
+where
+    F: FnMut(&'a [u8]) -> Option<f64>,
+{
+    fn next(&mut self) -> Result<Option<Token<'a>>, Error> {
+        match self.peeked.take() {
+            Some(tok) => Ok(Some(tok)),
+            None => self.tokens.next().transpose(),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<Token<'a>>, Error> {
+        if self.peeked.is_none() {
+            self.peeked = self.tokens.next().transpose()?;
+        }
+        Ok(self.peeked)
+    }
+
+    /// Evaluate operators with a precedence of at least `min`
+    fn expression(&mut self, min: u8) -> Result<f64, Error> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Operator(op)) = self.peek()? {
+            if op.precedence() < min {
+                break;
+            }
+            self.next()?;
+            let rhs = self.expression(op.precedence() + 1)?;
+            lhs = op.apply(lhs, rhs)?;
+        }
+        Ok(lhs)
+    }
+
+    /// Unary operators bind tighter than everything but `^`
+    fn unary(&mut self) -> Result<f64, Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid(b"Expression too complex"));
+        }
+        let op = match self.peek()? {
+            Some(Token::Operator(NumericOperator::Add)) => Some(UnaryNumericOperator::Add),
+            Some(Token::Operator(NumericOperator::Sub)) => Some(UnaryNumericOperator::Sub),
+            Some(Token::Not) => Some(UnaryNumericOperator::Not),
+            _ => None,
+        };
+        let value = if let Some(op) = op {
+            self.next()?;
+            let value = self.unary()?;
+            op.apply(value)?
+        } else {
+            let value = self.primary()?;
+            if let Some(Token::Operator(NumericOperator::Exp)) = self.peek()? {
+                self.next()?;
+                let exp = self.unary()?;
+                NumericOperator::Exp.apply(value, exp)?
+            } else {
+                value
+            }
+        };
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn primary(&mut self) -> Result<f64, Error> {
+        match self.next()? {
+            Some(Token::Numeric(value)) => Ok(value),
+            Some(Token::Variable(name)) => (self.resolve)(name).ok_or_else(|| {
+                Error::new(ErrorCode::ExecExpressionError).extended(b"Undefined variable")
+            }),
+            Some(Token::OpenParen) => {
+                let value = self.expression(0)?;
+                match self.next()? {
+                    Some(Token::CloseParen) => Ok(value),
+                    _ => Err(invalid(b"Unbalanced parentheses")),
+                }
+            }
+            _ => Err(invalid(b"Operand expected")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    fn eval(s: &[u8]) -> Result<f64, Error> {
+        NumericExpression::new(s).evaluate(|name| match name {
+            b"VOLT_OFFSET" => Some(0.5),
+            b"X" => Some(3.0),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens: Result<std::vec::Vec<_>, _> = NumericExpression::new(b"2.5e1 MOD(x)").collect();
+        assert_eq!(
+            tokens,
+            Ok(std::vec![
+                Token::Numeric(25.0),
+                Token::Operator(NumericOperator::Modulus),
+                Token::OpenParen,
+                Token::Variable(b"x"),
+                Token::CloseParen
+            ])
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval(b"3*VOLT_OFFSET+2^4"), Ok(17.5));
+        assert_eq!(eval(b"1+2*3"), Ok(7.0));
+        assert_eq!(eval(b"(1+2)*3"), Ok(9.0));
+        assert_eq!(eval(b"10-4-3"), Ok(3.0));
+        assert_eq!(eval(b"2^3^2"), Ok(512.0));
+        assert_eq!(eval(b"-2^2"), Ok(-4.0));
+        assert_eq!(eval(b"2^-1"), Ok(0.5));
+        assert_eq!(eval(b"--X"), Ok(3.0));
+        assert_eq!(eval(b"7 MOD 4 * 2"), Ok(6.0));
+        assert_eq!(eval(b"9 DIV 2"), Ok(4.5));
+    }
+
+    #[test]
+    fn test_bitwise() {
+        assert_eq!(eval(b"12 AND 10"), Ok(8.0));
+        assert_eq!(eval(b"12 or 3"), Ok(15.0));
+        assert_eq!(eval(b"12 EXOR 10"), Ok(6.0));
+        assert_eq!(eval(b"NOT 0"), Ok(-1.0));
+        assert_eq!(eval(b"1 OR 2 AND 6"), Ok(3.0));
+        assert_eq!(
+            eval(b"1.5 AND 1"),
+            Err(Error::new(ErrorCode::MathErrorInExpression).extended(b"Integer operand expected"))
+        );
+    }
+
+    #[test]
+    fn test_math_error() {
+        assert_eq!(
+            eval(b"1/0"),
+            Err(Error::new(ErrorCode::MathErrorInExpression).extended(b"Division by zero"))
+        );
+        assert_eq!(eval(b"1 MOD (X-3)").unwrap_err().get_code(), -261);
+        assert_eq!(eval(b"10^400").unwrap_err().get_code(), -261);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            eval(b"POTATO+1"),
+            Err(Error::new(ErrorCode::ExecExpressionError).extended(b"Undefined variable"))
+        );
+        assert_eq!(
+            eval(b"(1+2"),
+            Err(Error::new(ErrorCode::InvalidExpression).extended(b"Unbalanced parentheses"))
+        );
+        assert_eq!(
+            eval(b"1+2)"),
+            Err(Error::new(ErrorCode::InvalidExpression).extended(b"Unbalanced parentheses"))
+        );
+        assert_eq!(
+            eval(b"1 2"),
+            Err(Error::new(ErrorCode::InvalidExpression).extended(b"Operator expected"))
+        );
+        assert_eq!(
+            eval(b"1*"),
+            Err(Error::new(ErrorCode::InvalidExpression).extended(b"Operand expected"))
+        );
+        assert_eq!(
+            eval(b"1 & 2"),
+            Err(Error::new(ErrorCode::InvalidExpression).extended(b"Invalid character"))
+        );
+        assert_eq!(
+            eval(&[b'-'; 40]),
+            Err(Error::new(ErrorCode::InvalidExpression).extended(b"Expression too complex"))
+        );
+    }
+}
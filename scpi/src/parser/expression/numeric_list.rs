@@ -2,8 +2,16 @@
 //!
 //! A numeric list is a an expression format for compactly expressing numbers and ranges of
 //! numbers in a single parameter.
+//!
+//! Use [NumericList::values] to iterate over the values of a list with ranges expanded.
+//! ```
+//! # use scpi::parser::expression::numeric_list::NumericList;
+//! let values: Result<Vec<i32>, _> = NumericList::new(b"1,3:5,2").values().collect();
+//! assert_eq!(values, Ok(vec![1, 3, 4, 5, 2]));
+//! ```
 
 use crate::error::{Error, ErrorCode};
+use crate::parser::tokenizer::util;
 
 type Number<'a> = crate::parser::tokenizer::Token<'a>;
 
@@ -28,19 +36,262 @@ impl<'a> NumericList<'a> {
         }
     }
 
+    /// Iterate over the values of the list converted into `T`, expanding ranges.
+    ///
+    /// Ranges are only allowed if `T` has a default step (integers),
+    /// see [Self::values_with_step] otherwise.
+    pub fn values<T>(self) -> Values<'a, T>
+    where
+        T: ListElement + TryFrom<Number<'a>, Error = Error>,
+    {
+        Values {
+            list: self,
+            step: T::unit_step(),
+            max_len: usize::MAX,
+            len: 0,
+            range: None,
+            done: false,
+        }
+    }
+
+    /// Same as [Self::values] but ranges are expanded with `step` between values.
+    /// `step` must be positive, descending ranges step downwards.
+    ///
+    /// ```
+    /// # use scpi::parser::expression::numeric_list::NumericList;
+    /// let values: Result<Vec<f64>, _> = NumericList::new(b"1:0").values_with_step(0.25).collect();
+    /// assert_eq!(values, Ok(vec![1.0, 0.75, 0.5, 0.25, 0.0]));
+    /// ```
+    pub fn values_with_step<T>(self, step: T) -> Values<'a, T>
+    where
+        T: ListElement + TryFrom<Number<'a>, Error = Error>,
+    {
+        Values {
+            step: Some(step),
+            ..self.values()
+        }
+    }
+
     fn read_numeric_data(&mut self) -> Result<Token<'a>, ErrorCode> {
-        let begin: Number = self.tokenizer.read_nrf()?;
+        let begin: Number = self.read_element()?;
         if let Some(c) = self.tokenizer.chars.clone().next() {
             //&& *c == b':' {
             if *c == b':' {
                 self.tokenizer.chars.next();
-                let end = self.tokenizer.read_nrf()?;
+                let end = self.read_element()?;
                 return Ok(Token::NumericRange(begin, end));
             }
         }
 
         Ok(Token::Numeric(begin))
     }
+
+    /// Read a numeric with an optional suffix
+    fn read_element(&mut self) -> Result<Number<'a>, ErrorCode> {
+        let num = self.tokenizer.read_nrf()?;
+        // Suffix may be preceded by white space
+        let mut chars = self.tokenizer.chars.clone();
+        util::skip_ws(&mut chars);
+        match (num, chars.clone().next()) {
+            (Number::DecimalNumericProgramData(num), Some(x))
+                if x.is_ascii_alphabetic() || *x == b'/' =>
+            {
+                self.tokenizer.chars = chars;
+                let suffix = self.tokenizer.read_suffix()?;
+                Ok(Number::DecimalNumericSuffixProgramData(num, suffix))
+            }
+            _ => Ok(num),
+        }
+    }
+}
+
+/// A type which numeric list values can be converted into
+pub trait ListElement: Sized + Copy {
+    /// Step between values of a range if none is given, [None] if ranges require a step.
+    fn unit_step() -> Option<Self>;
+
+    /// Number of values in the range from `begin` to `end` (in either direction) with `step`
+    /// between them. Returns [None] if `step` is not positive.
+    fn range_len(begin: Self, end: Self, step: Self) -> Option<usize>;
+
+    /// The `n`th value of the range from `begin` to `end`
+    fn range_nth(begin: Self, end: Self, step: Self, n: usize) -> Self;
+}
+
+macro_rules! impl_list_element_int {
+    ($($t:ty),+) => {
+        $(impl ListElement for $t {
+            fn unit_step() -> Option<Self> {
+                Some(1)
+            }
+
+            fn range_len(begin: Self, end: Self, step: Self) -> Option<usize> {
+                if step <= 0 {
+                    return None;
+                }
+                let len = (begin as i128 - end as i128).unsigned_abs() / step as u128 + 1;
+                len.try_into().ok()
+            }
+
+            fn range_nth(begin: Self, end: Self, step: Self, n: usize) -> Self {
+                let offset = n as i128 * step as i128;
+                if end < begin {
+                    (begin as i128 - offset) as $t
+                } else {
+                    (begin as i128 + offset) as $t
+                }
+            }
+        })+
+    };
+}
+
+impl_list_element_int!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize);
+
+/// Tolerance of the last value of a real range relative to the step
+const REAL_RANGE_EPSILON: f64 = 1e-9;
+
+macro_rules! impl_list_element_real {
+    ($($t:ty),+) => {
+        $(impl ListElement for $t {
+            fn unit_step() -> Option<Self> {
+                None
+            }
+
+            fn range_len(begin: Self, end: Self, step: Self) -> Option<usize> {
+                // Also rejects NaN
+                if step.partial_cmp(&0.0) != Some(core::cmp::Ordering::Greater) {
+                    return None;
+                }
+                let diff = if end < begin { begin - end } else { end - begin };
+                let steps = (diff / step) as f64 + REAL_RANGE_EPSILON;
+                if steps.is_finite() && steps < usize::MAX as f64 {
+                    // Truncates towards zero
+                    Some(steps as usize + 1)
+                } else {
+                    None
+                }
+            }
+
+            fn range_nth(begin: Self, end: Self, step: Self, n: usize) -> Self {
+                if end < begin {
+                    begin - n as $t * step
+                } else {
+                    begin + n as $t * step
+                }
+            }
+        })+
+    };
+}
+
+impl_list_element_real!(f32, f64);
+
+/// Quantities with a unit suffix, stepped in the base unit
+#[cfg(feature = "uom")]
+macro_rules! impl_list_element_quantity {
+    ($($t:ty),+) => {
+        $(impl<D, U> ListElement for uom::si::Quantity<D, U, $t>
+        where
+            D: uom::si::Dimension + ?Sized,
+            U: uom::si::Units<$t> + ?Sized,
+        {
+            fn unit_step() -> Option<Self> {
+                None
+            }
+
+            fn range_len(begin: Self, end: Self, step: Self) -> Option<usize> {
+                <$t>::range_len(begin.value, end.value, step.value)
+            }
+
+            fn range_nth(begin: Self, end: Self, step: Self, n: usize) -> Self {
+                uom::si::Quantity {
+                    dimension: core::marker::PhantomData,
+                    units: core::marker::PhantomData,
+                    value: <$t>::range_nth(begin.value, end.value, step.value, n),
+                }
+            }
+        })+
+    };
+}
+
+#[cfg(feature = "uom")]
+impl_list_element_quantity!(f32, f64);
+
+/// Iterator over the values of a [NumericList] with ranges expanded, see [NumericList::values].
+#[derive(Clone)]
+pub struct Values<'a, T> {
+    list: NumericList<'a>,
+    step: Option<T>,
+    max_len: usize,
+    len: usize,
+    /// Range being expanded (begin, end, step, next index, length)
+    range: Option<(T, T, T, usize, usize)>,
+    done: bool,
+}
+
+impl<'a, T> Values<'a, T>
+where
+    T: ListElement + TryFrom<Number<'a>, Error = Error>,
+{
+    /// Limit the total number of values, a list expanding to more values returns an error
+    /// with [ErrorCode::TooMuchData] before any of the values in the offending range.
+    pub fn max_len(self, max_len: usize) -> Self {
+        Self { max_len, ..self }
+    }
+
+    fn next_value(&mut self) -> Option<Result<T, Error>> {
+        if let Some((begin, end, step, n, len)) = self.range {
+            if n < len {
+                self.range = Some((begin, end, step, n + 1, len));
+                return Some(Ok(T::range_nth(begin, end, step, n)));
+            }
+            self.range = None;
+        }
+        Some(match self.list.next()? {
+            Ok(Token::Numeric(value)) => self.take(1).and_then(|_| T::try_from(value)),
+            Ok(Token::NumericRange(begin, end)) => self.expand(begin, end),
+            Err(err) => Err(err),
+        })
+    }
+
+    fn expand(&mut self, begin: Number<'a>, end: Number<'a>) -> Result<T, Error> {
+        let begin = T::try_from(begin)?;
+        let end = T::try_from(end)?;
+        let step = self
+            .step
+            .ok_or(Error::new(ErrorCode::InvalidExpression).extended(b"Range not allowed"))?;
+        let len = T::range_len(begin, end, step).ok_or(ErrorCode::ExecutionError)?;
+        self.take(len)?;
+        self.range = Some((begin, end, step, 1, len));
+        Ok(begin)
+    }
+
+    /// Account for `n` more values
+    fn take(&mut self, n: usize) -> Result<(), Error> {
+        match self.len.checked_add(n) {
+            Some(len) if len <= self.max_len => {
+                self.len = len;
+                Ok(())
+            }
+            _ => Err(ErrorCode::TooMuchData.into()),
+        }
+    }
+}
+
+impl<'a, T> Iterator for Values<'a, T>
+where
+    T: ListElement + TryFrom<Number<'a>, Error = Error>,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self.next_value();
+        // Stop after an error
+        self.done = !matches!(item, Some(Ok(_)));
+        item
+    }
 }
 
 impl<'a> Iterator for NumericList<'a> {
@@ -132,4 +383,91 @@ mod tests {
                 .extended(ErrorCode::NumericDataError.get_message()))
         );
     }
+
+    #[test]
+    fn test_numeric_suffix() {
+        let mut expr = NumericList::new(b"1V,2MV:3E3 MV");
+        assert_eq!(
+            expr.next().unwrap(),
+            Ok(Token::Numeric(Number::DecimalNumericSuffixProgramData(
+                b"1", b"V"
+            )))
+        );
+        assert_eq!(
+            expr.next().unwrap(),
+            Ok(Token::NumericRange(
+                Number::DecimalNumericSuffixProgramData(b"2", b"MV"),
+                Number::DecimalNumericSuffixProgramData(b"3E3", b"MV")
+            ))
+        );
+        assert_eq!(expr.next(), None);
+    }
+
+    #[test]
+    fn test_values_int() {
+        let values: Result<std::vec::Vec<i32>, _> =
+            NumericList::new(b"1,3:5,-1:-3").values().collect();
+        assert_eq!(values, Ok(std::vec![1, 3, 4, 5, -1, -2, -3]));
+
+        let values: Result<std::vec::Vec<u8>, _> =
+            NumericList::new(b"0:10").values_with_step(4).collect();
+        assert_eq!(values, Ok(std::vec![0, 4, 8]));
+
+        let values: Result<std::vec::Vec<u8>, _> = NumericList::new(b"1,256").values().collect();
+        assert_eq!(values, Err(ErrorCode::DataOutOfRange.into()));
+    }
+
+    #[test]
+    fn test_values_real() {
+        let values: Result<std::vec::Vec<f64>, _> = NumericList::new(b"0.5,0:0.3")
+            .values_with_step(0.1)
+            .collect();
+        assert_eq!(
+            values,
+            Ok(std::vec![0.5, 0.0, 0.1, 0.2, 0.30000000000000004])
+        );
+
+        // Ranges require a step
+        let mut values = NumericList::new(b"1.5,0:1").values::<f32>();
+        assert_eq!(values.next(), Some(Ok(1.5)));
+        assert_eq!(
+            values.next(),
+            Some(Err(
+                Error::new(ErrorCode::InvalidExpression).extended(b"Range not allowed")
+            ))
+        );
+        assert_eq!(values.next(), None);
+    }
+
+    #[test]
+    fn test_values_max_len() {
+        let mut values = NumericList::new(b"1,2:4,1:1000000000")
+            .values::<i32>()
+            .max_len(4);
+        assert_eq!(values.next(), Some(Ok(1)));
+        assert_eq!(values.next(), Some(Ok(2)));
+        assert_eq!(values.next(), Some(Ok(3)));
+        assert_eq!(values.next(), Some(Ok(4)));
+        assert_eq!(values.next(), Some(Err(ErrorCode::TooMuchData.into())));
+        assert_eq!(values.next(), None);
+    }
+
+    #[cfg(feature = "unit-electric-potential")]
+    #[test]
+    fn test_values_unit() {
+        use uom::si::{electric_potential::volt, f64::ElectricPotential};
+
+        let values: Result<std::vec::Vec<ElectricPotential>, _> = NumericList::new(b"1V,1:2V")
+            .values_with_step(ElectricPotential::new::<volt>(0.5))
+            .collect();
+        assert_eq!(
+            values,
+            Ok(std::vec![
+                ElectricPotential::new::<volt>(1.0),
+                ElectricPotential::new::<volt>(1.0),
+                ElectricPotential::new::<volt>(1.5),
+                ElectricPotential::new::<volt>(2.0)
+            ])
+        );
+    }
 }
@@ -0,0 +1,226 @@
+//! Splitting a buffer into <PROGRAM MESSAGE>s
+//!
+//! A transport may receive several terminated program messages at once while
+//! [Node::run](crate::tree::Node::run) only accepts a single message. [Messages] splits a buffer
+//! at each <PROGRAM MESSAGE TERMINATOR> while skipping over <STRING PROGRAM DATA> and
+//! <ARBITRARY BLOCK PROGRAM DATA> which may contain newlines.
+//!
+//! See IEEE 488.2-1992 7.5
+
+/// <PROGRAM MESSAGE TERMINATOR> and <RESPONSE MESSAGE TERMINATOR>
+///
+/// IEEE 488.2 only allows NL (optionally followed by END) but other terminators are common on
+/// serial ports. END is signalled by the transport, i.e. it's the end of the buffer passed to
+/// [Node::run](crate::tree::Node::run).
+///
+/// See IEEE 488.2-1992 7.5.2 and 8.5
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum Terminator {
+    /// `\n`
+    #[default]
+    Nl,
+    /// `\r\n`
+    CrNl,
+    /// `\r`
+    Cr,
+    /// END only, no terminating character
+    End,
+}
+
+impl Terminator {
+    /// Terminator characters
+    pub fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Terminator::Nl => b"\n",
+            Terminator::CrNl => b"\r\n",
+            Terminator::Cr => b"\r",
+            Terminator::End => b"",
+        }
+    }
+
+    /// Returns true if `s` starts with the terminator characters.
+    /// Always false for [Terminator::End].
+    pub fn is_prefix_of(&self, s: &[u8]) -> bool {
+        !self.as_bytes().is_empty() && s.starts_with(self.as_bytes())
+    }
+}
+
+/// Returns the length of the first program message in `buf` terminated by `terminator`,
+/// including the terminator, or [None] if no complete message is available yet.
+///
+/// An indefinite block (`#0`) extends to END so it is only complete when `buf` ends with the
+/// terminator. Messages terminated by [Terminator::End] are never complete.
+///
+/// ```
+/// # use scpi::parser::message::{message_len, Terminator};
+/// assert_eq!(message_len(b"*IDN?\n*RST", Terminator::Nl), Some(6));
+/// assert_eq!(message_len(b"SYST:ERR? ;*RST", Terminator::Nl), None);
+/// assert_eq!(message_len(b"DATA \"\n\",#13\n\n\n;*OPC\n", Terminator::Nl), Some(21));
+/// assert_eq!(message_len(b"*IDN?\r\n*RST", Terminator::CrNl), Some(7));
+/// ```
+pub fn message_len(buf: &[u8], terminator: Terminator) -> Option<usize> {
+    let mut i = 0;
+    while let Some(&c) = buf.get(i) {
+        match c {
+            _ if terminator.is_prefix_of(&buf[i..]) => {
+                return Some(i + terminator.as_bytes().len())
+            }
+            // Skip <STRING PROGRAM DATA>, a doubled quote continues the string
+            b'"' | b'\'' => {
+                i += 1;
+                loop {
+                    if *buf.get(i)? == c {
+                        if buf.get(i + 1) != Some(&c) {
+                            break;
+                        }
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            // Skip <ARBITRARY BLOCK PROGRAM DATA>
+            b'#' => match buf.get(i + 1) {
+                Some(b'0') => {
+                    let terminated = !terminator.as_bytes().is_empty()
+                        && buf[i + 2..].ends_with(terminator.as_bytes());
+                    return terminated.then_some(buf.len());
+                }
+                Some(d @ b'1'..=b'9') => {
+                    let digits = (d - b'0') as usize;
+                    let start = i + 2 + digits;
+                    let len = buf.get(i + 2..start)?.iter().try_fold(0usize, |len, &c| {
+                        c.is_ascii_digit()
+                            .then(|| len.checked_mul(10)?.checked_add((c - b'0') as usize))
+                            .flatten()
+                    });
+                    i = match len {
+                        Some(len) if start.checked_add(len)? <= buf.len() => start + len,
+                        // Payload not received yet
+                        Some(_) => return None,
+                        // Malformed header, left for the parser to report
+                        None => i + 1,
+                    };
+                }
+                Some(_) => i += 1,
+                None => return None,
+            },
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Iterator over the <PROGRAM MESSAGE>s in a buffer.
+///
+/// Each message includes its terminator. Any trailing bytes without a terminator are
+/// returned as a last message terminated by END.
+///
+/// ```
+/// # use scpi::parser::message::Messages;
+/// let mut messages = Messages::new(b"*RST\n*IDN?;*OPC?\n*WAI");
+/// assert_eq!(messages.next(), Some(&b"*RST\n"[..]));
+/// assert_eq!(messages.next(), Some(&b"*IDN?;*OPC?\n"[..]));
+/// assert_eq!(messages.next(), Some(&b"*WAI"[..]));
+/// assert_eq!(messages.next(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Messages<'a> {
+    buf: &'a [u8],
+    terminator: Terminator,
+}
+
+impl<'a> Messages<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            terminator: Terminator::default(),
+        }
+    }
+
+    /// Split messages at `terminator` instead of NL
+    pub fn terminator(self, terminator: Terminator) -> Self {
+        Self { terminator, ..self }
+    }
+
+    /// Bytes not yet returned
+    pub fn remainder(&self) -> &'a [u8] {
+        self.buf
+    }
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let len = message_len(self.buf, self.terminator).unwrap_or(self.buf.len());
+        let (message, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_len() {
+        assert_eq!(message_len(b"", Terminator::Nl), None);
+        assert_eq!(message_len(b"\n", Terminator::Nl), Some(1));
+        assert_eq!(message_len(b"*RST", Terminator::Nl), None);
+        // Strings
+        assert_eq!(
+            message_len(b"SYST:TEXT \"a\nb\"\n", Terminator::Nl),
+            Some(16)
+        );
+        assert_eq!(
+            message_len(b"SYST:TEXT 'a''\n'\nX", Terminator::Nl),
+            Some(17)
+        );
+        assert_eq!(message_len(b"SYST:TEXT 'a\n", Terminator::Nl), None);
+        // Definite blocks
+        assert_eq!(
+            message_len(b"DATA #210\n\n\n\n\n\n\n\n\n\n\n", Terminator::Nl),
+            Some(20)
+        );
+        assert_eq!(message_len(b"DATA #210\n\n\n", Terminator::Nl), None);
+        assert_eq!(message_len(b"DATA #2", Terminator::Nl), None);
+        // Indefinite blocks
+        assert_eq!(message_len(b"DATA #0\n\n", Terminator::Nl), Some(9));
+        assert_eq!(message_len(b"DATA #0\nA", Terminator::Nl), None);
+        // Not blocks
+        assert_eq!(message_len(b"DATA #HFF\n", Terminator::Nl), Some(10));
+        assert_eq!(message_len(b"DATA #2X\n", Terminator::Nl), Some(9));
+    }
+
+    #[test]
+    fn test_message_len_terminator() {
+        assert_eq!(message_len(b"*RST\n\r\n", Terminator::CrNl), Some(7));
+        assert_eq!(message_len(b"*RST\r\n", Terminator::Cr), Some(5));
+        assert_eq!(message_len(b"DATA #0\r\r\n", Terminator::CrNl), Some(10));
+        assert_eq!(message_len(b"DATA #0\r\n\r", Terminator::CrNl), None);
+        assert_eq!(message_len(b"*RST\n", Terminator::End), None);
+        assert_eq!(message_len(b"DATA #0\n", Terminator::End), None);
+    }
+
+    #[test]
+    fn test_messages() {
+        let mut messages = Messages::new(b"A 'x\ny'\nB #15\n\n\n\n\n\nC #0\nD\n");
+        assert_eq!(messages.next(), Some(&b"A 'x\ny'\n"[..]));
+        assert_eq!(messages.next(), Some(&b"B #15\n\n\n\n\n\n"[..]));
+        assert_eq!(messages.remainder(), b"C #0\nD\n");
+        assert_eq!(messages.next(), Some(&b"C #0\nD\n"[..]));
+        assert_eq!(messages.next(), None);
+    }
+
+    #[test]
+    fn test_messages_end() {
+        let mut messages = Messages::new(b"A\nB\n").terminator(Terminator::End);
+        assert_eq!(messages.next(), Some(&b"A\nB\n"[..]));
+        assert_eq!(messages.next(), None);
+    }
+}
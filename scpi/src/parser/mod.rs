@@ -1,12 +1,21 @@
 //! SCPI Parser and response formatter
 //!
 
+pub mod block;
+pub mod builder;
+pub mod canonical;
+pub mod config;
+#[cfg(feature = "serde")]
+pub mod de;
 pub mod expression;
+pub mod message;
 pub mod parameters;
 pub mod response;
+pub mod string;
 pub mod suffix;
 pub mod tokenizer;
 
+pub use config::ParserConfig;
 pub use tokenizer::util::{mnemonic_compare, mnemonic_match};
 
 /// Wrappers to format and discriminate SCPI types
@@ -35,4 +44,16 @@ pub mod format {
     /// Character data
     #[derive(Debug, PartialEq, Eq, Copy, Clone)]
     pub struct Character<'a>(pub &'a [u8]);
+
+    /// <NR2> real with a fixed number of decimals, e.g. `Nr2(0.25, 3)` is `0.250`
+    #[derive(Debug, PartialEq, Copy, Clone)]
+    pub struct Nr2<V>(pub V, pub u8);
+
+    /// <NR3> real with a fixed number of significant digits, e.g. `Nr3(0.25, 3)` is `2.50E-01`
+    #[derive(Debug, PartialEq, Copy, Clone)]
+    pub struct Nr3<V>(pub V, pub u8);
+
+    /// Comma separated list of the items of an iterator, e.g. `List(trace.iter().map(|x| x * 2.0))`
+    #[derive(Debug, Copy, Clone)]
+    pub struct List<I>(pub I);
 }
@@ -1,12 +1,12 @@
 //! Command parameters
 
-use core::iter::Peekable;
 use core::str;
 
-use crate::error::{Error, ErrorCode};
+use crate::error::{Error, ErrorCode, Span};
 
 use super::{
-    expression::{channel_list, numeric_list},
+    block::{BlockReader, BlockSource},
+    expression::{channel_list, numeric_expression, numeric_list},
     format,
     tokenizer::{util, Token, Tokenizer},
 };
@@ -39,12 +39,22 @@ macro_rules! parser_unreachable {
 pub(crate) use parser_unreachable;
 
 /// Parameter iterator for a command
-pub struct Parameters<'a, 'b>(&'a mut Peekable<Tokenizer<'b>>);
+pub struct Parameters<'a, 'b>(&'a mut Tokenizer<'b>, Option<&'a mut dyn BlockSource>);
 
 impl<'a, 'b> Parameters<'a, 'b> {
     /// Create a argument iterator from a tokenizer
-    pub fn with(toka: &'a mut Peekable<Tokenizer<'b>>) -> Self {
-        Self(toka)
+    ///
+    /// Earlier versions took a `Peekable<Tokenizer>`, pass the [Tokenizer] itself instead.
+    pub fn with(toka: &'a mut Tokenizer<'b>) -> Self {
+        Self(toka, None)
+    }
+
+    /// Create a argument iterator from a tokenizer which may stream block data from `stream`
+    pub(crate) fn with_stream(
+        toka: &'a mut Tokenizer<'b>,
+        stream: Option<&'a mut (dyn BlockSource + '_)>,
+    ) -> Self {
+        Self(toka, stream.map(|stream| stream as &mut dyn BlockSource))
     }
 }
 
@@ -57,12 +67,19 @@ impl<'a, 'b> Parameters<'a, 'b> {
 
         if let Some(item) = self.0.peek() {
             //Check if next item is a data object
-            let token = (*item)?;
+            let token = match *item {
+                Ok(token) => token,
+                Err(err) => {
+                    let span = self.0.peek_span().unwrap_or_default();
+                    return Err(Error::new(err).with_span(span));
+                }
+            };
             match token {
                 //Data object
                 t if t.is_data() => {
                     //Valid data object, consume and return
                     self.0.next();
+                    self.begin_stream(token);
                     Ok(Some(token))
                 }
                 //Data separator, next token must be a data object
@@ -78,12 +95,26 @@ impl<'a, 'b> Parameters<'a, 'b> {
         }
     }
 
+    /// Returns true if more data follows, without consuming it.
+    #[cfg(feature = "serde")]
+    pub(crate) fn has_next(&mut self) -> bool {
+        match self.0.peek() {
+            Some(Ok(token)) => token.is_data() || matches!(token, Token::ProgramDataSeparator),
+            // Reported by the next read
+            Some(Err(_)) => true,
+            None => false,
+        }
+    }
+
     /// Get next data token.
     /// If no data is found a error with [ErrorCode::MissingParameter] is returned instead.
     pub fn next_token(&mut self) -> Result<Token<'a>, Error> {
         match self.next_optional_token() {
             Ok(Some(tok)) => Ok(tok),
-            Ok(None) => Err(ErrorCode::MissingParameter.into()),
+            Ok(None) => {
+                let end = self.0.span().end;
+                Err(Error::new(ErrorCode::MissingParameter).with_span(Span::new(end, end)))
+            }
             Err(err) => Err(err),
         }
     }
@@ -96,7 +127,8 @@ impl<'a, 'b> Parameters<'a, 'b> {
     where
         T: TryFrom<Token<'a>, Error = Error>,
     {
-        self.next_token()?.try_into()
+        let token = self.next_token()?;
+        token.try_into().map_err(|err| self.locate::<T>(token, err))
     }
 
     /// Same as [`Self::next_optional_token`] but attempts to convert the data token into type T.
@@ -109,14 +141,76 @@ impl<'a, 'b> Parameters<'a, 'b> {
     {
         let tok = self.next_optional_token()?;
         match tok {
-            Some(tok) => Ok(Some(tok.try_into()?)),
+            Some(tok) => Ok(Some(
+                tok.try_into().map_err(|err| self.locate::<T>(tok, err))?,
+            )),
             None => Ok(None),
         }
     }
+
+    /// Get next data token as a reader over the payload of a <ARBITRARY BLOCK PROGRAM DATA>.
+    ///
+    /// Unlike [`Self::next_data`] the payload does not have to fit in the input buffer when
+    /// the message is executed with [Node::run_stream](crate::tree::Node::run_stream).
+    /// A block read this way must be the last parameter of the program message unit.
+    ///
+    /// If no data is found a error with [ErrorCode::MissingParameter] is returned instead.
+    pub fn next_block(&mut self) -> Result<BlockReader<'_>, Error> {
+        match self.next_token()? {
+            Token::ArbitraryBlockData(s) => Ok(BlockReader::new(s)),
+            Token::ArbitraryBlockStream(s, len) => {
+                let Some(stream) = self.1.as_deref_mut() else {
+                    return parser_unreachable!();
+                };
+                // The last byte was held back by begin_stream
+                let data = match len {
+                    None => s.split_last().map_or(s, |(_, data)| data),
+                    Some(_) => s,
+                };
+                Ok(BlockReader::streamed(data, len, stream))
+            }
+            _ => Err(Error::new(ErrorCode::DataTypeError).with_span(self.0.span())),
+        }
+    }
+
+    /// Start tracking the payload of a streamed block handed out in any form, so that the
+    /// part still in the transport is skipped even if the handler doesn't read it.
+    fn begin_stream(&mut self, token: Token<'a>) {
+        if let (Token::ArbitraryBlockStream(s, len), Some(stream)) = (token, self.1.as_deref_mut())
+        {
+            // Payload is followed by NL^END when indefinite, hold the last byte back
+            let held = if len.is_none() {
+                s.last().copied()
+            } else {
+                None
+            };
+            stream.begin(len.map(|len| len - s.len()), held);
+        }
+    }
+
+    /// Attach the location of a token which failed to convert to the error.
+    /// If the numeric part of a suffixed numeric converts fine, the suffix is blamed instead.
+    fn locate<T>(&self, token: Token<'a>, err: Error) -> Error
+    where
+        T: TryFrom<Token<'a>, Error = Error>,
+    {
+        let span = match token {
+            Token::DecimalNumericSuffixProgramData(num, suffix)
+                if T::try_from(Token::DecimalNumericProgramData(num)).is_ok() =>
+            {
+                self.0.span_of(suffix)
+            }
+            _ => None,
+        };
+        err.or_span(span.unwrap_or_else(|| self.0.span()))
+    }
 }
 
 /// Convert string data data into a slice (&\[u8\]).
 ///
+/// The slice is the raw string contents, embedded quotes are still doubled.
+/// Use [ScpiString](crate::parser::string::ScpiString) to get the unescaped string.
+///
 /// # Returns
 /// * `Ok(&[u8])` - If data is a string.
 /// * `Err(DataTypeError)` - If data is not a string.
@@ -126,7 +220,7 @@ impl<'a> TryFrom<Token<'a>> for &'a [u8] {
 
     fn try_from(value: Token<'a>) -> Result<&'a [u8], Self::Error> {
         match value {
-            Token::StringProgramData(s) => Ok(s),
+            Token::StringProgramData(s) => Ok(s.raw()),
             t => {
                 if t.is_data() {
                     Err(ErrorCode::DataTypeError.into())
@@ -176,19 +270,26 @@ impl<'a> TryFrom<Token<'a>> for bool {
 
 /// Convert string/block data data into a str.
 ///
+/// String data is not unescaped, see [ScpiString](crate::parser::string::ScpiString).
+///
 /// # Returns
 /// * `Ok(&str)` - If data is a string or block data.
 /// * `Err(DataTypeError)` - If data is not a string.
 /// * `Err(StringDataError)` - If string is not valid utf8
+/// * `Err(TooMuchData)` - If block data does not fit the input buffer, see [Parameters::next_block].
 /// * `Err(SyntaxError)` - If token is not data
 impl<'a> TryFrom<Token<'a>> for &'a str {
     type Error = Error;
 
     fn try_from(value: Token<'a>) -> Result<&'a str, Self::Error> {
         match value {
-            Token::StringProgramData(s) | Token::ArbitraryBlockData(s) => {
+            Token::StringProgramData(s) => {
+                str::from_utf8(s.raw()).map_err(|_| ErrorCode::StringDataError.into())
+            }
+            Token::ArbitraryBlockData(s) => {
                 str::from_utf8(s).map_err(|_| ErrorCode::StringDataError.into())
             }
+            Token::ArbitraryBlockStream(..) => Err(ErrorCode::TooMuchData.into()),
             t => {
                 if t.is_data() {
                     Err(ErrorCode::DataTypeError.into())
@@ -212,6 +313,7 @@ impl<'a> TryFrom<Token<'a>> for format::Arbitrary<'a> {
     fn try_from(value: Token<'a>) -> Result<format::Arbitrary<'a>, Self::Error> {
         match value {
             Token::ArbitraryBlockData(s) => Ok(format::Arbitrary(s)),
+            Token::ArbitraryBlockStream(..) => Err(ErrorCode::TooMuchData.into()),
             t => {
                 if t.is_data() {
                     Err(ErrorCode::DataTypeError.into())
@@ -263,6 +365,31 @@ impl<'a> TryFrom<Token<'a>> for numeric_list::NumericList<'a> {
     }
 }
 
+/// Convert expression data into a numeric expression.
+///
+/// # Returns
+/// * `Ok(NumericExpression)` - If data is an expression.
+/// * `Err(DataTypeError)` - If data is not an expression.
+/// * `Err(SyntaxError)` - If token is not data
+impl<'a> TryFrom<Token<'a>> for numeric_expression::NumericExpression<'a> {
+    type Error = Error;
+
+    fn try_from(
+        value: Token<'a>,
+    ) -> Result<numeric_expression::NumericExpression<'a>, Self::Error> {
+        match value {
+            Token::ExpressionProgramData(s) => Ok(numeric_expression::NumericExpression::new(s)),
+            t => {
+                if t.is_data() {
+                    Err(ErrorCode::DataTypeError.into())
+                } else {
+                    parser_unreachable!()
+                }
+            }
+        }
+    }
+}
+
 impl<'a> TryFrom<Token<'a>> for channel_list::ChannelList<'a> {
     type Error = Error;
 
@@ -306,15 +433,18 @@ macro_rules! impl_tryfrom_float {
 
             fn try_from(value: Token) -> Result<Self, Self::Error> {
                 match value {
-                    Token::DecimalNumericProgramData(value) => lexical_core::parse::<$from>(value)
-                        .map_err(|e| match e {
+                    Token::DecimalNumericProgramData(value) => {
+                        let mut buf = [0u8; util::NRF_BUFFER_SIZE];
+                        let value = util::normalize_nrf(value, &mut buf)?;
+                        lexical_core::parse::<$from>(value).map_err(|e| match e {
                             lexical_core::Error::InvalidDigit(_) => {
                                 ErrorCode::InvalidCharacterInNumber.into()
                             }
                             lexical_core::Error::Overflow(_)
                             | lexical_core::Error::Underflow(_) => ErrorCode::DataOutOfRange.into(),
                             _ => ErrorCode::NumericDataError.into(),
-                        }),
+                        })
+                    }
                     Token::CharacterProgramData(s) => match s {
                         //Check for special float values
                         ref x if util::mnemonic_compare(b"INFinity", x) => Ok(<$from>::INFINITY),
@@ -353,38 +483,44 @@ macro_rules! impl_tryfrom_integer {
 
             fn try_from(value: Token) -> Result<Self, Self::Error> {
                 match value {
-                    Token::DecimalNumericProgramData(value) => lexical_core::parse::<$from>(value)
-                        .or_else(|e| {
-                            if matches!(e, lexical_core::Error::InvalidDigit(_)) {
-                                let value = lexical_core::parse::<$intermediate>(value)?;
-
-                                if !value.is_normal() {
-                                    Err(lexical_core::Error::Overflow(0).into())
-                                } else if value > (<$from>::MAX as $intermediate) {
-                                    Err(lexical_core::Error::Overflow(0).into())
-                                } else if value < (<$from>::MIN as $intermediate) {
-                                    Err(lexical_core::Error::Underflow(0).into())
-                                } else {
-                                    // <f32|f64>::round() doesn't exist in no_std...
-                                    // Safe because value is checked to be normal and within bounds earlier
-                                    if value.is_sign_positive() {
-                                        Ok(unsafe { (value + 0.5).to_int_unchecked() })
+                    Token::DecimalNumericProgramData(value) => {
+                        let mut buf = [0u8; util::NRF_BUFFER_SIZE];
+                        let value = util::normalize_nrf(value, &mut buf)?;
+                        lexical_core::parse::<$from>(value)
+                            .or_else(|e| {
+                                if matches!(e, lexical_core::Error::InvalidDigit(_)) {
+                                    let value = lexical_core::parse::<$intermediate>(value)?;
+
+                                    if !value.is_normal() {
+                                        Err(lexical_core::Error::Overflow(0).into())
+                                    } else if value > (<$from>::MAX as $intermediate) {
+                                        Err(lexical_core::Error::Overflow(0).into())
+                                    } else if value < (<$from>::MIN as $intermediate) {
+                                        Err(lexical_core::Error::Underflow(0).into())
                                     } else {
-                                        Ok(unsafe { (value - 0.5).to_int_unchecked() })
+                                        // <f32|f64>::round() doesn't exist in no_std...
+                                        // Safe because value is checked to be normal and within bounds earlier
+                                        if value.is_sign_positive() {
+                                            Ok(unsafe { (value + 0.5).to_int_unchecked() })
+                                        } else {
+                                            Ok(unsafe { (value - 0.5).to_int_unchecked() })
+                                        }
                                     }
+                                } else {
+                                    Err(e)
                                 }
-                            } else {
-                                Err(e)
-                            }
-                        })
-                        .map_err(|e| match e {
-                            lexical_core::Error::InvalidDigit(_) => {
-                                ErrorCode::InvalidCharacterInNumber.into()
-                            }
-                            lexical_core::Error::Overflow(_)
-                            | lexical_core::Error::Underflow(_) => ErrorCode::DataOutOfRange.into(),
-                            _ => ErrorCode::NumericDataError.into(),
-                        }),
+                            })
+                            .map_err(|e| match e {
+                                lexical_core::Error::InvalidDigit(_) => {
+                                    ErrorCode::InvalidCharacterInNumber.into()
+                                }
+                                lexical_core::Error::Overflow(_)
+                                | lexical_core::Error::Underflow(_) => {
+                                    ErrorCode::DataOutOfRange.into()
+                                }
+                                _ => ErrorCode::NumericDataError.into(),
+                            })
+                    }
                     Token::NonDecimalNumericProgramData(value) => {
                         <$from>::try_from(value).map_err(|_| ErrorCode::DataOutOfRange.into())
                     }
@@ -0,0 +1,244 @@
+//! Binary array response data
+//!
+//! Long arrays such as traces are slow to transfer as comma separated <NR3>s. [ArrayData]
+//! instead formats them as a definite <ARBITRARY BLOCK RESPONSE DATA> of IEEE 754 or two's
+//! complement integers, as selected by the SCPI `FORMat[:DATA]` and `FORMat:BORDer` commands.
+//!
+//! ```
+//! # use scpi::parser::response::{ArrayData, ByteOrder, DataFormat, Formatter};
+//! let data = [1.0f32, -2.0];
+//! let mut response = Vec::new();
+//! response
+//!     .response_unit()
+//!     .unwrap()
+//!     .data(ArrayData::new(&data))
+//!     .data(ArrayData::new(&data).format(DataFormat::Real32))
+//!     .data(ArrayData::new(&data).format(DataFormat::Int16).byte_order(ByteOrder::Swapped))
+//!     .finish()
+//!     .unwrap();
+//! assert_eq!(
+//!     response,
+//!     b"1.0,-2.0,#18\x3f\x80\x00\x00\xc0\x00\x00\x00,#14\x01\x00\xfe\xff"
+//! );
+//! ```
+
+use super::{format_sequence, Formatter, ResponseData};
+use crate::error::{ErrorCode, Result};
+use lexical_core::FormattedSize;
+
+/// Byte order of binary data, see SCPI-99 9.1 `FORMat:BORDer`
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum ByteOrder {
+    /// Most significant byte first (big-endian)
+    #[default]
+    Normal,
+    /// Least significant byte first (little-endian)
+    Swapped,
+}
+
+/// Array data format, see SCPI-99 9.2 `FORMat[:DATA]`
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum DataFormat {
+    /// Comma separated response data
+    #[default]
+    Ascii,
+    /// 16-bit two's complement integers
+    Int16,
+    /// 32-bit two's complement integers
+    Int32,
+    /// IEEE 754 single precision floats
+    Real32,
+    /// IEEE 754 double precision floats
+    Real64,
+}
+
+impl DataFormat {
+    /// Size in bytes of each binary element, 0 for [DataFormat::Ascii]
+    pub fn element_size(&self) -> usize {
+        match self {
+            DataFormat::Ascii => 0,
+            DataFormat::Int16 => 2,
+            DataFormat::Int32 | DataFormat::Real32 => 4,
+            DataFormat::Real64 => 8,
+        }
+    }
+}
+
+/// An element of [ArrayData].
+///
+/// Elements are converted to the selected binary format with `as`, i.e. reals are truncated
+/// and saturated when sent as integers.
+pub trait ArrayElement: ResponseData + Copy {
+    /// Value sent as [DataFormat::Int16]. Wider integers wrap around, reals are truncated
+    /// towards zero and saturate at the bounds of `i16`, NaN becomes 0.
+    fn to_i16(self) -> i16;
+    /// Value sent as [DataFormat::Int32]. Reals are truncated towards zero and saturate at the
+    /// bounds of `i32`, NaN becomes 0.
+    fn to_i32(self) -> i32;
+    /// Value sent as [DataFormat::Real32]. `i32` and `f64` are rounded to the nearest `f32`,
+    /// `f64` values outside of its range become infinite.
+    fn to_f32(self) -> f32;
+    /// Value sent as [DataFormat::Real64], exact for every element type.
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_array_element {
+    ($($typ:ty),*) => {
+        $(
+            impl ArrayElement for $typ {
+                fn to_i16(self) -> i16 {
+                    self as i16
+                }
+                fn to_i32(self) -> i32 {
+                    self as i32
+                }
+                fn to_f32(self) -> f32 {
+                    self as f32
+                }
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_array_element!(i16, i32, f32, f64);
+
+/// Array response data in the selected [DataFormat] and [ByteOrder].
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ArrayData<'a, T> {
+    data: &'a [T],
+    format: DataFormat,
+    order: ByteOrder,
+}
+
+impl<'a, T> ArrayData<'a, T>
+where
+    T: ArrayElement,
+{
+    /// ASCII array data
+    pub fn new(data: &'a [T]) -> Self {
+        Self {
+            data,
+            format: DataFormat::default(),
+            order: ByteOrder::default(),
+        }
+    }
+
+    /// Format data as `format` instead of ASCII
+    pub fn format(self, format: DataFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    /// Use `order` for binary data instead of [ByteOrder::Normal]
+    pub fn byte_order(self, order: ByteOrder) -> Self {
+        Self { order, ..self }
+    }
+}
+
+macro_rules! push_bytes {
+    ($formatter:ident, $order:expr, $x:expr) => {
+        match $order {
+            ByteOrder::Normal => $formatter.push_str(&$x.to_be_bytes()),
+            ByteOrder::Swapped => $formatter.push_str(&$x.to_le_bytes()),
+        }
+    };
+}
+
+impl<'a, T> ResponseData for ArrayData<'a, T>
+where
+    T: ArrayElement,
+{
+    fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+        if self.format == DataFormat::Ascii {
+            return format_sequence::<T, _>(self.data.iter(), formatter);
+        }
+
+        // Definite block header
+        let len = self
+            .data
+            .len()
+            .checked_mul(self.format.element_size())
+            .ok_or(ErrorCode::ExecutionError)?;
+        let mut buf = [0u8; usize::FORMATTED_SIZE_DECIMAL];
+        let slc = lexical_core::write::<usize>(len, &mut buf);
+        if slc.len() > 9 {
+            return Err(ErrorCode::ExecutionError.into());
+        }
+        formatter.push_byte(b'#')?;
+        slc.len().format_response_data(formatter)?;
+        formatter.push_str(slc)?;
+
+        for x in self.data {
+            match self.format {
+                DataFormat::Int16 => push_bytes!(formatter, self.order, x.to_i16()),
+                DataFormat::Int32 => push_bytes!(formatter, self.order, x.to_i32()),
+                DataFormat::Real32 => push_bytes!(formatter, self.order, x.to_f32()),
+                DataFormat::Real64 => push_bytes!(formatter, self.order, x.to_f64()),
+                DataFormat::Ascii => unreachable!(),
+            }?;
+        }
+        Ok(())
+    }
+
+    /// Empty ASCII arrays are empty, binary ones are still an (empty) block
+    fn is_empty(&self) -> bool {
+        self.format == DataFormat::Ascii && self.data.is_empty()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    fn format<T: ArrayElement>(data: ArrayData<T>) -> std::vec::Vec<u8> {
+        let mut buf = std::vec::Vec::new();
+        data.format_response_data(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_ascii() {
+        assert_eq!(format(ArrayData::new(&[1i16, -2, 3])), b"1,-2,3");
+        assert_eq!(format(ArrayData::new(&[0.5f64])), b"0.5");
+        let empty: [i32; 0] = [];
+        assert!(ArrayData::new(&empty).is_empty());
+        assert_eq!(format(ArrayData::new(&empty)), b"");
+    }
+
+    #[test]
+    fn test_binary() {
+        let data = [1i16, -2];
+        assert_eq!(
+            format(ArrayData::new(&data).format(DataFormat::Int16)),
+            b"#14\x00\x01\xff\xfe"
+        );
+        assert_eq!(
+            format(
+                ArrayData::new(&data)
+                    .format(DataFormat::Int32)
+                    .byte_order(ByteOrder::Swapped)
+            ),
+            b"#18\x01\x00\x00\x00\xfe\xff\xff\xff"
+        );
+        assert_eq!(
+            format(ArrayData::new(&data).format(DataFormat::Real64)),
+            b"#216\x3f\xf0\x00\x00\x00\x00\x00\x00\xc0\x00\x00\x00\x00\x00\x00\x00"
+        );
+        // Saturated
+        assert_eq!(
+            format(ArrayData::new(&[1e6f32, 2.5]).format(DataFormat::Int16)),
+            b"#14\x7f\xff\x00\x02"
+        );
+        // Empty
+        let empty: [i32; 0] = [];
+        assert!(!ArrayData::new(&empty).format(DataFormat::Real32).is_empty());
+        assert_eq!(
+            format(ArrayData::new(&empty).format(DataFormat::Real32)),
+            b"#10"
+        );
+    }
+}
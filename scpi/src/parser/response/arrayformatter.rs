@@ -1,10 +1,9 @@
 use arrayvec::ArrayVec;
 
 use crate::error::{ErrorCode, Result};
+use crate::parser::message::Terminator;
 
-use super::{
-    Formatter, ResponseUnit, RESPONSE_MESSAGE_TERMINATOR, RESPONSE_MESSAGE_UNIT_SEPARATOR,
-};
+use super::{Formatter, ResponseUnit};
 
 impl<const CAP: usize> Formatter for ArrayVec<u8, CAP> {
     /// Internal use
@@ -33,20 +32,12 @@ impl<const CAP: usize> Formatter for ArrayVec<u8, CAP> {
         Ok(())
     }
 
-    fn message_end(&mut self) -> Result<()> {
-        self.push_byte(RESPONSE_MESSAGE_TERMINATOR)
+    fn message_end(&mut self, terminator: Terminator) -> Result<()> {
+        self.push_str(terminator.as_bytes())
     }
 
     fn response_unit(&mut self) -> Result<ResponseUnit> {
-        if !self.is_empty() {
-            self.push_byte(RESPONSE_MESSAGE_UNIT_SEPARATOR)?;
-        }
-        Ok(ResponseUnit {
-            fmt: self,
-            result: Ok(()),
-            has_header: false,
-            has_data: false,
-        })
+        Ok(ResponseUnit::new(self))
     }
 }
 
@@ -69,7 +60,7 @@ mod tests {
             .unwrap();
         // Second unit
         array.response_unit().unwrap().data(42i16).finish().unwrap();
-        array.message_end().unwrap();
+        array.message_end(Terminator::Nl).unwrap();
         assert_eq!(array.as_slice(), b"\"potato\",0;42\n");
     }
 
@@ -1,12 +1,26 @@
 //! Response formatting
 
-use super::format::{Arbitrary, Binary, Character, Expression, Hex, Octal};
+use super::format::{Arbitrary, Binary, Character, Expression, Hex, List, Nr2, Nr3, Octal};
+use super::message::Terminator;
 use crate::error::{Error, ErrorCode, Result};
+use core::borrow::Borrow;
 
+mod array;
 #[cfg(feature = "arrayvec")]
 mod arrayformatter;
+mod real;
+mod sliceformatter;
+pub mod tokenizer;
 #[cfg(feature = "alloc")]
 mod vecformatter;
+pub mod writeformatter;
+
+pub use array::{ArrayData, ArrayElement, ByteOrder, DataFormat};
+use real::WithPrecision;
+pub use real::{Precision, Real};
+pub use sliceformatter::SliceFormatter;
+pub use tokenizer::{ResponseToken, ResponseTokenizer};
+pub use writeformatter::{Write, WriteFormatter};
 
 use lexical_core::FormattedSize;
 use lexical_core::NumberFormatBuilder;
@@ -14,11 +28,56 @@ use lexical_core::NumberFormatBuilder;
 const RESPONSE_DATA_SEPARATOR: u8 = b',';
 const RESPONSE_HEADER_SEPARATOR: u8 = b' ';
 const RESPONSE_MESSAGE_UNIT_SEPARATOR: u8 = b';';
-const RESPONSE_MESSAGE_TERMINATOR: u8 = b'\n';
 
 /// A type which can be formatted for a SCPI response
+///
+/// Sequences (slices, arrays, tuples, `Vec`, [List] etc.) are formatted as comma separated
+/// response data. Empty data, i.e. `None` or a sequence without any non-empty elements, is left
+/// out of the response together with its separator.
+///
+/// `&[u8]` is formatted as `<STRING RESPONSE DATA>`, not as a list of numbers. Slices of other
+/// element types are lists, any slice can also be formatted as one with `List(slice.iter())`:
+/// ```
+/// # use scpi::parser::{format::{Hex, List}, response::{Formatter, ResponseData}};
+/// let mut buf = Vec::new();
+/// b"abc".as_slice().format_response_data(&mut buf).unwrap();
+/// assert_eq!(buf, br#""abc""#);
+///
+/// let mut buf = Vec::new();
+/// List([Hex(1u8), Hex(255)].as_slice().iter())
+///     .format_response_data(&mut buf)
+///     .unwrap();
+/// assert_eq!(buf, b"#H1,#HFF");
+/// ```
 pub trait ResponseData {
     fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()>;
+
+    /// Returns true if there's nothing to format
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// Format non-empty `items` separated by commas
+fn format_sequence<T, I>(items: I, formatter: &mut dyn Formatter) -> Result<()>
+where
+    T: ResponseData,
+    I: Iterator,
+    I::Item: Borrow<T>,
+{
+    let mut first = true;
+    for item in items {
+        let item = item.borrow();
+        if item.is_empty() {
+            continue;
+        }
+        if !first {
+            formatter.data_separator()?;
+        }
+        item.format_response_data(formatter)?;
+        first = false;
+    }
+    Ok(())
 }
 
 macro_rules! impl_non_decimal_data {
@@ -80,9 +139,15 @@ macro_rules! impl_real {
                         formatter.push_str(b"9.9E+37")
                     }
                 } else {
-                    let mut buf = [b'0'; <$typ>::FORMATTED_SIZE_DECIMAL];
-                    let slc = lexical_core::write::<$typ>(*self, &mut buf);
-                    formatter.push_str(slc)
+                    match formatter.precision() {
+                        Precision::Shortest => {
+                            let mut buf = [b'0'; <$typ>::FORMATTED_SIZE_DECIMAL];
+                            let slc = lexical_core::write::<$typ>(*self, &mut buf);
+                            formatter.push_str(slc)
+                        }
+                        Precision::Decimals(n) => Nr2(*self, n).format_response_data(formatter),
+                        Precision::Significant(n) => Nr3(*self, n).format_response_data(formatter),
+                    }
                 }
             }
         }
@@ -142,20 +207,24 @@ impl<'a> ResponseData for &'a [u8] {
         if !self.is_ascii() {
             Err(ErrorCode::ExecutionError.into())
         } else {
-            let mut first = true;
             formatter.push_byte(b'"')?;
-            for ss in self.split(|x| *x == b'"') {
-                if !first {
-                    formatter.push_str(br#""""#)?;
-                }
-                formatter.push_ascii(ss)?;
-                first = false;
-            }
+            push_quoted(self, formatter)?;
             formatter.push_byte(b'"')
         }
     }
 }
 
+/// Push the contents of <STRING RESPONSE DATA>, doubling embedded quotes
+fn push_quoted(s: &[u8], formatter: &mut dyn Formatter) -> Result<()> {
+    for (i, ss) in s.split(|x| *x == b'"').enumerate() {
+        if i > 0 {
+            formatter.push_str(br#""""#)?;
+        }
+        formatter.push_str(ss)?;
+    }
+    Ok(())
+}
+
 impl ResponseData for Error {
     fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
         self.get_code().format_response_data(formatter)?;
@@ -163,9 +232,9 @@ impl ResponseData for Error {
 
         if let Some(ext) = self.get_extended() {
             formatter.push_byte(b'"')?;
-            formatter.push_str(self.get_message())?;
+            push_quoted(self.get_message(), formatter)?;
             formatter.push_byte(b';')?;
-            formatter.push_str(ext)?;
+            push_quoted(ext, formatter)?;
             formatter.push_byte(b'"')
         } else {
             self.get_message().format_response_data(formatter)
@@ -173,34 +242,152 @@ impl ResponseData for Error {
     }
 }
 
-impl<T> ResponseData for T
+impl<T> ResponseData for Option<T>
+where
+    T: ResponseData,
+{
+    fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+        match self {
+            Some(data) => data.format_response_data(formatter),
+            None => Ok(()),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Some(data) => data.is_empty(),
+            None => true,
+        }
+    }
+}
+
+impl<T, const N: usize> ResponseData for [T; N]
+where
+    T: ResponseData,
+{
+    fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+        format_sequence::<T, _>(self.iter(), formatter)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.iter().all(|x| x.is_empty())
+    }
+}
+
+impl<T> ResponseData for &T
+where
+    T: ResponseData + ?Sized,
+{
+    fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+        (**self).format_response_data(formatter)
+    }
+
+    fn is_empty(&self) -> bool {
+        (**self).is_empty()
+    }
+}
+
+// `&[u8]` is string data (see [ResponseData]) so slices are implemented for the remaining
+// element types only.
+macro_rules! impl_slice {
+    ($($typ:ty),*) => {
+        $(
+            impl<'a> ResponseData for &'a [$typ] {
+                fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+                    format_sequence::<$typ, _>(self.iter(), formatter)
+                }
+
+                fn is_empty(&self) -> bool {
+                    self.iter().all(|x| x.is_empty())
+                }
+            }
+        )*
+    };
+}
+
+impl_slice!(
+    i8,
+    u16,
+    i16,
+    u32,
+    i32,
+    u64,
+    i64,
+    isize,
+    usize,
+    f32,
+    f64,
+    bool,
+    &'a [u8],
+    &'a str
+);
+
+impl<I> ResponseData for List<I>
 where
-    T: crate::option::ScpiEnum,
+    I: Iterator + Clone,
+    I::Item: ResponseData,
 {
     fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
-        let mnemonic = self.mnemonic();
-        let short_form = mnemonic.split(|c| !c.is_ascii_uppercase()).next().unwrap();
-        formatter.push_str(short_form)
+        format_sequence::<I::Item, _>(self.0.clone(), formatter)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.clone().all(|x| x.is_empty())
     }
 }
 
+macro_rules! impl_tuple {
+    ($($name:ident),+) => {
+        impl<$($name),+> ResponseData for ($($name,)+)
+        where
+            $($name: ResponseData),+
+        {
+            #[allow(non_snake_case)]
+            fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+                let ($($name,)+) = self;
+                let mut first = true;
+                $(
+                    if !$name.is_empty() {
+                        if !first {
+                            formatter.data_separator()?;
+                        }
+                        $name.format_response_data(formatter)?;
+                        first = false;
+                    }
+                )+
+                let _ = first;
+                Ok(())
+            }
+
+            #[allow(non_snake_case)]
+            fn is_empty(&self) -> bool {
+                let ($($name,)+) = self;
+                $($name.is_empty())&&+
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+
 #[cfg(feature = "alloc")]
 impl<T> ResponseData for alloc::vec::Vec<T>
 where
     T: ResponseData,
 {
     fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
-        let mut it = self.iter();
-
-        let first = it
-            .next()
-            .ok_or_else(|| Error::new(ErrorCode::DeviceSpecificError))?;
-        first.format_response_data(formatter)?;
-        for func in it {
-            formatter.push_byte(b',')?;
-            func.format_response_data(formatter)?;
-        }
-        Ok(())
+        format_sequence::<T, _>(self.iter(), formatter)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.iter().all(|x| x.is_empty())
     }
 }
 
@@ -210,17 +397,11 @@ where
     T: ResponseData,
 {
     fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
-        let mut it = self.iter();
-
-        let first = it
-            .next()
-            .ok_or_else(|| Error::new(ErrorCode::DeviceSpecificError))?;
-        first.format_response_data(formatter)?;
-        while let Some(func) = it.next() {
-            formatter.push_byte(b',')?;
-            func.format_response_data(formatter)?;
-        }
-        Ok(())
+        format_sequence::<T, _>(self.iter(), formatter)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.iter().all(|x| x.is_empty())
     }
 }
 
@@ -259,8 +440,8 @@ pub trait Formatter {
     /// Start a response message
     fn message_start(&mut self) -> Result<()>;
 
-    /// End a response message
-    fn message_end(&mut self) -> Result<()>;
+    /// End a response message with `terminator`
+    fn message_end(&mut self, terminator: Terminator) -> Result<()>;
 
     /* Formatters */
 
@@ -274,23 +455,71 @@ pub trait Formatter {
         self.push_byte(RESPONSE_HEADER_SEPARATOR)
     }
 
+    /// Start an <INDEFINITE LENGTH ARBITRARY BLOCK RESPONSE DATA> (`#0`). The block lasts
+    /// until the end of the response message so no more data may follow it.
+    ///
+    /// Only formatters which can enforce this support it, others return
+    /// [ExecutionError](ErrorCode::ExecutionError).
+    fn indefinite_block(&mut self) -> Result<()> {
+        Err(ErrorCode::ExecutionError.into())
+    }
+
+    /// Precision of reals formatted without one of their own, see [ResponseUnit::precision]
+    fn precision(&self) -> Precision {
+        Precision::Shortest
+    }
+
     fn response_unit(&mut self) -> Result<ResponseUnit>;
 }
 
 /// A response unit returned by a query
+///
+/// The unit is separated from the previous one only once it writes a header or data, and
+/// [Self::finish] returns an error if it wrote neither since a query must respond.
 pub struct ResponseUnit<'a> {
     fmt: &'a mut dyn Formatter,
     result: Result<()>,
+    // Previous units have written something
+    separator: bool,
     has_header: bool,
     has_data: bool,
+    precision: Precision,
 }
 
 impl<'a> ResponseUnit<'a> {
+    fn new(fmt: &'a mut dyn Formatter) -> Self {
+        Self {
+            separator: !fmt.is_empty(),
+            fmt,
+            result: Ok(()),
+            has_header: false,
+            has_data: false,
+            precision: Precision::Shortest,
+        }
+    }
+
+    /// Format reals in the following data with `precision` unless they have their own, e.g.
+    /// [Nr2] or [Real](real::Real). Set from [Device::precision](crate::Device::precision) for
+    /// queries run by a [Node](crate::tree::Node).
+    pub fn precision(&mut self, precision: Precision) -> &mut Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Write the response message unit separator before the first header or data
+    fn separate(&mut self) -> Result<()> {
+        if self.separator && !self.has_header && !self.has_data {
+            self.fmt.push_byte(RESPONSE_MESSAGE_UNIT_SEPARATOR)?;
+        }
+        Ok(())
+    }
+
     /// Response header
     ///
     /// **Warning**: Panics if called after [`Self::data`]
     pub fn header(&mut self, header: &[u8]) -> &mut Self {
         debug_assert!(!self.has_data, "Tried to put header after data");
+        self.result = self.result.and_then(|_| self.separate());
         self.result = self.result.and_then(|_| {
             if self.has_header {
                 self.fmt.push_byte(b':')?;
@@ -303,25 +532,143 @@ impl<'a> ResponseUnit<'a> {
 
     /// A piece of data be returned
     ///
-    /// Can be called multiple times.
+    /// Can be called multiple times. Empty data is skipped, see [ResponseData].
     pub fn data<U>(&mut self, data: U) -> &mut Self
     where
         U: ResponseData,
     {
+        if data.is_empty() {
+            return self;
+        }
+        self.result = self.result.and_then(|_| self.separate());
         self.result = self.result.and_then(|_| {
             if self.has_data {
                 self.fmt.data_separator()?;
             } else if self.has_header {
                 self.fmt.header_separator()?;
             }
-            data.format_response_data(self.fmt)
+            match self.precision {
+                Precision::Shortest => data.format_response_data(self.fmt),
+                precision => data.format_response_data(&mut WithPrecision {
+                    fmt: self.fmt,
+                    precision,
+                }),
+            }
+        });
+        self.has_data = true;
+        self
+    }
+
+    /// Indefinite length block data, `payload` pushes the block contents in as many chunks
+    /// as it likes. See [Formatter::indefinite_block].
+    ///
+    /// Must be the last data of the response message, later response units return
+    /// [QueryUnterminatedAfterIndefiniteResponse](ErrorCode::QueryUnterminatedAfterIndefiniteResponse).
+    pub fn indefinite_block<F>(&mut self, payload: F) -> &mut Self
+    where
+        F: FnOnce(&mut dyn Formatter) -> Result<()>,
+    {
+        self.result = self.result.and_then(|_| self.separate());
+        self.result = self.result.and_then(|_| {
+            if self.has_data {
+                self.fmt.data_separator()?;
+            } else if self.has_header {
+                self.fmt.header_separator()?;
+            }
+            self.fmt.indefinite_block()?;
+            payload(self.fmt)
         });
         self.has_data = true;
         self
     }
 
     /// Finish the response unit and return any error
+    ///
+    /// Returns [ExecutionError](ErrorCode::ExecutionError) if the unit has neither header nor
+    /// data, e.g. when all of its data was empty. Respond with something like `0` or `""`
+    /// instead if nothing to send is a valid result.
     pub fn finish(&mut self) -> Result<()> {
-        self.result
+        self.result?;
+        if !self.has_header && !self.has_data {
+            return Err(Error::new(ErrorCode::ExecutionError).extended(b"Empty response"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::{vec, vec::Vec};
+
+    fn format<U: ResponseData>(data: U) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.response_unit()
+            .unwrap()
+            .data(1)
+            .data(data)
+            .finish()
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_sequences() {
+        assert_eq!(format(&[1.5f32, 2.0][..]), b"1,1.5,2.0");
+        assert_eq!(format([true, false]), b"1,1,0");
+        assert_eq!(format([b"a".as_slice(), b"b"]), b"1,\"a\",\"b\"");
+        assert_eq!(format(&[&b"a"[..], b"b"][..]), b"1,\"a\",\"b\"");
+        assert_eq!(format(List((1..=3u8).map(|x| x * 2))), b"1,2,4,6");
+        assert_eq!(format(vec![Hex(10u8), Hex(11)]), b"1,#HA,#HB");
+        assert_eq!(format(List([Hex(10u8), Hex(11)][..].iter())), b"1,#HA,#HB");
+        assert_eq!(format(List([1u8, 2][..].iter())), b"1,1,2");
+        // Strings are still strings
+        assert_eq!(format(b"ab".as_slice()), b"1,\"ab\"");
+    }
+
+    #[test]
+    fn test_tuples() {
+        assert_eq!(format((2.5f64, -1i32, Character(b"ON"))), b"1,2.5,-1,ON");
+        assert_eq!(format(([1u8, 2], (3u8,))), b"1,1,2,3");
+        assert_eq!(
+            format((1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8)),
+            b"1,1,2,3,4,5,6,7,8"
+        );
+    }
+
+    #[test]
+    fn test_empty() {
+        let empty: [u16; 0] = [];
+        // Left out with the separator
+        assert_eq!(format(None::<u8>), b"1");
+        assert_eq!(format(empty), b"1");
+        assert_eq!(format(&empty[..]), b"1");
+        assert_eq!(format(Vec::<f32>::new()), b"1");
+        assert_eq!(format(List(core::iter::empty::<u8>())), b"1");
+        assert_eq!(format([None, Some(2u8), None, Some(3)]), b"1,2,3");
+        assert_eq!(format((None::<u8>, 2u8, empty)), b"1,2");
+        assert_eq!(format((None::<u8>, empty)), b"1");
+        // Empty strings are data
+        assert_eq!(format(Some(b"".as_slice())), b"1,\"\"");
+        assert!(!Some(2u8).is_empty());
+        assert!(Some([None::<u8>]).is_empty());
+
+        // A unit without data is an error and doesn't write a separator
+        let mut buf = Vec::new();
+        buf.response_unit().unwrap().data(1).finish().unwrap();
+        assert_eq!(
+            buf.response_unit().unwrap().data(empty).finish(),
+            Err(ErrorCode::ExecutionError.extended(b"Empty response"))
+        );
+        assert_eq!(buf, b"1");
+        buf.response_unit()
+            .unwrap()
+            .data(empty)
+            .data(2)
+            .finish()
+            .unwrap();
+        assert_eq!(buf, b"1;2");
     }
 }
@@ -0,0 +1,357 @@
+//! Real response data with a fixed precision
+//!
+//! Reals are normally formatted in their shortest round-trip form, which for a value that has
+//! passed through an `f32` can look like `0.30000001192092896`. [Nr2] and [Nr3] instead round
+//! to a fixed number of decimals or significant digits. A device can pick a default for the
+//! other reals in its query responses with [Device::precision](crate::Device::precision).
+//!
+//! ```
+//! # use scpi::parser::{format::{Nr2, Nr3}, response::{Formatter, Precision}};
+//! let x = 0.3f32 as f64;
+//! let mut response = Vec::new();
+//! response
+//!     .response_unit()
+//!     .unwrap()
+//!     .data(x)
+//!     .data(Nr2(x, 3))
+//!     .data(Nr3(x, 4))
+//!     .data(Precision::Significant(2).data(-1234.5))
+//!     .finish()
+//!     .unwrap();
+//! assert_eq!(response, b"0.30000001192092896,0.300,3.000E-01,-1.2E+03");
+//! ```
+//!
+//! Digits are rounded half away from zero from the shortest round-trip form, so formatting
+//! needs neither `alloc` nor a buffer longer than that form.
+
+use super::{Formatter, ResponseData, ResponseUnit};
+use crate::error::Result;
+use crate::parser::format::{Nr2, Nr3};
+use crate::parser::message::Terminator;
+use lexical_core::FormattedSize;
+
+/// Precision of real response data
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum Precision {
+    /// Shortest form which parses back to the same value
+    #[default]
+    Shortest,
+    /// <NR2> with a fixed number of decimals, see [Nr2]
+    Decimals(u8),
+    /// <NR3> with a fixed number of significant digits, see [Nr3]
+    Significant(u8),
+}
+
+impl Precision {
+    /// Real value formatted with `self` precision
+    pub fn data<V>(self, value: V) -> Real<V> {
+        Real(value, self)
+    }
+}
+
+/// A real value formatted according to a [Precision]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Real<V>(pub V, pub Precision);
+
+/// Formatter which formats reals with `precision` unless they have their own
+pub(super) struct WithPrecision<'a> {
+    pub fmt: &'a mut dyn Formatter,
+    pub precision: Precision,
+}
+
+impl Formatter for WithPrecision<'_> {
+    fn push_str(&mut self, s: &[u8]) -> Result<()> {
+        self.fmt.push_str(s)
+    }
+
+    fn push_byte(&mut self, b: u8) -> Result<()> {
+        self.fmt.push_byte(b)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        self.fmt.as_slice()
+    }
+
+    fn clear(&mut self) {
+        self.fmt.clear()
+    }
+
+    fn len(&self) -> usize {
+        self.fmt.len()
+    }
+
+    fn message_start(&mut self) -> Result<()> {
+        self.fmt.message_start()
+    }
+
+    fn message_end(&mut self, terminator: Terminator) -> Result<()> {
+        self.fmt.message_end(terminator)
+    }
+
+    fn data_separator(&mut self) -> Result<()> {
+        self.fmt.data_separator()
+    }
+
+    fn header_separator(&mut self) -> Result<()> {
+        self.fmt.header_separator()
+    }
+
+    fn indefinite_block(&mut self) -> Result<()> {
+        self.fmt.indefinite_block()
+    }
+
+    fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    fn response_unit(&mut self) -> Result<ResponseUnit<'_>> {
+        self.fmt.response_unit()
+    }
+}
+
+/// Significant digits of a finite real as `0.<digits> * 10^exp`
+struct Digits {
+    negative: bool,
+    // Shortest round trip is at most 17 digits, one more for a carry
+    buf: [u8; 18],
+    len: usize,
+    exp: i32,
+}
+
+impl Digits {
+    /// Parse the shortest round-trip form written by lexical-core, e.g. `-1.5e-7`
+    fn parse(s: &[u8]) -> Self {
+        let mut digits = Digits {
+            negative: false,
+            buf: [b'0'; 18],
+            len: 0,
+            exp: 0,
+        };
+        let (s, negative) = match s.split_first() {
+            Some((b'-', rest)) => (rest, true),
+            _ => (s, false),
+        };
+        digits.negative = negative;
+
+        let mut iter = s.split(|&c| c == b'e' || c == b'E');
+        let mantissa = iter.next().unwrap_or_default();
+        let exp = iter
+            .next()
+            .map_or(0, |e| lexical_core::parse::<i32>(e).unwrap_or_default());
+        let mut point = false;
+        for &c in mantissa {
+            match c {
+                b'.' => point = true,
+                // Leading zeros after the point move the point left
+                b'0' if digits.len == 0 && point => digits.exp -= 1,
+                b'0' if digits.len == 0 => {}
+                c if digits.len < digits.buf.len() - 1 => {
+                    digits.buf[digits.len] = c;
+                    digits.len += 1;
+                    if !point {
+                        digits.exp += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        digits.exp += exp;
+        while digits.len > 0 && digits.buf[digits.len - 1] == b'0' {
+            digits.len -= 1;
+        }
+        digits
+    }
+
+    fn is_zero(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Round to `n` significant digits
+    fn round(&mut self, n: i32) {
+        if n < 0 || self.len <= n as usize {
+            if n < 0 {
+                self.len = 0;
+            }
+            return;
+        }
+        let n = n as usize;
+        let up = self.buf[n] >= b'5';
+        self.len = n;
+        if up {
+            let mut i = n;
+            loop {
+                if i == 0 {
+                    // Carried out of all digits, e.g. 9.99 -> 10.0
+                    self.buf[0] = b'1';
+                    self.len = 1;
+                    self.exp += 1;
+                    break;
+                }
+                i -= 1;
+                if self.buf[i] == b'9' {
+                    self.len = i;
+                } else {
+                    self.buf[i] += 1;
+                    break;
+                }
+            }
+        }
+        while self.len > 0 && self.buf[self.len - 1] == b'0' {
+            self.len -= 1;
+        }
+    }
+
+    /// Digit at `i` where 0 is the first significant digit, zero outside of the digits
+    fn digit(&self, i: i32) -> u8 {
+        if i < 0 {
+            b'0'
+        } else {
+            self.buf[..self.len]
+                .get(i as usize)
+                .copied()
+                .unwrap_or(b'0')
+        }
+    }
+
+    fn format_nr2(mut self, decimals: u8, formatter: &mut dyn Formatter) -> Result<()> {
+        let decimals = decimals as i32;
+        self.round(self.exp + decimals);
+        if self.negative && !self.is_zero() {
+            formatter.push_byte(b'-')?;
+        }
+        if self.exp <= 0 || self.is_zero() {
+            formatter.push_byte(b'0')?;
+        } else {
+            for i in 0..self.exp {
+                formatter.push_byte(self.digit(i))?;
+            }
+        }
+        if decimals > 0 {
+            formatter.push_byte(b'.')?;
+            for i in 0..decimals {
+                let d = if self.is_zero() {
+                    b'0'
+                } else {
+                    self.digit(self.exp + i)
+                };
+                formatter.push_byte(d)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn format_nr3(mut self, significant: u8, formatter: &mut dyn Formatter) -> Result<()> {
+        let significant = significant.max(1) as i32;
+        self.round(significant);
+        if self.negative && !self.is_zero() {
+            formatter.push_byte(b'-')?;
+        }
+        formatter.push_byte(self.digit(0))?;
+        if significant > 1 {
+            formatter.push_byte(b'.')?;
+            for i in 1..significant {
+                formatter.push_byte(self.digit(i))?;
+            }
+        }
+        let exp = if self.is_zero() { 0 } else { self.exp - 1 };
+        formatter.push_str(if exp < 0 { b"E-" } else { b"E+" })?;
+        let exp = exp.unsigned_abs();
+        if exp < 10 {
+            formatter.push_byte(b'0')?;
+        }
+        exp.format_response_data(formatter)
+    }
+}
+
+macro_rules! impl_precision {
+    ($typ:ty) => {
+        impl ResponseData for Nr2<$typ> {
+            fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+                if !self.0.is_finite() {
+                    return self.0.format_response_data(formatter);
+                }
+                let mut buf = [b'0'; <$typ>::FORMATTED_SIZE_DECIMAL];
+                let slc = lexical_core::write::<$typ>(self.0, &mut buf);
+                Digits::parse(slc).format_nr2(self.1, formatter)
+            }
+        }
+
+        impl ResponseData for Nr3<$typ> {
+            fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+                if !self.0.is_finite() {
+                    return self.0.format_response_data(formatter);
+                }
+                let mut buf = [b'0'; <$typ>::FORMATTED_SIZE_DECIMAL];
+                let slc = lexical_core::write::<$typ>(self.0, &mut buf);
+                Digits::parse(slc).format_nr3(self.1, formatter)
+            }
+        }
+
+        impl ResponseData for Real<$typ> {
+            fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+                match self.1 {
+                    Precision::Shortest => self.0.format_response_data(&mut WithPrecision {
+                        fmt: formatter,
+                        precision: Precision::Shortest,
+                    }),
+                    Precision::Decimals(n) => Nr2(self.0, n).format_response_data(formatter),
+                    Precision::Significant(n) => Nr3(self.0, n).format_response_data(formatter),
+                }
+            }
+        }
+    };
+}
+
+impl_precision!(f32);
+impl_precision!(f64);
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    fn format<U: ResponseData>(data: U) -> std::string::String {
+        let mut buf = std::vec::Vec::new();
+        data.format_response_data(&mut buf).unwrap();
+        std::string::String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_nr2() {
+        assert_eq!(format(Nr2(0.3f32 as f64, 3)), "0.300");
+        assert_eq!(format(Nr2(1.0f64, 0)), "1");
+        assert_eq!(format(Nr2(2.5f64, 0)), "3");
+        assert_eq!(format(Nr2(-0.004f64, 2)), "0.00");
+        assert_eq!(format(Nr2(-0.005f64, 2)), "-0.01");
+        assert_eq!(format(Nr2(0.0005f32, 3)), "0.001");
+        assert_eq!(format(Nr2(0.0004f32, 3)), "0.000");
+        assert_eq!(format(Nr2(9.996f64, 2)), "10.00");
+        assert_eq!(format(Nr2(-99.5f64, 0)), "-100");
+        assert_eq!(format(Nr2(123456.789f64, 1)), "123456.8");
+        assert_eq!(format(Nr2(1.5e-7f64, 8)), "0.00000015");
+        assert_eq!(format(Nr2(1e20f64, 1)), "100000000000000000000.0");
+        assert_eq!(format(Nr2(0.0f32, 2)), "0.00");
+        assert_eq!(format(Nr2(f32::NAN, 2)), "9.91E+37");
+    }
+
+    #[test]
+    fn test_nr3() {
+        assert_eq!(format(Nr3(0.3f32 as f64, 6)), "3.00000E-01");
+        assert_eq!(format(Nr3(1.0f64, 1)), "1E+00");
+        assert_eq!(format(Nr3(1.0f64, 0)), "1E+00");
+        assert_eq!(format(Nr3(-1234.5f64, 3)), "-1.23E+03");
+        assert_eq!(format(Nr3(9.999e-12f64, 3)), "1.00E-11");
+        assert_eq!(format(Nr3(6.02214076e23f64, 4)), "6.022E+23");
+        assert_eq!(format(Nr3(1e300f64, 2)), "1.0E+300");
+        assert_eq!(format(Nr3(0.0f64, 3)), "0.00E+00");
+        assert_eq!(format(Nr3(-f64::INFINITY, 3)), "-9.9E+37");
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(format(Precision::default().data(0.1f32)), "0.1");
+        assert_eq!(format(Precision::Decimals(2).data(0.1f32)), "0.10");
+        assert_eq!(format(Precision::Significant(2).data(0.1f32)), "1.0E-01");
+    }
+}
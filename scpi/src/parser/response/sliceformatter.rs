@@ -0,0 +1,142 @@
+use crate::error::{ErrorCode, Result};
+use crate::parser::message::Terminator;
+
+use super::{Formatter, ResponseUnit};
+
+/// A [Formatter] writing into a caller-provided buffer, for targets without `alloc` or
+/// `arrayvec`.
+///
+/// Pushing more than fits returns [OutOfMemory](ErrorCode::OutOfMemory) and leaves the
+/// buffer as it was. Since a command handler may ignore that error, [Self::is_truncated] tells
+/// whether anything has been dropped since the buffer was last cleared.
+///
+/// ```
+/// # use scpi::parser::{message::Terminator, response::{Formatter, SliceFormatter}};
+/// let mut buf = [0u8; 8];
+/// let mut response = SliceFormatter::new(&mut buf);
+/// response.response_unit().unwrap().data(42).finish().unwrap();
+/// response.message_end(Terminator::Nl).unwrap();
+/// assert_eq!(response.as_slice(), b"42\n");
+/// assert!(!response.is_truncated());
+/// ```
+#[derive(Debug)]
+pub struct SliceFormatter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    truncated: bool,
+}
+
+impl<'a> SliceFormatter<'a> {
+    /// Write responses into `buf`
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Size of the buffer
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns true if data was dropped because the buffer was full
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl Formatter for SliceFormatter<'_> {
+    fn push_str(&mut self, s: &[u8]) -> Result<()> {
+        match self.buf.get_mut(self.len..self.len + s.len()) {
+            Some(dst) => {
+                dst.copy_from_slice(s);
+                self.len += s.len();
+                Ok(())
+            }
+            None => {
+                self.truncated = true;
+                Err(ErrorCode::OutOfMemory.into())
+            }
+        }
+    }
+
+    fn push_byte(&mut self, b: u8) -> Result<()> {
+        self.push_str(&[b])
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn message_start(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn message_end(&mut self, terminator: Terminator) -> Result<()> {
+        self.push_str(terminator.as_bytes())
+    }
+
+    fn response_unit(&mut self) -> Result<ResponseUnit<'_>> {
+        Ok(ResponseUnit::new(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_slice() {
+        let mut buf = [0u8; 16];
+        let mut slice = SliceFormatter::new(&mut buf);
+        slice.message_start().unwrap();
+        // First unit
+        slice
+            .response_unit()
+            .unwrap()
+            .data(&b"potato"[..])
+            .data(0u8)
+            .finish()
+            .unwrap();
+        // Second unit
+        slice.response_unit().unwrap().data(42i16).finish().unwrap();
+        slice.message_end(Terminator::Nl).unwrap();
+        assert_eq!(slice.as_slice(), b"\"potato\",0;42\n");
+        assert_eq!(slice.capacity(), 16);
+        assert!(!slice.is_truncated());
+    }
+
+    #[test]
+    fn test_outamemory() {
+        let mut buf = [0u8; 3];
+        let mut slice = SliceFormatter::new(&mut buf);
+        slice.push_str(b"xy").unwrap();
+        assert_eq!(
+            slice.push_str(b"zz"),
+            Err(Error::from(ErrorCode::OutOfMemory))
+        );
+        assert!(slice.is_truncated());
+        slice.push_byte(b'z').unwrap();
+        assert_eq!(
+            slice.push_byte(b'x'),
+            Err(Error::from(ErrorCode::OutOfMemory))
+        );
+        assert_eq!(slice.as_slice(), b"xyz");
+
+        slice.clear();
+        assert!(slice.is_empty());
+        assert!(!slice.is_truncated());
+    }
+}
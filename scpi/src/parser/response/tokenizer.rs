@@ -0,0 +1,410 @@
+//! Response message parsing
+//!
+//! Splits a <RESPONSE MESSAGE> received by a controller into tokens, the inverse of
+//! [ResponseUnit](super::ResponseUnit). Data tokens convert into the same types as program
+//! data, see [ResponseTokenizer::next_data].
+//!
+//! See IEEE 488.2-1992 chapter 8
+//!
+//! ```
+//! # use scpi::parser::{response::ResponseTokenizer, string::ScpiString};
+//! let mut response = ResponseTokenizer::new(b"1.5E-3,#HFF;\"it\"\"s\"\n");
+//! assert_eq!(response.next_data::<f64>(), Ok(1.5e-3));
+//! assert_eq!(response.next_data::<u8>(), Ok(255));
+//! assert_eq!(response.next_unit(), Ok(true));
+//! assert_eq!(response.next_data::<ScpiString>().unwrap(), b"it\"s");
+//! assert_eq!(response.next_unit(), Ok(false));
+//! ```
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::parser::expression::{channel_list, numeric_expression, numeric_list};
+use crate::parser::format;
+use crate::parser::message::Terminator;
+use crate::parser::string::ScpiString;
+use crate::parser::tokenizer::{util, Token, Tokenizer};
+use crate::parser::ParserConfig;
+
+/// SCPI response tokens
+/// Loosely based on IEEE488.2 Chapter 8
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ResponseToken<'a> {
+    /// A response message unit separator `;`
+    ResponseMessageUnitSeparator,
+    /// A response data separator `,`
+    ResponseDataSeparator,
+    /// A <RESPONSE HEADER> 8.6, e.g. `:VOLT` in `:VOLT 1.5`
+    ResponseHeader(&'a [u8]),
+    /// A <CHARACTER RESPONSE DATA> 8.7.1
+    CharacterResponseData(&'a [u8]),
+    /// A <NR1>, <NR2> or <NR3 NUMERIC RESPONSE DATA> 8.7.2-8.7.4
+    DecimalNumericResponseData(&'a [u8]),
+    /// A <HEXADECIMAL|OCTAL|BINARY NUMERIC RESPONSE DATA> 8.7.5-8.7.7
+    NonDecimalNumericResponseData(u64),
+    /// A <STRING RESPONSE DATA> 8.7.8
+    StringResponseData(ScpiString<'a>),
+    /// A <DEFINITE|INDEFINITE LENGTH ARBITRARY BLOCK RESPONSE DATA> 8.7.9-8.7.10
+    ArbitraryBlockResponseData(&'a [u8]),
+    /// A <EXPRESSION RESPONSE DATA> 8.7.12
+    ExpressionResponseData(&'a [u8]),
+}
+
+impl<'a> ResponseToken<'a> {
+    pub fn is_data(&self) -> bool {
+        self.as_program_data().is_some()
+    }
+
+    /// The program data token with the same value
+    fn as_program_data(&self) -> Option<Token<'a>> {
+        match *self {
+            Self::CharacterResponseData(s) => Some(Token::CharacterProgramData(s)),
+            Self::DecimalNumericResponseData(s) => Some(Token::DecimalNumericProgramData(s)),
+            Self::NonDecimalNumericResponseData(x) => Some(Token::NonDecimalNumericProgramData(x)),
+            Self::StringResponseData(s) => Some(Token::StringProgramData(s)),
+            Self::ArbitraryBlockResponseData(s) => Some(Token::ArbitraryBlockData(s)),
+            Self::ExpressionResponseData(s) => Some(Token::ExpressionProgramData(s)),
+            _ => None,
+        }
+    }
+}
+
+/// Tokenizer for a <RESPONSE MESSAGE>
+///
+/// Parsing stops at the first error.
+#[derive(Clone)]
+pub struct ResponseTokenizer<'a> {
+    toks: Tokenizer<'a>,
+    terminator: Terminator,
+    peeked: Option<Option<core::result::Result<ResponseToken<'a>, ErrorCode>>>,
+    in_unit: bool,
+    done: bool,
+}
+
+impl<'a> ResponseTokenizer<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self {
+            toks: Tokenizer::new_params(buf).config(ParserConfig::LENIENT),
+            terminator: Terminator::default(),
+            peeked: None,
+            in_unit: false,
+            done: false,
+        }
+    }
+
+    /// Use `terminator` as the <RESPONSE MESSAGE TERMINATOR> instead of NL
+    pub fn terminator(mut self, terminator: Terminator) -> Self {
+        self.toks = self.toks.terminator(terminator);
+        Self { terminator, ..self }
+    }
+
+    /// Peek at the next token without consuming it
+    pub fn peek(&mut self) -> Option<&core::result::Result<ResponseToken<'a>, ErrorCode>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read_token());
+        }
+        self.peeked.as_ref().and_then(Option::as_ref)
+    }
+
+    /// Get the next data token of the current response message unit, skipping any header
+    /// and data separator.
+    ///
+    /// If no data is found a error with [ErrorCode::MissingParameter] is returned instead.
+    pub fn next_data<T>(&mut self) -> Result<T>
+    where
+        T: TryFrom<ResponseToken<'a>, Error = Error>,
+    {
+        while let Some(tok) = self.peek() {
+            match *tok {
+                Err(err) => return Err(err.into()),
+                Ok(ResponseToken::ResponseHeader(_) | ResponseToken::ResponseDataSeparator) => {
+                    self.next();
+                }
+                Ok(tok) if tok.is_data() => {
+                    self.next();
+                    return tok.try_into();
+                }
+                Ok(_) => break,
+            }
+        }
+        Err(ErrorCode::MissingParameter.into())
+    }
+
+    /// Skip the rest of the current response message unit.
+    ///
+    /// Returns true if another unit follows, false at the end of the response message.
+    pub fn next_unit(&mut self) -> Result<bool> {
+        for tok in self.by_ref() {
+            if tok? == ResponseToken::ResponseMessageUnitSeparator {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn read_token(&mut self) -> Option<core::result::Result<ResponseToken<'a>, ErrorCode>> {
+        if self.done {
+            return None;
+        }
+        let ret = self.read_unchecked();
+        if !matches!(ret, Some(Ok(_))) {
+            self.done = true;
+        }
+        ret
+    }
+
+    fn read_unchecked(&mut self) -> Option<core::result::Result<ResponseToken<'a>, ErrorCode>> {
+        let rest = self.toks.chars.as_slice();
+        match rest.first() {
+            /* Response message unit separator */
+            Some(b';') => {
+                self.toks.chars.next();
+                let next = self.toks.chars.as_slice();
+                if !self.in_unit
+                    || matches!(next.first(), None | Some(b';' | b','))
+                    || self.terminator.is_prefix_of(next)
+                {
+                    return Some(Err(ErrorCode::SyntaxError));
+                }
+                self.in_unit = false;
+                Some(Ok(ResponseToken::ResponseMessageUnitSeparator))
+            }
+            /* Response header, followed by a header separator */
+            Some(c) if !self.in_unit && (c.is_ascii_alphabetic() || b"*:".contains(c)) => {
+                self.in_unit = true;
+                let len = rest
+                    .iter()
+                    .position(|c| !(c.is_ascii_alphanumeric() || b"_*:".contains(c)))
+                    .unwrap_or(rest.len());
+                match rest.get(len) {
+                    Some(ws)
+                        if ws.is_ascii_whitespace()
+                            && !self.terminator.is_prefix_of(&rest[len..]) =>
+                    {
+                        self.toks.chars.nth(len);
+                        util::skip_ws(&mut self.toks.chars);
+                        Some(Ok(ResponseToken::ResponseHeader(&rest[..len])))
+                    }
+                    _ => self.read_data(),
+                }
+            }
+            Some(_) => {
+                self.in_unit = true;
+                self.read_data()
+            }
+            None => None,
+        }
+    }
+
+    fn read_data(&mut self) -> Option<core::result::Result<ResponseToken<'a>, ErrorCode>> {
+        Some(match self.toks.next()? {
+            Ok(Token::ProgramDataSeparator) => Ok(ResponseToken::ResponseDataSeparator),
+            Ok(Token::CharacterProgramData(s)) => Ok(ResponseToken::CharacterResponseData(s)),
+            Ok(Token::DecimalNumericProgramData(s)) => {
+                Ok(ResponseToken::DecimalNumericResponseData(s))
+            }
+            Ok(Token::NonDecimalNumericProgramData(x)) => {
+                Ok(ResponseToken::NonDecimalNumericResponseData(x))
+            }
+            Ok(Token::StringProgramData(s)) => Ok(ResponseToken::StringResponseData(s)),
+            Ok(Token::ArbitraryBlockData(s)) => Ok(ResponseToken::ArbitraryBlockResponseData(s)),
+            Ok(Token::ExpressionProgramData(s)) => Ok(ResponseToken::ExpressionResponseData(s)),
+            Ok(Token::DecimalNumericSuffixProgramData(..)) => Err(ErrorCode::SuffixNotAllowed),
+            Ok(_) => Err(ErrorCode::SyntaxError),
+            Err(err) => Err(err),
+        })
+    }
+}
+
+impl<'a> Iterator for ResponseTokenizer<'a> {
+    type Item = core::result::Result<ResponseToken<'a>, ErrorCode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.read_token(),
+        }
+    }
+}
+
+/// Convert response data the same way as the corresponding program data
+macro_rules! impl_tryfrom_program_data {
+    ($($from:ty),*) => {
+        $(
+            impl<'a> TryFrom<ResponseToken<'a>> for $from {
+                type Error = Error;
+
+                fn try_from(value: ResponseToken<'a>) -> Result<Self> {
+                    value
+                        .as_program_data()
+                        .ok_or(Error::new(ErrorCode::DataTypeError))?
+                        .try_into()
+                }
+            }
+        )*
+    };
+}
+
+impl_tryfrom_program_data!(u8, i8, u16, i16, u32, i32, u64, i64, usize, isize);
+impl_tryfrom_program_data!(bool, &'a [u8], &'a str, ScpiString<'a>);
+impl_tryfrom_program_data!(format::Arbitrary<'a>, format::Character<'a>);
+impl_tryfrom_program_data!(
+    numeric_list::NumericList<'a>,
+    numeric_expression::NumericExpression<'a>,
+    channel_list::ChannelList<'a>
+);
+
+/// Convert expression response data into an [Expression](format::Expression) without the
+/// enclosing parentheses.
+impl<'a> TryFrom<ResponseToken<'a>> for format::Expression<'a> {
+    type Error = Error;
+
+    fn try_from(value: ResponseToken<'a>) -> Result<Self> {
+        match value {
+            ResponseToken::ExpressionResponseData(s) => Ok(format::Expression(s)),
+            _ => Err(ErrorCode::DataTypeError.into()),
+        }
+    }
+}
+
+// Reverses the NaN and infinity representations used by the formatter
+macro_rules! impl_tryfrom_real {
+    ($from:ty) => {
+        impl<'a> TryFrom<ResponseToken<'a>> for $from {
+            type Error = Error;
+
+            fn try_from(value: ResponseToken<'a>) -> Result<Self> {
+                let x: $from = value
+                    .as_program_data()
+                    .ok_or(Error::new(ErrorCode::DataTypeError))?
+                    .try_into()?;
+                Ok(if x == 9.91E+37 {
+                    <$from>::NAN
+                } else if x == 9.9E+37 {
+                    <$from>::INFINITY
+                } else if x == -9.9E+37 {
+                    <$from>::NEG_INFINITY
+                } else {
+                    x
+                })
+            }
+        }
+    };
+}
+
+impl_tryfrom_real!(f32);
+impl_tryfrom_real!(f64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+
+    fn tokens(buf: &[u8]) -> std::vec::Vec<core::result::Result<ResponseToken<'_>, ErrorCode>> {
+        ResponseTokenizer::new(buf).take(32).collect()
+    }
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(
+            tokens(b"1,-2.5,+1.0E-3,#H1F,#Q17,#B11;\"a\"\"b\",#14\n;,x,(@1:3),ON\n"),
+            [
+                Ok(ResponseToken::DecimalNumericResponseData(b"1")),
+                Ok(ResponseToken::ResponseDataSeparator),
+                Ok(ResponseToken::DecimalNumericResponseData(b"-2.5")),
+                Ok(ResponseToken::ResponseDataSeparator),
+                Ok(ResponseToken::DecimalNumericResponseData(b"+1.0E-3")),
+                Ok(ResponseToken::ResponseDataSeparator),
+                Ok(ResponseToken::NonDecimalNumericResponseData(0x1F)),
+                Ok(ResponseToken::ResponseDataSeparator),
+                Ok(ResponseToken::NonDecimalNumericResponseData(0o17)),
+                Ok(ResponseToken::ResponseDataSeparator),
+                Ok(ResponseToken::NonDecimalNumericResponseData(0b11)),
+                Ok(ResponseToken::ResponseMessageUnitSeparator),
+                Ok(ResponseToken::StringResponseData(ScpiString::new(
+                    b"a\"\"b", b'"'
+                ))),
+                Ok(ResponseToken::ResponseDataSeparator),
+                Ok(ResponseToken::ArbitraryBlockResponseData(b"\n;,x")),
+                Ok(ResponseToken::ResponseDataSeparator),
+                Ok(ResponseToken::ExpressionResponseData(b"@1:3")),
+                Ok(ResponseToken::ResponseDataSeparator),
+                Ok(ResponseToken::CharacterResponseData(b"ON")),
+            ]
+        );
+        // Indefinite block
+        assert_eq!(
+            tokens(b"#0a\nb\n"),
+            [Ok(ResponseToken::ArbitraryBlockResponseData(b"a\nb"))]
+        );
+        // Empty response
+        assert_eq!(tokens(b"\n"), []);
+    }
+
+    #[test]
+    fn test_headers() {
+        assert_eq!(
+            tokens(b":VOLT 1.5;*ESE 4;:SOUR:FUNC VOLT;VOLT\n"),
+            [
+                Ok(ResponseToken::ResponseHeader(b":VOLT")),
+                Ok(ResponseToken::DecimalNumericResponseData(b"1.5")),
+                Ok(ResponseToken::ResponseMessageUnitSeparator),
+                Ok(ResponseToken::ResponseHeader(b"*ESE")),
+                Ok(ResponseToken::DecimalNumericResponseData(b"4")),
+                Ok(ResponseToken::ResponseMessageUnitSeparator),
+                Ok(ResponseToken::ResponseHeader(b":SOUR:FUNC")),
+                Ok(ResponseToken::CharacterResponseData(b"VOLT")),
+                Ok(ResponseToken::ResponseMessageUnitSeparator),
+                Ok(ResponseToken::CharacterResponseData(b"VOLT")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(tokens(b";1"), [Err(ErrorCode::SyntaxError)]);
+        assert_eq!(
+            tokens(b"1;\n"),
+            [
+                Ok(ResponseToken::DecimalNumericResponseData(b"1")),
+                Err(ErrorCode::SyntaxError)
+            ]
+        );
+        assert_eq!(
+            tokens(b"1,,2"),
+            [
+                Ok(ResponseToken::DecimalNumericResponseData(b"1")),
+                Err(ErrorCode::SyntaxError)
+            ]
+        );
+        assert_eq!(tokens(b"1V"), [Err(ErrorCode::SuffixNotAllowed)]);
+        assert_eq!(tokens(b"\"abc"), [Err(ErrorCode::InvalidStringData)]);
+        assert_eq!(tokens(b"#15ab"), [Err(ErrorCode::InvalidBlockData)]);
+        assert_eq!(tokens(b"ON\nOFF\n"), [Err(ErrorCode::InvalidCharacterData)]);
+    }
+
+    #[test]
+    fn test_next_data() {
+        let mut response = ResponseTokenizer::new(b":MEAS 9.91E+37,-9.9E+37,1;#13abc,(1,2);1,0\n");
+        assert!(response.next_data::<f32>().unwrap().is_nan());
+        assert_eq!(response.next_data::<f64>(), Ok(f64::NEG_INFINITY));
+        assert_eq!(response.next_data::<i32>(), Ok(1));
+        assert_eq!(
+            response.next_data::<i32>(),
+            Err(ErrorCode::MissingParameter.into())
+        );
+        assert_eq!(response.next_unit(), Ok(true));
+        assert_eq!(response.next_data::<&str>(), Ok("abc"));
+        assert_eq!(
+            response.next_data::<format::Expression>(),
+            Ok(format::Expression(b"1,2"))
+        );
+        assert_eq!(response.next_unit(), Ok(true));
+        assert_eq!(
+            response.next_data::<format::Character>(),
+            Err(ErrorCode::DataTypeError.into())
+        );
+        assert_eq!(response.next_data::<bool>(), Ok(false));
+        assert_eq!(response.next_unit(), Ok(false));
+        assert_eq!(response.next_unit(), Ok(false));
+    }
+}
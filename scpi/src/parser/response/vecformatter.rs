@@ -1,8 +1,7 @@
 use crate::error::Result;
+use crate::parser::message::Terminator;
 
-use super::{
-    Formatter, ResponseUnit, RESPONSE_MESSAGE_TERMINATOR, RESPONSE_MESSAGE_UNIT_SEPARATOR,
-};
+use super::{Formatter, ResponseUnit};
 
 impl Formatter for alloc::vec::Vec<u8> {
     /// Internal use
@@ -32,19 +31,11 @@ impl Formatter for alloc::vec::Vec<u8> {
         Ok(())
     }
 
-    fn message_end(&mut self) -> Result<()> {
-        self.push_byte(RESPONSE_MESSAGE_TERMINATOR)
+    fn message_end(&mut self, terminator: Terminator) -> Result<()> {
+        self.push_str(terminator.as_bytes())
     }
 
     fn response_unit(&mut self) -> Result<ResponseUnit> {
-        if !self.is_empty() {
-            self.push_byte(RESPONSE_MESSAGE_UNIT_SEPARATOR)?;
-        }
-        Ok(ResponseUnit {
-            fmt: self,
-            result: Ok(()),
-            has_header: false,
-            has_data: false,
-        })
+        Ok(ResponseUnit::new(self))
     }
 }
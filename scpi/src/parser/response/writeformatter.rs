@@ -0,0 +1,339 @@
+//! Streaming response formatter
+//!
+//! [Vec] and `ArrayVec` buffer the whole response message before it is
+//! sent. [WriteFormatter] instead writes response data to a [Write]r as it is produced so
+//! long responses such as traces don't need a buffer of their own.
+//!
+//! Since nothing is buffered the formatter also supports
+//! <INDEFINITE LENGTH ARBITRARY BLOCK RESPONSE DATA>, see [ResponseUnit::indefinite_block].
+//!
+//! ```
+//! # use scpi::parser::{message::Terminator, response::{Formatter, WriteFormatter}};
+//! let mut response = WriteFormatter::new(Vec::new());
+//! response.message_start().unwrap();
+//! response.response_unit().unwrap().data(1).finish().unwrap();
+//! response
+//!     .response_unit()
+//!     .unwrap()
+//!     .indefinite_block(|fmt| {
+//!         for chunk in [&b"ab"[..], b"cd"] {
+//!             fmt.push_str(chunk)?;
+//!         }
+//!         Ok(())
+//!     })
+//!     .finish()
+//!     .unwrap();
+//! // No more response units may follow an indefinite block
+//! assert!(response.response_unit().is_err());
+//! response.message_end(Terminator::Nl).unwrap();
+//! assert_eq!(response.writer(), b"1;#0abcd\n");
+//! ```
+//!
+//! [Vec]: alloc::vec::Vec
+
+use crate::error::{ErrorCode, Result};
+use crate::parser::message::Terminator;
+
+use super::{Formatter, ResponseUnit, RESPONSE_DATA_SEPARATOR};
+
+/// Byte sink written to by [WriteFormatter], like `embedded_io::Write`.
+pub trait Write {
+    /// Write all of `buf`.
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+
+    /// Flush buffered data, called at the end of every response message.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W> Write for &mut W
+where
+    W: Write + ?Sized,
+{
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Write for alloc::vec::Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// Adapts a [core::fmt::Write], e.g. a `String` or a UART driver.
+///
+/// Only ASCII can be written, binary block data returns
+/// [ExecutionError](ErrorCode::ExecutionError). Errors from the writer are reported as
+/// [SystemError](ErrorCode::SystemError).
+#[derive(Debug)]
+pub struct FmtWriter<W>(pub W);
+
+impl<W> Write for FmtWriter<W>
+where
+    W: core::fmt::Write,
+{
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        let s = core::str::from_utf8(buf)
+            .ok()
+            .filter(|s| s.is_ascii())
+            .ok_or(ErrorCode::ExecutionError)?;
+        self.0
+            .write_str(s)
+            .map_err(|_| ErrorCode::SystemError.into())
+    }
+}
+
+/// Adapts a [std::io::Write], e.g. a `TcpStream`.
+///
+/// Errors from the writer are reported as [SystemError](ErrorCode::SystemError).
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoWriter<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W> Write for IoWriter<W>
+where
+    W: std::io::Write,
+{
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.0
+            .write_all(buf)
+            .map_err(|_| ErrorCode::SystemError.into())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush().map_err(|_| ErrorCode::SystemError.into())
+    }
+}
+
+/// A [Formatter] writing response data straight to a [Write]r.
+///
+/// Output cannot be taken back, so unlike a buffering formatter a response message which
+/// fails halfway has already been partly written. Call [Formatter::message_end] after an
+/// error to terminate it (nothing is written if the message had no response).
+/// [Formatter::as_slice] is always empty, use [Node::run](crate::tree::Node::run) rather than
+/// [Node::run_messages](crate::tree::Node::run_messages) which expects a buffer.
+#[derive(Debug)]
+pub struct WriteFormatter<W> {
+    writer: W,
+    len: usize,
+    indefinite: bool,
+}
+
+impl<W> WriteFormatter<W>
+where
+    W: Write,
+{
+    /// Write responses to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            len: 0,
+            indefinite: false,
+        }
+    }
+
+    /// Returns a reference to the writer
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the writer
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> Formatter for WriteFormatter<W>
+where
+    W: Write,
+{
+    fn push_str(&mut self, s: &[u8]) -> Result<()> {
+        if !s.is_empty() {
+            self.writer.write_all(s)?;
+            self.len += s.len();
+        }
+        Ok(())
+    }
+
+    fn push_byte(&mut self, b: u8) -> Result<()> {
+        self.push_str(&[b])
+    }
+
+    /// Always empty, data is written as it's pushed
+    fn as_slice(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Forget the current response message, written data is not affected
+    fn clear(&mut self) {
+        self.len = 0;
+        self.indefinite = false;
+    }
+
+    /// Number of bytes written in the current response message
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn message_start(&mut self) -> Result<()> {
+        self.clear();
+        Ok(())
+    }
+
+    /// Terminates the response message if anything was written and flushes the writer.
+    ///
+    /// An indefinite block is always terminated by NL^END regardless of `terminator`.
+    fn message_end(&mut self, terminator: Terminator) -> Result<()> {
+        if !self.is_empty() {
+            let terminator = if self.indefinite {
+                Terminator::Nl
+            } else {
+                terminator
+            };
+            self.push_str(terminator.as_bytes())?;
+        }
+        self.clear();
+        self.writer.flush()
+    }
+
+    fn data_separator(&mut self) -> Result<()> {
+        if self.indefinite {
+            return Err(ErrorCode::QueryUnterminatedAfterIndefiniteResponse.into());
+        }
+        self.push_byte(RESPONSE_DATA_SEPARATOR)
+    }
+
+    fn indefinite_block(&mut self) -> Result<()> {
+        if self.indefinite {
+            return Err(ErrorCode::QueryUnterminatedAfterIndefiniteResponse.into());
+        }
+        self.push_str(b"#0")?;
+        self.indefinite = true;
+        Ok(())
+    }
+
+    /// Returns [QueryUnterminatedAfterIndefiniteResponse](ErrorCode::QueryUnterminatedAfterIndefiniteResponse)
+    /// if an indefinite block has been written in this response message.
+    fn response_unit(&mut self) -> Result<ResponseUnit<'_>> {
+        if self.indefinite {
+            return Err(ErrorCode::QueryUnterminatedAfterIndefiniteResponse.into());
+        }
+        Ok(ResponseUnit::new(self))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    extern crate std;
+
+    #[test]
+    fn test_write() {
+        let mut fmt = WriteFormatter::new(std::vec::Vec::new());
+        fmt.message_start().unwrap();
+        fmt.response_unit()
+            .unwrap()
+            .header(b"VOLT")
+            .data(1.5)
+            .data(2)
+            .finish()
+            .unwrap();
+        fmt.response_unit().unwrap().data(true).finish().unwrap();
+        assert_eq!(fmt.len(), 12);
+        assert!(fmt.as_slice().is_empty());
+        fmt.message_end(Terminator::CrNl).unwrap();
+        assert!(fmt.is_empty());
+
+        // Nothing to terminate
+        fmt.message_start().unwrap();
+        fmt.message_end(Terminator::Nl).unwrap();
+        assert_eq!(fmt.into_inner(), b"VOLT 1.5,2;1\r\n");
+    }
+
+    #[test]
+    fn test_indefinite() {
+        let mut out = std::vec::Vec::new();
+        let mut fmt = WriteFormatter::new(&mut out);
+        fmt.message_start().unwrap();
+        fmt.response_unit()
+            .unwrap()
+            .data(1)
+            .indefinite_block(|fmt| fmt.push_str(b"\x00\n;"))
+            .finish()
+            .unwrap();
+
+        // Data may not follow in the same unit...
+        let mut unit = fmt.response_unit().err();
+        assert_eq!(
+            unit,
+            Some(Error::new(
+                ErrorCode::QueryUnterminatedAfterIndefiniteResponse
+            ))
+        );
+        // ...or message
+        unit = fmt.indefinite_block().err();
+        assert_eq!(
+            unit,
+            Some(Error::new(
+                ErrorCode::QueryUnterminatedAfterIndefiniteResponse
+            ))
+        );
+        assert_eq!(
+            fmt.data_separator(),
+            Err(ErrorCode::QueryUnterminatedAfterIndefiniteResponse.into())
+        );
+
+        // Always NL^END
+        fmt.message_end(Terminator::CrNl).unwrap();
+        fmt.message_start().unwrap();
+        fmt.response_unit().unwrap().data(2).finish().unwrap();
+        fmt.message_end(Terminator::CrNl).unwrap();
+        assert_eq!(out, b"1,#0\x00\n;\n2\r\n");
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut buf = std::vec::Vec::new();
+        assert_eq!(
+            buf.response_unit()
+                .unwrap()
+                .indefinite_block(|_| Ok(()))
+                .finish(),
+            Err(ErrorCode::ExecutionError.into())
+        );
+    }
+
+    #[test]
+    fn test_fmt_writer() {
+        let mut fmt = WriteFormatter::new(FmtWriter(std::string::String::new()));
+        fmt.response_unit()
+            .unwrap()
+            .data(b"hi".as_slice())
+            .finish()
+            .unwrap();
+        assert_eq!(
+            fmt.response_unit()
+                .unwrap()
+                .data(crate::parser::format::Arbitrary(b"\xff"))
+                .finish(),
+            Err(ErrorCode::ExecutionError.into())
+        );
+        assert_eq!(fmt.writer().0, "\"hi\";#11");
+    }
+}
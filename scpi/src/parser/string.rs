@@ -0,0 +1,203 @@
+//! String program data
+//!
+//! See IEEE 488.2-1992 7.7.5
+
+use core::iter::FusedIterator;
+use core::slice::Iter;
+
+use crate::error::{Error, ErrorCode, Result};
+
+use super::{
+    parameters::parser_unreachable,
+    response::{Formatter, ResponseData},
+    tokenizer::Token,
+};
+
+/// A <STRING PROGRAM DATA> element.
+///
+/// Holds the raw string contents between the enclosing quotes, where an embedded quote of the
+/// same kind as the enclosing one is doubled (`'it''s'`).
+/// The contents are unescaped lazily with [`Self::bytes`] without allocating.
+///
+/// ```
+/// # use scpi::parser::string::ScpiString;
+/// let s = ScpiString::new(b"it''s", b'\'');
+/// assert_eq!(s, b"it's");
+/// assert_eq!(s.len(), 4);
+/// ```
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ScpiString<'a> {
+    raw: &'a [u8],
+    quote: u8,
+}
+
+impl<'a> ScpiString<'a> {
+    /// Create a string from its raw (escaped) contents and the enclosing quote character (`'` or `"`).
+    pub const fn new(raw: &'a [u8], quote: u8) -> Self {
+        Self { raw, quote }
+    }
+
+    /// Raw contents as found in the program message, embedded quotes are still doubled.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// The enclosing quote character
+    pub fn quote(&self) -> u8 {
+        self.quote
+    }
+
+    /// Returns true if the string contains any doubled quotes.
+    pub fn is_escaped(&self) -> bool {
+        self.raw.contains(&self.quote)
+    }
+
+    /// Returns the contents as a slice if no unescaping is necessary.
+    pub fn as_unescaped(&self) -> Option<&'a [u8]> {
+        if self.is_escaped() {
+            None
+        } else {
+            Some(self.raw)
+        }
+    }
+
+    /// Iterate over the unescaped bytes of the string.
+    pub fn bytes(&self) -> Unescaped<'a> {
+        Unescaped {
+            chars: self.raw.iter(),
+            quote: self.quote,
+        }
+    }
+
+    /// Length of the unescaped string
+    pub fn len(&self) -> usize {
+        self.bytes().count()
+    }
+
+    /// Returns true if the string has no characters, e.g. `""`
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Copy the unescaped string into a [`alloc::vec::Vec`].
+    #[cfg(feature = "alloc")]
+    pub fn to_owned(&self) -> alloc::vec::Vec<u8> {
+        self.bytes().collect()
+    }
+}
+
+/// Iterator over the unescaped bytes of a [ScpiString].
+#[derive(Debug, Clone)]
+pub struct Unescaped<'a> {
+    chars: Iter<'a, u8>,
+    quote: u8,
+}
+
+impl<'a> Iterator for Unescaped<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let c = *self.chars.next()?;
+        if c == self.quote {
+            // Doubled quote, skip the second one
+            self.chars.next();
+        }
+        Some(c)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.chars.len();
+        (len / 2, Some(len))
+    }
+}
+
+impl<'a> FusedIterator for Unescaped<'a> {}
+
+impl<'a, 'b> PartialEq<&'b [u8]> for ScpiString<'a> {
+    fn eq(&self, other: &&'b [u8]) -> bool {
+        self.bytes().eq(other.iter().copied())
+    }
+}
+
+impl<'a, 'b, const N: usize> PartialEq<&'b [u8; N]> for ScpiString<'a> {
+    fn eq(&self, other: &&'b [u8; N]) -> bool {
+        self.bytes().eq(other.iter().copied())
+    }
+}
+
+/// Convert string data into a [ScpiString].
+///
+/// # Returns
+/// * `Ok(ScpiString)` - If data is a string.
+/// * `Err(DataTypeError)` - If data is not a string.
+/// * `Err(SyntaxError)` - If token is not data
+impl<'a> TryFrom<Token<'a>> for ScpiString<'a> {
+    type Error = Error;
+
+    fn try_from(value: Token<'a>) -> Result<Self> {
+        match value {
+            Token::StringProgramData(s) => Ok(s),
+            t => {
+                if t.is_data() {
+                    Err(ErrorCode::DataTypeError.into())
+                } else {
+                    parser_unreachable!()
+                }
+            }
+        }
+    }
+}
+
+impl<'a> ResponseData for ScpiString<'a> {
+    fn format_response_data(&self, formatter: &mut dyn Formatter) -> Result<()> {
+        if !self.raw.is_ascii() {
+            return Err(ErrorCode::ExecutionError.into());
+        }
+        formatter.push_byte(b'"')?;
+        for c in self.bytes() {
+            if c == b'"' {
+                formatter.push_byte(b'"')?;
+            }
+            formatter.push_byte(c)?;
+        }
+        formatter.push_byte(b'"')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(ScpiString::new(b"MOHM", b'"'), b"MOHM");
+        assert_eq!(ScpiString::new(b"MO''HM", b'\''), b"MO'HM");
+        assert_eq!(ScpiString::new(b"MO\"\"HM", b'"'), b"MO\"HM");
+        // Other quote is not escaped
+        assert_eq!(ScpiString::new(b"MO\"\"HM", b'\''), b"MO\"\"HM");
+        assert_eq!(ScpiString::new(b"''''", b'\''), b"''");
+        assert_eq!(ScpiString::new(b"", b'\''), b"");
+    }
+
+    #[test]
+    fn test_len() {
+        assert_eq!(ScpiString::new(b"it''s", b'\'').len(), 4);
+        assert_eq!(ScpiString::new(b"it''s", b'"').len(), 5);
+        assert!(ScpiString::new(b"", b'"').is_empty());
+    }
+
+    #[test]
+    fn test_as_unescaped() {
+        assert_eq!(
+            ScpiString::new(b"MOHM", b'"').as_unescaped(),
+            Some(&b"MOHM"[..])
+        );
+        assert_eq!(ScpiString::new(b"MO''HM", b'\'').as_unescaped(), None);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_to_owned() {
+        assert_eq!(ScpiString::new(b"MO''HM", b'\'').to_owned(), b"MO'HM");
+    }
+}
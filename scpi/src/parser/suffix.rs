@@ -5,7 +5,7 @@ use {
     crate::{
         error::{Error, ErrorCode},
         parser::{
-            response::{Formatter, ResponseData},
+            response::{Formatter, Real, ResponseData},
             tokenizer::Token,
         },
     },
@@ -75,6 +75,33 @@ where
     }
 }
 
+/// Quantity response data in unit `N` rather than the default unit.
+///
+/// Quantities are formatted as plain numbers in the SI base unit. For temperatures that is
+/// kelvin, although program data without a suffix is in degrees Celsius.
+/// Wrap a quantity to respond in another unit. Unit conversions are rarely exact, use a
+/// [Precision](crate::parser::response::Precision) to round them:
+/// ```
+/// # use scpi::parser::{response::{Formatter, Precision}, suffix::InUnit};
+/// use uom::si::{electric_potential::millivolt, f32::ElectricPotential};
+///
+/// let voltage = ElectricPotential::new::<millivolt>(500.0);
+/// let mut response = Vec::new();
+/// response
+///     .response_unit()
+///     .unwrap()
+///     .data(voltage)
+///     .data(InUnit(voltage, millivolt))
+///     .data(Precision::Decimals(1).data(InUnit(voltage, millivolt)))
+///     .data(Precision::Significant(3).data(voltage))
+///     .finish()
+///     .unwrap();
+/// assert_eq!(response, b"0.5,499.99997,500.0,5.00E-01");
+/// ```
+#[cfg(feature = "uom")]
+#[derive(Debug, Copy, Clone)]
+pub struct InUnit<Q, N>(pub Q, pub N);
+
 #[cfg(feature = "unit-angle")]
 mod angle {
     use super::*;
@@ -368,6 +395,46 @@ mod test_suffix {
         assert!(matches!(peak_to_peak, Db::Logarithmic(_, _)));
     }
 
+    #[test]
+    fn test_response() {
+        use crate::parser::{response::Precision, suffix::InUnit};
+        use uom::si::{electric_potential::kilovolt, electric_potential::millivolt};
+
+        let mut response = std::vec::Vec::new();
+        let voltage = ElectricPotential::new::<millivolt>(250.0);
+        response
+            .response_unit()
+            .unwrap()
+            .data(voltage)
+            .data(InUnit(ElectricPotential::new::<kilovolt>(-2.0), kilovolt))
+            .data(Precision::Decimals(2).data(InUnit(voltage, millivolt)))
+            .data(Precision::Significant(2).data(InUnit(voltage, kilovolt)))
+            .finish()
+            .unwrap();
+        assert_eq!(response, b"0.25,-2.0,250.00,2.5E-04");
+    }
+
+    #[cfg(feature = "unit-thermodynamic-temperature")]
+    #[test]
+    fn test_response_temperature() {
+        use crate::parser::{response::Precision, suffix::InUnit};
+        use uom::si::thermodynamic_temperature::{degree_celsius, kelvin};
+
+        // Program data defaults to degrees Celsius, responses are in kelvin
+        let temp: ThermodynamicTemperature =
+            Token::DecimalNumericProgramData(b"25").try_into().unwrap();
+        let mut response = std::vec::Vec::new();
+        response
+            .response_unit()
+            .unwrap()
+            .data(Precision::Decimals(2).data(temp))
+            .data(Precision::Decimals(2).data(InUnit(temp, kelvin)))
+            .data(Precision::Decimals(0).data(InUnit(temp, degree_celsius)))
+            .finish()
+            .unwrap();
+        assert_eq!(response, b"298.15,298.15,25");
+    }
+
     // #[test]
     // fn test_suffix_numeric_value() {
     //     let volt_max = ElectricPotential::numeric_value_max();
@@ -448,15 +515,63 @@ macro_rules! impl_unit {
             }
         }
 
-        impl<U, V> ResponseData for $unit<U, V> where U: Units<V> + ?Sized, V: Num + Conversion<V> + ResponseData {
+        impl<U, V> ResponseData for $unit<U, V>
+        where
+            U: Units<V> + ?Sized,
+            V: Num + Conversion<V> + ResponseData,
+        {
             fn format_response_data(
                 &self,
                 formatter: &mut dyn Formatter,
             ) -> core::result::Result<(), Error> {
+                // Stored in the SI base unit
                 self.value.format_response_data(formatter)
             }
         }
 
+        impl<U, V, N> ResponseData for InUnit<$unit<U, V>, N>
+        where
+            U: Units<V> + ?Sized,
+            V: Num + Conversion<V> + ResponseData,
+            N: $conversion,
+        {
+            fn format_response_data(
+                &self,
+                formatter: &mut dyn Formatter,
+            ) -> core::result::Result<(), Error> {
+                self.0.get::<N>().format_response_data(formatter)
+            }
+        }
+
+        impl<U, V> ResponseData for Real<$unit<U, V>>
+        where
+            U: Units<V> + ?Sized,
+            V: Num + Conversion<V> + Copy,
+            Real<V>: ResponseData,
+        {
+            fn format_response_data(
+                &self,
+                formatter: &mut dyn Formatter,
+            ) -> core::result::Result<(), Error> {
+                self.1.data(self.0.value).format_response_data(formatter)
+            }
+        }
+
+        impl<U, V, N> ResponseData for Real<InUnit<$unit<U, V>, N>>
+        where
+            U: Units<V> + ?Sized,
+            V: Num + Conversion<V>,
+            Real<V>: ResponseData,
+            N: $conversion,
+        {
+            fn format_response_data(
+                &self,
+                formatter: &mut dyn Formatter,
+            ) -> core::result::Result<(), Error> {
+                self.1.data(self.0 .0.get::<N>()).format_response_data(formatter)
+            }
+        }
+
         // impl<U, V, T> NumericValueDefaults for $unit<U, V>
         // where
         //     U: Units<V> + ?Sized,
@@ -502,7 +617,7 @@ macro_rules! impl_unit {
                     .try_into();
                 assert_eq!(l, Err(Error::from(ErrorCode::IllegalParameterValue)));
                 // Do not accept incorrect datatype
-                let l: Result<$unit, Error> = Token::StringProgramData(b"STRING").try_into();
+                let l: Result<$unit, Error> = Token::StringProgramData(ScpiString::new(b"STRING", b'"')).try_into();
                 assert_eq!(l, Err(Error::from(ErrorCode::DataTypeError)))
                 // Do not accept
             }
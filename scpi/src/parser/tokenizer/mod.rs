@@ -1,7 +1,10 @@
 //! The tokenizer splits a SCPI command into more managable tokens.
 //!   
 
-use crate::error::ErrorCode;
+use crate::error::{ErrorCode, Span};
+use crate::parser::config::ParserConfig;
+use crate::parser::message::Terminator;
+use crate::parser::string::ScpiString;
 
 use core::slice::Iter;
 
@@ -13,11 +16,19 @@ pub mod util;
 #[cfg(test)]
 mod tests;
 
+type Peeked<'a> = (Option<Result<Token<'a>, ErrorCode>>, Span);
+
 #[derive(Clone)]
 pub struct Tokenizer<'a> {
     pub chars: Iter<'a, u8>,
+    buf: &'a [u8],
     in_header: bool,
     in_common: bool,
+    streaming: bool,
+    terminator: Terminator,
+    config: ParserConfig,
+    peeked: Option<Peeked<'a>>,
+    span: Span,
 }
 
 impl<'a> Tokenizer<'a> {
@@ -33,28 +44,115 @@ impl<'a> Tokenizer<'a> {
 
     pub(crate) fn from_byte_iter(iter: Iter<'a, u8>) -> Self {
         Tokenizer {
+            buf: iter.as_slice(),
             chars: iter,
             in_header: true,
             in_common: false,
+            streaming: false,
+            terminator: Terminator::default(),
+            config: ParserConfig::default(),
+            peeked: None,
+            span: Span::default(),
+        }
+    }
+
+    /// Emit [Token::ArbitraryBlockStream] for block data which does not end within the buffer
+    /// instead of failing with [ErrorCode::InvalidBlockData].
+    pub(crate) fn streaming(mut self) -> Self {
+        self.streaming = true;
+        self
+    }
+
+    /// Use `terminator` as the <PROGRAM MESSAGE TERMINATOR> instead of NL
+    pub fn terminator(mut self, terminator: Terminator) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Parse according to `config` instead of [ParserConfig::STRICT]
+    pub fn config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Does the remaining input start with the message terminator?
+    fn at_terminator(&self) -> bool {
+        self.terminator.is_prefix_of(self.chars.as_slice())
+    }
+
+    /// Peek at the next token without consuming it
+    pub fn peek(&mut self) -> Option<&Result<Token<'a>, ErrorCode>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read_spanned());
+        }
+        self.peeked.as_ref().and_then(|(tok, _)| tok.as_ref())
+    }
+
+    /// Consume and return the next token if `func` returns true for it
+    pub fn next_if(
+        &mut self,
+        func: impl FnOnce(&Result<Token<'a>, ErrorCode>) -> bool,
+    ) -> Option<Result<Token<'a>, ErrorCode>> {
+        match self.peek() {
+            Some(tok) if func(tok) => self.next(),
+            _ => None,
         }
     }
 
+    /// Location of the last token returned by [Iterator::next] within the program message
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Location of the token returned by [Self::peek] within the program message
+    pub fn peek_span(&self) -> Option<Span> {
+        self.peeked.as_ref().map(|(_, span)| *span)
+    }
+
+    /// Location of a slice returned in a token within the program message.
+    /// Returns [None] if the slice is not part of this program message.
+    pub fn span_of(&self, s: &[u8]) -> Option<Span> {
+        let start = (s.as_ptr() as usize).checked_sub(self.buf.as_ptr() as usize)?;
+        if start + s.len() <= self.buf.len() {
+            Some(Span::new(start, start + s.len()))
+        } else {
+            None
+        }
+    }
+
+    /// Current offset from the start of the program message
+    fn offset(&self) -> usize {
+        self.buf.len() - self.chars.as_slice().len()
+    }
+
+    fn read_spanned(&mut self) -> Peeked<'a> {
+        let start = self.offset();
+        let tok = self.read_token();
+        let mut end = self.offset();
+        // Don't include trailing white space
+        while end > start + 1 && self.buf[end - 1].is_ascii_whitespace() {
+            end -= 1;
+        }
+        (tok, Span::new(start, end))
+    }
+
     /// <program mnemonic>
     /// See IEEE 488.2-1992 7.6.1
     /// Must start with a alphabetic character followed by alphanumeric and '_' characters.
     ///
     /// Returned errors:
-    /// * ProgramMnemonicTooLong if suffix is longer than 12 characters
+    /// * ProgramMnemonicTooLong if mnemonic is longer than 12 characters (unless disabled by
+    ///   [ParserConfig::mnemonic_limit])
     fn read_mnemonic(&mut self, mut common: bool) -> Result<Token<'a>, ErrorCode> {
         let s = self.chars.as_slice();
-        let mut len = 0u8;
+        let mut len = 0usize;
         while self.chars.clone().next().map_or(false, |ch| {
             ch.is_ascii_alphanumeric() || *ch == b'_' || (*ch == b'*' && common)
         }) {
             common = false;
             self.chars.next();
             len += 1;
-            if len > 12 {
+            if len > 12 && self.config.mnemonic_limit {
                 return Err(ErrorCode::ProgramMnemonicTooLong);
             }
         }
@@ -68,10 +166,11 @@ impl<'a> Tokenizer<'a> {
     /// Consists of a single <program mnemonic>
     ///
     /// Returned errors:
-    /// * CharacterDataTooLong if suffix is longer than 12 characters
+    /// * CharacterDataTooLong if data is longer than 12 characters (unless disabled by
+    ///   [ParserConfig::mnemonic_limit])
     pub(crate) fn read_character_data(&mut self) -> Result<Token<'a>, ErrorCode> {
         let s = self.chars.as_slice();
-        let mut len = 0u8;
+        let mut len = 0usize;
         while self
             .chars
             .clone()
@@ -80,7 +179,7 @@ impl<'a> Tokenizer<'a> {
         {
             self.chars.next();
             len += 1;
-            if len > 12 {
+            if len > 12 && self.config.mnemonic_limit {
                 return Err(ErrorCode::CharacterDataTooLong);
             }
         }
@@ -93,6 +192,10 @@ impl<'a> Tokenizer<'a> {
         ret
     }
 
+    /// <NRf>
+    /// See IEEE 488.2-1992 7.7.2.2
+    /// White space is allowed before and after the exponent character and its sign, e.g. `1.5 E -3`.
+    /// Use [util::normalize_nrf] to remove it before handing the number to lexical-core.
     pub(crate) fn read_nrf(&mut self) -> Result<Token<'a>, ErrorCode> {
         let s = self.chars.as_slice();
         /* Read leading +/- */
@@ -108,19 +211,26 @@ impl<'a> Tokenizer<'a> {
         } else if !leading_digits {
             return Err(ErrorCode::NumericDataError);
         }
-        //TODO: Lexical-core doesn't like ws around Exponent
-        //util::skip_ws(self.chars);
+        let mantissa = self.chars.clone();
+        util::skip_ws(&mut self.chars);
+        let ws = self.chars.as_slice().len() != mantissa.as_slice().len();
         /* Read exponent */
-        if let Some(exponent) = self.chars.clone().next() {
-            if *exponent == b'E' || *exponent == b'e' {
+        match self.chars.clone().next() {
+            Some(b'E' | b'e') => {
                 self.chars.next().unwrap();
-                //TODO: Lexical-core doesn't like ws around Exponent
-                //util::skip_ws(self.chars);
+                util::skip_ws(&mut self.chars);
                 util::skip_sign(&mut self.chars);
+                util::skip_ws(&mut self.chars);
                 if !util::skip_digits(&mut self.chars) {
-                    return Err(ErrorCode::NumericDataError);
+                    if ws {
+                        // Not an exponent, probably a suffix
+                        self.chars = mantissa;
+                    } else {
+                        return Err(ErrorCode::NumericDataError);
+                    }
                 }
             }
+            _ => self.chars = mantissa,
         }
         Ok(Token::DecimalNumericProgramData(
             &s[0..s.len() - self.chars.as_slice().len()],
@@ -130,7 +240,6 @@ impl<'a> Tokenizer<'a> {
     /// <DECIMAL NUMERIC PROGRAM DATA>
     /// See IEEE 488.2-1992 7.7.2
     ///
-    /// TODO: lexical-core does not accept whitespace between exponent separator and exponent <mantissa>E <exponent>.
     pub(crate) fn read_numeric_data(&mut self) -> Result<Token<'a>, ErrorCode> {
         let tok = self.read_nrf()?;
         if let Token::DecimalNumericProgramData(s) = tok {
@@ -153,6 +262,17 @@ impl<'a> Tokenizer<'a> {
     /// Returned errors:
     /// * SuffixTooLong if suffix is longer than 12 characters
     fn read_suffix_data(&mut self, val: &'a [u8]) -> Result<Token<'a>, ErrorCode> {
+        let ret = Ok(Token::DecimalNumericSuffixProgramData(
+            val,
+            self.read_suffix()?,
+        ));
+        // Skip to next separator
+        self.skip_ws_to_separator(ErrorCode::InvalidSuffix)?;
+        ret
+    }
+
+    /// Reads the suffix of a <SUFFIX PROGRAM DATA> without checking what follows it
+    pub(crate) fn read_suffix(&mut self) -> Result<&'a [u8], ErrorCode> {
         let s = self.chars.as_slice();
         let mut len = 0u8;
         while self.chars.clone().next().map_or(false, |ch| {
@@ -164,14 +284,7 @@ impl<'a> Tokenizer<'a> {
                 return Err(ErrorCode::SuffixTooLong);
             }
         }
-
-        let ret = Ok(Token::DecimalNumericSuffixProgramData(
-            val,
-            &s[0..s.len() - self.chars.as_slice().len()],
-        ));
-        // Skip to next separator
-        self.skip_ws_to_separator(ErrorCode::InvalidSuffix)?;
-        ret
+        Ok(&s[0..s.len() - self.chars.as_slice().len()])
     }
 
     /// <NONDECIMAL NUMERIC PROGRAM DATA>
@@ -255,9 +368,10 @@ impl<'a> Tokenizer<'a> {
                 return Err(ErrorCode::InvalidStringData);
             }
         }
-        let ret = Ok(Token::StringProgramData(
+        let ret = Ok(Token::StringProgramData(ScpiString::new(
             &s[0..s.len() - self.chars.as_slice().len() - 1],
-        ));
+            x,
+        )));
         // Skip to next separator
         self.skip_ws_to_separator(ErrorCode::SuffixNotAllowed)?;
         ret
@@ -271,19 +385,17 @@ impl<'a> Tokenizer<'a> {
             if len == 0 {
                 //Take rest of string
                 let rest = self.chars.as_slice();
-                if rest.is_empty() {
-                    return Err(ErrorCode::InvalidBlockData);
+                if self.streaming {
+                    // Terminator is checked when reading the payload
+                    self.chars = rest[rest.len()..].iter();
+                    return Ok(Token::ArbitraryBlockStream(rest, None));
                 }
+                //Indefinite block data must be terminated with NL (or configured terminator)
+                //before END
                 let u8str = rest
-                    .get(0..rest.len() - 1)
+                    .strip_suffix(self.terminator.as_bytes())
                     .ok_or(ErrorCode::InvalidBlockData)?;
-                for _ in u8str {
-                    self.chars.next();
-                }
-                //Indefinite block data must be terminated with NL before END
-                if *self.chars.next().unwrap() != b'\n' {
-                    return Err(ErrorCode::InvalidBlockData);
-                }
+                self.chars = rest[rest.len()..].iter();
                 return Ok(Token::ArbitraryBlockData(u8str));
             }
 
@@ -295,6 +407,11 @@ impl<'a> Tokenizer<'a> {
             )
             .map_err(|_| ErrorCode::InvalidBlockData)?;
             self.chars.nth(len as usize - 1).unwrap();
+            let rest = self.chars.as_slice();
+            if self.streaming && rest.len() < payload_len {
+                self.chars = rest[rest.len()..].iter();
+                return Ok(Token::ArbitraryBlockStream(rest, Some(payload_len)));
+            }
             let u8str = self
                 .chars
                 .as_slice()
@@ -319,14 +436,21 @@ impl<'a> Tokenizer<'a> {
     pub(crate) fn read_expression_data(&mut self) -> Result<Token<'a>, ErrorCode> {
         self.chars.next();
         let s = self.chars.as_slice();
-        static ILLEGAL_CHARS: &[u8] = &[b'"', b'\'', b';', b'(', b')'];
-        //Read until closing ')'
-        while self.chars.clone().next().map_or(false, |ch| *ch != b')') {
-            let c = self.chars.next().unwrap();
-            //Return an error if a unexpected character is encountered
-            if ILLEGAL_CHARS.contains(c) || !c.is_ascii() {
-                return Err(ErrorCode::InvalidExpression);
+        static ILLEGAL_CHARS: &[u8] = &[b'"', b'\'', b';'];
+        //Read until closing ')', nested parentheses are allowed for numeric expressions
+        let mut depth = 0usize;
+        while let Some(c) = self.chars.clone().next() {
+            match c {
+                b')' if depth == 0 => break,
+                b')' => depth -= 1,
+                b'(' => depth += 1,
+                //Return an error if a unexpected character is encountered
+                c if ILLEGAL_CHARS.contains(c) || !c.is_ascii() => {
+                    return Err(ErrorCode::InvalidExpression)
+                }
+                _ => {}
             }
+            self.chars.next();
         }
 
         let ret = Ok(Token::ExpressionProgramData(
@@ -344,7 +468,7 @@ impl<'a> Tokenizer<'a> {
     fn skip_ws_to_separator(&mut self, error: ErrorCode) -> Result<(), ErrorCode> {
         util::skip_ws(&mut self.chars);
         if let Some(c) = self.chars.clone().next() {
-            if *c != b',' && *c != b';' && *c != b'\n' {
+            if *c != b',' && *c != b';' && !self.at_terminator() {
                 return Err(error);
             }
         }
@@ -356,8 +480,30 @@ impl<'a> Iterator for Tokenizer<'a> {
     type Item = Result<Token<'a>, ErrorCode>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let (tok, span) = match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.read_spanned(),
+        };
+        self.span = span;
+        tok
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    fn read_token(&mut self) -> Option<Result<Token<'a>, ErrorCode>> {
         let x = self.chars.clone().next()?;
         let ret = match x {
+            /* Message terminator */
+            //END is implied.
+            // Parser should reset itself and parse next message as a new message.
+            _ if self.at_terminator() => {
+                self.chars = self.chars.as_slice()[self.terminator.as_bytes().len()..].iter();
+                if self.chars.next().is_none() {
+                    None
+                } else {
+                    Some(Err(ErrorCode::SyntaxError))
+                }
+            }
             /* Common command prefix */
             b'*' => {
                 self.in_common = true;
@@ -384,7 +530,7 @@ impl<'a> Iterator for Tokenizer<'a> {
                 self.chars.next();
                 //Next character after query must be a space, unit separator or <END>
                 if let Some(x) = self.chars.clone().next() {
-                    if !x.is_ascii_whitespace() && *x != b';' {
+                    if !x.is_ascii_whitespace() && *x != b';' && self.config.query_separator {
                         return Some(Err(ErrorCode::SyntaxError));
                     }
                 }
@@ -403,17 +549,6 @@ impl<'a> Iterator for Tokenizer<'a> {
                 self.in_common = false;
                 Some(Ok(Token::ProgramMessageUnitSeparator))
             }
-            /* Message terminator */
-            //END is implied.
-            // Parser should reset itself and parse next message as a new message.
-            b'\n' => {
-                self.chars.next();
-                if self.chars.next().is_none() {
-                    None
-                } else {
-                    Some(Err(ErrorCode::SyntaxError))
-                }
-            }
             /* Data separator*/
             b',' => {
                 self.chars.next();
@@ -422,7 +557,7 @@ impl<'a> Iterator for Tokenizer<'a> {
                 } else {
                     util::skip_ws(&mut self.chars);
                     if let Some(c) = self.chars.clone().next() {
-                        if *c == b',' || *c == b';' || *c == b'\n' {
+                        if *c == b',' || *c == b';' || self.at_terminator() {
                             return Some(Err(ErrorCode::SyntaxError));
                         }
                     }
@@ -448,6 +583,16 @@ impl<'a> Iterator for Tokenizer<'a> {
             }
             /* Number */
             x if x.is_ascii_digit() || *x == b'-' || *x == b'+' || *x == b'.' => {
+                let rest = &self.chars.as_slice()[1..];
+                if self.in_header
+                    && *x == b'+'
+                    && !self.config.header_sign
+                    && matches!(rest.first(), Some(c) if c.is_ascii_alphabetic() || *c == b'*' || *c == b':')
+                {
+                    // Ignore sign before header
+                    self.chars.next();
+                    return self.read_token();
+                }
                 if self.in_header {
                     Some(Err(ErrorCode::CommandHeaderError))
                 } else {
@@ -475,7 +620,7 @@ impl<'a> Iterator for Tokenizer<'a> {
                 if self.in_header {
                     Some(Err(ErrorCode::CommandHeaderError))
                 } else {
-                    Some(self.read_string_data(*x, true))
+                    Some(self.read_string_data(*x, self.config.ascii_strings))
                 }
             }
             b'(' => Some(self.read_expression_data()),
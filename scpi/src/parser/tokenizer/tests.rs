@@ -1,4 +1,7 @@
 use super::*;
+use crate::error::Span;
+use crate::parser::config::ParserConfig;
+use crate::parser::message::Terminator;
 
 extern crate std;
 
@@ -67,8 +70,6 @@ fn test_read_character_data() {
 
 #[test]
 fn test_read_numeric_data() {
-    //TODO: FIX EXPONENTS!
-
     assert_eq!(
         Tokenizer::new(b"25").read_numeric_data().unwrap(),
         Token::DecimalNumericProgramData(b"25")
@@ -99,6 +100,33 @@ fn test_read_numeric_data() {
         Token::DecimalNumericProgramData(b".1E2")
     );
 
+    // White space around exponent
+    assert_eq!(
+        Tokenizer::new(b"1.5 E -3").read_numeric_data().unwrap(),
+        Token::DecimalNumericProgramData(b"1.5 E -3")
+    );
+
+    assert_eq!(
+        Tokenizer::new(b"1.5E- 3").read_numeric_data().unwrap(),
+        Token::DecimalNumericProgramData(b"1.5E- 3")
+    );
+
+    assert_eq!(
+        Tokenizer::new(b"1.5\tE3 V").read_numeric_data().unwrap(),
+        Token::DecimalNumericSuffixProgramData(b"1.5\tE3", b"V")
+    );
+
+    // Not an exponent, suffix
+    assert_eq!(
+        Tokenizer::new(b"1 EV").read_numeric_data().unwrap(),
+        Token::DecimalNumericSuffixProgramData(b"1", b"EV")
+    );
+
+    assert_eq!(
+        Tokenizer::new(b"1EV").read_numeric_data().unwrap_err(),
+        ErrorCode::NumericDataError
+    );
+
     assert_eq!(
         Tokenizer::new(b".1E2  SUFFIX").read_numeric_data().unwrap(),
         Token::DecimalNumericSuffixProgramData(b".1E2", b"SUFFIX")
@@ -117,6 +145,24 @@ fn test_read_numeric_data() {
     );
 }
 
+#[test]
+fn test_normalize_nrf() {
+    let mut buf = [0u8; util::NRF_BUFFER_SIZE];
+    assert_eq!(util::normalize_nrf(b"1.5E-3", &mut buf), Ok(&b"1.5E-3"[..]));
+    assert_eq!(
+        util::normalize_nrf(b"1.5 E - 3", &mut buf),
+        Ok(&b"1.5E-3"[..])
+    );
+    let long = [b'1'; util::NRF_BUFFER_SIZE + 1];
+    assert_eq!(util::normalize_nrf(&long, &mut buf), Ok(&long[..]));
+    let mut long_ws = [b'1'; util::NRF_BUFFER_SIZE + 2];
+    long_ws[1] = b' ';
+    assert_eq!(
+        util::normalize_nrf(&long_ws, &mut buf),
+        Err(ErrorCode::TooManyDigits)
+    );
+}
+
 #[test]
 fn test_read_suffix_data() {}
 
@@ -147,15 +193,15 @@ fn test_read_numeric_suffix_data() {
 fn test_read_string_data() {
     assert_eq!(
         Tokenizer::new(b"\"MOHM\",  gui").read_string_data(b'"', true),
-        Ok(Token::StringProgramData(b"MOHM"))
+        Ok(Token::StringProgramData(ScpiString::new(b"MOHM", b'"')))
     );
     assert_eq!(
         Tokenizer::new(b"'MOHM',  gui").read_string_data(b'\'', true),
-        Ok(Token::StringProgramData(b"MOHM"))
+        Ok(Token::StringProgramData(ScpiString::new(b"MOHM", b'\'')))
     );
     assert_eq!(
         Tokenizer::new(b"'MO''HM',  gui").read_string_data(b'\'', true),
-        Ok(Token::StringProgramData(b"MO''HM"))
+        Ok(Token::StringProgramData(ScpiString::new(b"MO''HM", b'\'')))
     );
 
     assert_eq!(
@@ -212,6 +258,50 @@ fn test_read_arb_data() {
         Tokenizer::new(b"\x01\x02").read_arbitrary_data(b'0'),
         Err(ErrorCode::InvalidBlockData)
     );
+
+    // Indefinite length with other terminators
+    assert_eq!(
+        Tokenizer::new(b"\x01\n\r\n")
+            .terminator(Terminator::CrNl)
+            .read_arbitrary_data(b'0'),
+        Ok(Token::ArbitraryBlockData(&[1, b'\n']))
+    );
+    assert_eq!(
+        Tokenizer::new(b"\x01\x02\n")
+            .terminator(Terminator::End)
+            .read_arbitrary_data(b'0'),
+        Ok(Token::ArbitraryBlockData(&[1, 2, b'\n']))
+    );
+}
+
+#[test]
+fn test_read_arb_stream() {
+    // Fits in buffer
+    assert_eq!(
+        Tokenizer::new(b"02\x01\x02,")
+            .streaming()
+            .read_arbitrary_data(b'2'),
+        Ok(Token::ArbitraryBlockData(&[1, 2]))
+    );
+    // Continues past end of buffer
+    let mut tokens = Tokenizer::new(b"04\x01\x02").streaming();
+    assert_eq!(
+        tokens.read_arbitrary_data(b'2'),
+        Ok(Token::ArbitraryBlockStream(&[1, 2], Some(4)))
+    );
+    assert_eq!(tokens.next(), None);
+    // Indefinite length
+    assert_eq!(
+        Tokenizer::new(b"\x01\x02")
+            .streaming()
+            .read_arbitrary_data(b'0'),
+        Ok(Token::ArbitraryBlockStream(&[1, 2], None))
+    );
+    // Error, header cut short
+    assert_eq!(
+        Tokenizer::new(b"0").streaming().read_arbitrary_data(b'2'),
+        Err(ErrorCode::InvalidBlockData)
+    );
 }
 
 #[test]
@@ -220,6 +310,21 @@ fn test_read_expr_data() {
         Tokenizer::new(b"(@1!2,2,3,4,5,#,POTATO)").read_expression_data(),
         Ok(Token::ExpressionProgramData(b"@1!2,2,3,4,5,#,POTATO"))
     );
+    assert_eq!(
+        Tokenizer::new(b"((1+2)*(3))").read_expression_data(),
+        Ok(Token::ExpressionProgramData(b"(1+2)*(3)"))
+    );
+
+    // Error, unbalanced parentheses
+    assert_eq!(
+        Tokenizer::new(b"((1+2)").read_expression_data(),
+        Err(ErrorCode::InvalidExpression)
+    );
+    // Error, illegal character
+    assert_eq!(
+        Tokenizer::new(b"(1;2)").read_expression_data(),
+        Err(ErrorCode::InvalidExpression)
+    );
 }
 
 macro_rules! match_tokens {
@@ -272,7 +377,7 @@ fn test_parse_programdata() {
         Ok(Token::ProgramDataSeparator),
         Ok(Token::DecimalNumericProgramData(b"255")),
         Ok(Token::ProgramDataSeparator),
-        Ok(Token::StringProgramData(b"STRING")),
+        Ok(Token::StringProgramData(ScpiString::new(b"STRING", b'"'))),
         Ok(Token::ProgramDataSeparator),
         Ok(Token::DecimalNumericSuffixProgramData(b"1", b"SUFFIX")),
         Ok(Token::ProgramDataSeparator),
@@ -283,3 +388,147 @@ fn test_parse_programdata() {
         Ok(Token::ExpressionProgramData(b"1,11,3:9"))
     ];
 }
+
+#[test]
+fn test_parse_terminator() {
+    let tokens = |s, terminator| -> std::vec::Vec<Result<Token, ErrorCode>> {
+        Tokenizer::new(s).terminator(terminator).collect()
+    };
+    let expected = [
+        Ok(Token::ProgramMnemonic(b"VOLT")),
+        Ok(Token::ProgramHeaderSeparator),
+        Ok(Token::DecimalNumericProgramData(b"1")),
+        Ok(Token::ProgramDataSeparator),
+        Ok(Token::StringProgramData(ScpiString::new(b"A", b'"'))),
+    ];
+    assert_eq!(tokens(b"VOLT 1,\"A\"\n", Terminator::Nl), expected);
+    assert_eq!(tokens(b"VOLT 1,\"A\"\r\n", Terminator::CrNl), expected);
+    assert_eq!(tokens(b"VOLT 1,\"A\"\r", Terminator::Cr), expected);
+    assert_eq!(tokens(b"VOLT 1,\"A\"", Terminator::End), expected);
+    // Nothing may follow the terminator
+    assert_eq!(
+        tokens(b"*RST\r*CLS", Terminator::Cr)[..2],
+        [
+            Ok(Token::ProgramMnemonic(b"*RST")),
+            Err(ErrorCode::SyntaxError)
+        ]
+    );
+    assert_eq!(
+        tokens(b"*RST\n*CLS", Terminator::Nl)[..2],
+        [
+            Ok(Token::ProgramMnemonic(b"*RST")),
+            Err(ErrorCode::SyntaxError)
+        ]
+    );
+}
+
+#[test]
+fn test_parse_config() {
+    let tokens = |s, config| -> std::vec::Vec<Result<Token, ErrorCode>> {
+        // Errors don't always consume input
+        Tokenizer::new(s).config(config).take(8).collect()
+    };
+    // Mnemonic and character data length
+    assert_eq!(
+        tokens(b"MNEMONICISLONG", ParserConfig::STRICT)[0],
+        Err(ErrorCode::ProgramMnemonicTooLong)
+    );
+    assert_eq!(
+        tokens(b"MNEMONICISLONG CHARACTERDATA", ParserConfig::LENIENT),
+        [
+            Ok(Token::ProgramMnemonic(b"MNEMONICISLONG")),
+            Ok(Token::ProgramHeaderSeparator),
+            Ok(Token::CharacterProgramData(b"CHARACTERDATA"))
+        ]
+    );
+    // Non-ASCII strings
+    assert_eq!(
+        tokens("A \"\u{b0}C\"".as_bytes(), ParserConfig::STRICT)[2],
+        Err(ErrorCode::InvalidCharacter)
+    );
+    assert_eq!(
+        tokens("A \"\u{b0}C\"".as_bytes(), ParserConfig::LENIENT)[2],
+        Ok(Token::StringProgramData(ScpiString::new(
+            "\u{b0}C".as_bytes(),
+            b'"'
+        )))
+    );
+    // Header sign
+    assert_eq!(
+        tokens(b"+*RST;+:VOLT 1", ParserConfig::STRICT)[0],
+        Err(ErrorCode::CommandHeaderError)
+    );
+    assert_eq!(
+        tokens(b"+*RST;+:VOLT +1", ParserConfig::LENIENT),
+        [
+            Ok(Token::ProgramMnemonic(b"*RST")),
+            Ok(Token::ProgramMessageUnitSeparator),
+            Ok(Token::HeaderMnemonicSeparator),
+            Ok(Token::ProgramMnemonic(b"VOLT")),
+            Ok(Token::ProgramHeaderSeparator),
+            Ok(Token::DecimalNumericProgramData(b"+1"))
+        ]
+    );
+    // Data directly after query
+    assert_eq!(
+        tokens(b"MEAS?1", ParserConfig::STRICT)[1],
+        Err(ErrorCode::SyntaxError)
+    );
+    assert_eq!(
+        tokens(b"MEAS?1", ParserConfig::LENIENT),
+        [
+            Ok(Token::ProgramMnemonic(b"MEAS")),
+            Ok(Token::HeaderQuerySuffix),
+            Ok(Token::DecimalNumericProgramData(b"1"))
+        ]
+    );
+}
+
+#[test]
+fn test_span() {
+    let mut tokenizer = Tokenizer::new(b"VOLT:RANG 10 XV , 'STR' ");
+    let mut spans = std::vec::Vec::new();
+    while let Some(tok) = tokenizer.next() {
+        assert!(tok.is_ok());
+        spans.push(tokenizer.span());
+    }
+    assert_eq!(
+        spans,
+        [
+            Span::new(0, 4),
+            Span::new(4, 5),
+            Span::new(5, 9),
+            Span::new(9, 10),
+            Span::new(10, 15),
+            Span::new(16, 17),
+            Span::new(18, 23),
+        ]
+    );
+}
+
+#[test]
+fn test_span_peek() {
+    let mut tokenizer = Tokenizer::new(b"HEAD #Q9");
+    assert_eq!(tokenizer.next(), Some(Ok(Token::ProgramMnemonic(b"HEAD"))));
+    assert_eq!(tokenizer.next(), Some(Ok(Token::ProgramHeaderSeparator)));
+    assert_eq!(tokenizer.peek(), Some(&Err(ErrorCode::NumericDataError)));
+    assert_eq!(tokenizer.peek_span(), Some(Span::new(5, 7)));
+    // Last consumed token
+    assert_eq!(tokenizer.span(), Span::new(4, 5));
+    tokenizer.next();
+    assert_eq!(tokenizer.span(), Span::new(5, 7));
+    assert_eq!(tokenizer.peek_span(), None);
+}
+
+#[test]
+fn test_span_of() {
+    let tokenizer = Tokenizer::new(b"HEAD 10 XV");
+    if let Some(Ok(Token::DecimalNumericSuffixProgramData(num, suffix))) = tokenizer.clone().nth(2)
+    {
+        assert_eq!(tokenizer.span_of(num), Some(Span::new(5, 7)));
+        assert_eq!(tokenizer.span_of(suffix), Some(Span::new(8, 10)));
+    } else {
+        panic!("Not a suffixed numeric")
+    }
+    assert_eq!(tokenizer.span_of(b"XV"), None);
+}
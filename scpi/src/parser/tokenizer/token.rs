@@ -1,4 +1,5 @@
 use super::util;
+use crate::parser::string::ScpiString;
 
 /// SCPI tokens
 /// Loosely based on IEEE488.2 Chapter 7
@@ -26,9 +27,13 @@ pub enum Token<'a> {
     /// A <NONDECIMAL NUMERIC PROGRAM DATA> 7.7.4
     NonDecimalNumericProgramData(u64),
     /// A <STRING PROGRAM DATA> 7.7.5
-    StringProgramData(&'a [u8]),
+    StringProgramData(ScpiString<'a>),
     /// A <ARBITRARY BLOCK PROGRAM DATA> 7.7.6
     ArbitraryBlockData(&'a [u8]),
+    /// A <ARBITRARY BLOCK PROGRAM DATA> 7.7.6 whose payload continues past the end of the buffer.
+    /// Holds the part of the payload within the buffer and the total payload length
+    /// ([None] if indefinite).
+    ArbitraryBlockStream(&'a [u8], Option<usize>),
     /// A <EXPRESSION PROGRAM DATA> 7.7.7
     ExpressionProgramData(&'a [u8]),
 }
@@ -43,6 +48,7 @@ impl<'a> Token<'a> {
                 | Self::NonDecimalNumericProgramData(_)
                 | Self::StringProgramData(_)
                 | Self::ArbitraryBlockData(_)
+                | Self::ArbitraryBlockStream(_, _)
                 | Self::ExpressionProgramData(_)
         )
     }
@@ -1,5 +1,7 @@
 use core::slice::Iter;
 
+use crate::error::ErrorCode;
+
 /// Skip continuous digits
 ///
 pub(crate) fn skip_digits(iter: &mut Iter<u8>) -> bool {
@@ -34,6 +36,29 @@ pub(crate) fn skip_ws(iter: &mut Iter<u8>) {
     }
 }
 
+/// Size of the buffer used by [normalize_nrf].
+pub(crate) const NRF_BUFFER_SIZE: usize = 64;
+
+/// Remove any white space around the exponent of a decimal numeric, e.g. `1.5 E -3` => `1.5E-3`.
+/// Returns the numeric as-is if it doesn't contain any white space.
+///
+/// Returned errors:
+/// * TooManyDigits if the normalized numeric does not fit into `buf`
+pub(crate) fn normalize_nrf<'b>(
+    s: &'b [u8],
+    buf: &'b mut [u8; NRF_BUFFER_SIZE],
+) -> Result<&'b [u8], ErrorCode> {
+    if !s.iter().any(u8::is_ascii_whitespace) {
+        return Ok(s);
+    }
+    let mut len = 0;
+    for c in s.iter().filter(|c| !c.is_ascii_whitespace()) {
+        *buf.get_mut(len).ok_or(ErrorCode::TooManyDigits)? = *c;
+        len += 1;
+    }
+    Ok(&buf[..len])
+}
+
 /// Split a mnemonic of the form "ABC123" into ("ABC", "123")
 /// Returns None if the mnemonic does not end with digits.
 pub(crate) fn mnemonic_split_index(mnemonic: &[u8]) -> Option<(&[u8], &[u8])> {
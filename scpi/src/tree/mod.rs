@@ -55,14 +55,16 @@
 //! };
 //! ```
 
-use core::iter::Peekable;
 //extern crate std;
 
 pub mod command;
 
 use command::Command;
 
-use crate::error::{Error, ErrorCode, Result};
+use crate::error::{Error, ErrorCode, Result, Span};
+use crate::parser::block::{BlockSource, BlockStream, Read};
+use crate::parser::canonical::{self, HeaderForm, TreeHeaders};
+use crate::parser::message::Messages;
 use crate::parser::parameters::Parameters;
 use crate::parser::response::Formatter;
 use crate::parser::tokenizer::{Token, Tokenizer};
@@ -78,8 +80,10 @@ pub mod prelude {
         error::{Error, ErrorCode},
         parser::{
             format::*,
+            message::Terminator,
             parameters::Parameters,
             response::{Formatter, ResponseData, ResponseUnit},
+            string::ScpiString,
             tokenizer::{Token, Tokenizer},
         },
         Context, Device,
@@ -256,6 +260,9 @@ where
     /// * context - Context for this command
     /// * response - A formatter to write a response into.
     ///
+    /// If an error is returned, [Error::get_span] points out the offending part of `command`
+    /// (when known) as a byte range, useful for printing diagnostics.
+    ///
     pub fn run<FMT>(
         &self,
         command: &[u8],
@@ -266,7 +273,9 @@ where
     where
         FMT: Formatter,
     {
-        let mut tokenizer = Tokenizer::new(command).peekable();
+        let mut tokenizer = Tokenizer::new(command)
+            .terminator(context.terminator)
+            .config(context.config);
         let res = self.run_tokens(device, context, &mut tokenizer, response);
         if let Err(err) = &res {
             device.handle_error(*err);
@@ -274,11 +283,158 @@ where
         res
     }
 
+    /// Execute every program message in `messages`, e.g. everything a transport received at
+    /// once. See [Messages] for how the buffer is split.
+    ///
+    /// `response` is cleared before each message and `on_response` called with the response
+    /// message of every message that produced one. Errors are reported to the device like
+    /// [Self::run] and any partial response of the failing message is discarded before
+    /// continuing with the next message.
+    ///
+    pub fn run_messages<FMT, F>(
+        &self,
+        messages: &[u8],
+        device: &mut D,
+        context: &mut Context,
+        response: &mut FMT,
+        mut on_response: F,
+    ) where
+        FMT: Formatter,
+        F: FnMut(&[u8]),
+    {
+        for message in Messages::new(messages).terminator(context.terminator) {
+            response.clear();
+            if self.run(message, device, context, response).is_ok() && !response.is_empty() {
+                on_response(response.as_slice());
+            }
+        }
+        response.clear();
+    }
+
+    /// Write the program message in `tokens` to `out` in canonical form with headers in this
+    /// tree written in `form`. Headers not in the tree are written in uppercase as received.
+    ///
+    /// See [canonical](crate::parser::canonical) for details.
+    pub fn write_canonical(
+        &self,
+        tokens: Tokenizer,
+        form: HeaderForm,
+        out: &mut dyn Formatter,
+    ) -> Result<()> {
+        canonical::write_message(tokens, &mut TreeHeaders::new(self, form), out)
+    }
+
+    /// Execute a command which may contain block data too large for the input buffer.
+    ///
+    /// `command` holds the start of the program message as received so far, `source` yields
+    /// the rest of it and must return `Ok(0)` at END. <ARBITRARY BLOCK PROGRAM DATA> not
+    /// ending within `command` is read by the handler with
+    /// [Parameters::next_block](crate::parser::parameters::Parameters::next_block) straight
+    /// from `source`, after which the rest of the message is read into `buf` and executed.
+    ///
+    /// `command` must end at END, or within or right after block data, and contain at least
+    /// the complete block header.
+    ///
+    /// A streamed indefinite block is always terminated by NL^END, whatever
+    /// [Context::terminator] is.
+    ///
+    /// Errors are reported like [Self::run] except spans refer to the buffer being parsed.
+    /// The rest of the message is discarded from `source` on error.
+    pub fn run_stream<FMT>(
+        &self,
+        command: &[u8],
+        source: &mut dyn Read,
+        buf: &mut [u8],
+        device: &mut D,
+        context: &mut Context,
+        response: &mut FMT,
+    ) -> Result<()>
+    where
+        FMT: Formatter,
+    {
+        let mut stream = BlockStream::new(source);
+        let res = self.run_stream_tokens(command, &mut stream, buf, device, context, response);
+        if let Err(err) = &res {
+            stream.discard();
+            device.handle_error(*err);
+        }
+        res
+    }
+
+    fn run_stream_tokens<FMT>(
+        &self,
+        command: &[u8],
+        stream: &mut BlockStream,
+        buf: &mut [u8],
+        device: &mut D,
+        context: &mut Context,
+        response: &mut FMT,
+    ) -> Result<()>
+    where
+        FMT: Formatter,
+    {
+        let mut leaf = self;
+
+        //Start response message
+        response.message_start()?;
+        let mut tokenizer = Tokenizer::new(command)
+            .terminator(context.terminator)
+            .config(context.config)
+            .streaming();
+        self.run_units(
+            &mut leaf,
+            device,
+            context,
+            &mut tokenizer,
+            Some(stream),
+            response,
+        )?;
+        // Message continues after block data
+        loop {
+            stream.finish()?;
+            let rest = stream.read_rest(buf)?;
+            if rest.is_empty() {
+                break;
+            }
+            let mut tokenizer = Tokenizer::new_params(rest)
+                .terminator(context.terminator)
+                .config(context.config)
+                .streaming();
+            // Only a new program message unit may follow the block
+            match tokenizer.peek() {
+                Some(Ok(Token::ProgramMessageUnitSeparator)) | None => {}
+                Some(Ok(tok)) if tok.is_data() || *tok == Token::ProgramDataSeparator => {
+                    return Err(
+                        Error::new(ErrorCode::ParameterNotAllowed).or_span(peek_span(&tokenizer))
+                    );
+                }
+                Some(Err(err)) => return Err(Error::new(*err).or_span(peek_span(&tokenizer))),
+                Some(_) => {
+                    return Err(Error::new(ErrorCode::SyntaxError).or_span(peek_span(&tokenizer)))
+                }
+            }
+            if tokenizer.next().is_some() {
+                self.run_units(
+                    &mut leaf,
+                    device,
+                    context,
+                    &mut tokenizer,
+                    Some(stream),
+                    response,
+                )?;
+            }
+        }
+        if !response.is_empty() {
+            response.message_end(context.terminator)?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn run_tokens<FMT>(
         &self,
         device: &mut D,
         context: &mut Context,
-        tokens: &mut Peekable<Tokenizer>,
+        tokens: &mut Tokenizer,
         response: &mut FMT,
     ) -> Result<()>
     where
@@ -288,57 +444,75 @@ where
 
         //Start response message
         response.message_start()?;
+        self.run_units(&mut leaf, device, context, tokens, None, response)?;
+        if !response.is_empty() {
+            response.message_end(context.terminator)?;
+        }
+        Ok(())
+    }
+
+    /// Execute program message units until the tokens run out
+    fn run_units<FMT>(
+        &'a self,
+        leaf: &mut &'a Node<'a, D>,
+        device: &mut D,
+        context: &mut Context,
+        tokens: &mut Tokenizer,
+        mut stream: Option<&mut (dyn BlockSource + '_)>,
+        response: &mut FMT,
+    ) -> Result<()>
+    where
+        FMT: Formatter,
+    {
         loop {
             // Execute header
             match tokens.peek() {
                 // :header..
                 Some(Ok(Token::HeaderMnemonicSeparator)) => {
-                    leaf = self;
+                    *leaf = self;
                     // Consume seperator
                     tokens.next();
-                    self.exec(&mut leaf, device, context, tokens, response)?;
+                    let stream = stream.as_deref_mut();
+                    self.exec(leaf, device, context, tokens, stream, response)?;
                 }
                 // header.. | *header
                 Some(Ok(Token::ProgramMnemonic(s))) => {
+                    let stream = stream.as_deref_mut();
                     if s.starts_with(b"*") {
                         let mut _x = self;
-                        self.exec(&mut _x, device, context, tokens, response)?;
+                        self.exec(&mut _x, device, context, tokens, stream, response)?;
                     } else {
-                        leaf.exec(&mut leaf, device, context, tokens, response)?;
+                        leaf.exec(leaf, device, context, tokens, stream, response)?;
                     }
                 }
                 // Empty input
                 None => break Ok(()),
                 //
-                Some(Err(err)) => break Err(Error::new(*err)),
+                Some(Err(err)) => break Err(Error::new(*err).or_span(peek_span(tokens))),
                 // idk?
-                Some(_) => break Err(ErrorCode::SyntaxError.into()),
+                Some(_) => break Err(Error::new(ErrorCode::SyntaxError).or_span(peek_span(tokens))),
             }
             // Should've consumed up to unit seperator
 
             // What's next?
             match tokens.next() {
                 // EOM
-                None => {
-                    if !response.is_empty() {
-                        response.message_end()?;
-                    }
-                    break Ok(());
-                }
+                None => break Ok(()),
                 // New unit
                 Some(Ok(Token::ProgramMessageUnitSeparator)) => {
                     continue;
                 }
                 // More tokens...
                 Some(Ok(tok)) => {
-                    if tok.is_data() || tok == Token::ProgramDataSeparator {
-                        break Err(ErrorCode::ParameterNotAllowed.into());
+                    let err = if tok.is_data() || tok == Token::ProgramDataSeparator {
+                        Error::new(ErrorCode::ParameterNotAllowed)
                     } else {
-                        break Err(ErrorCode::SyntaxError.into());
-                    }
+                        Error::new(ErrorCode::SyntaxError)
+                    };
+                    break Err(err.with_span(tokens.span()));
                 }
                 // Error
-                Some(Err(err)) => break Err(Error::new(err)),
+                Some(Err(err)) => break Err(Error::new(err).with_span(tokens.span())),
             }
         }
     }
@@ -348,15 +522,16 @@ where
         leaf: &mut &'a Node<'a, D>,
         device: &mut D,
         context: &mut Context,
-        tokens: &mut Peekable<Tokenizer>,
+        tokens: &mut Tokenizer,
+        stream: Option<&mut (dyn BlockSource + '_)>,
         response: &mut FMT,
     ) -> Result<()>
     where
         FMT: Formatter,
     {
         let next = match tokens.peek() {
-            Some(Ok(tok)) => Some(tok),
-            Some(Err(err)) => return Err(Error::new(*err)),
+            Some(Ok(tok)) => Some(*tok),
+            Some(Err(err)) => return Err(Error::new(*err).or_span(peek_span(tokens))),
             None => None,
         };
 
@@ -365,6 +540,8 @@ where
         match self {
             Node::Leaf { handler, .. } => {
                 //std::println!("Leaf {}", std::str::from_utf8(name).unwrap());
+                // Errors without a location are blamed on the header
+                let header = tokens.span();
                 match next {
                     // "Leaf .." | "Leaf\EOM"
                     Some(Token::ProgramHeaderSeparator | Token::ProgramMessageUnitSeparator)
@@ -373,7 +550,9 @@ where
                         tokens.next_if(|t| matches!(t, Ok(Token::ProgramHeaderSeparator)));
 
                         // Execute handler
-                        handler.event(device, context, Parameters::with(tokens))
+                        handler
+                            .event(device, context, Parameters::with_stream(tokens, stream))
+                            .map_err(|err| err.or_span(header))
                     }
                     // Branch?..
                     Some(Token::HeaderQuerySuffix) => {
@@ -384,15 +563,23 @@ where
                         tokens.next_if(|t| matches!(t, Ok(Token::ProgramHeaderSeparator)));
 
                         // Execute handler
-                        let response_unit = response.response_unit()?;
-                        handler.query(device, context, Parameters::with(tokens), response_unit)
+                        let mut response_unit = response.response_unit()?;
+                        response_unit.precision(device.precision());
+                        handler
+                            .query(
+                                device,
+                                context,
+                                Parameters::with_stream(tokens, stream),
+                                response_unit,
+                            )
+                            .map_err(|err| err.or_span(header))
                     }
                     // This is a leaf node, cannot traverse further
                     Some(Token::HeaderMnemonicSeparator | Token::ProgramMnemonic(..)) => {
-                        Err(ErrorCode::UndefinedHeader.into())
+                        Err(Error::new(ErrorCode::UndefinedHeader).or_span(peek_span(tokens)))
                     }
                     // Tokenizer shouldn't emit anything else...
-                    Some(_) => Err(ErrorCode::SyntaxError.into()),
+                    Some(_) => Err(Error::new(ErrorCode::SyntaxError).or_span(peek_span(tokens))),
                 }
             }
             Node::Branch { sub, .. } => {
@@ -405,9 +592,14 @@ where
 
                         // Get mnemonic
                         let mnemonic = match tokens.peek() {
-                            Some(Ok(mnemonic @ Token::ProgramMnemonic(..))) => mnemonic,
-                            Some(Err(err)) => return Err((*err).into()),
-                            _ => return Err(ErrorCode::CommandHeaderError.into()),
+                            Some(Ok(mnemonic @ Token::ProgramMnemonic(..))) => *mnemonic,
+                            Some(Err(err)) => {
+                                return Err(Error::new(*err).or_span(peek_span(tokens)))
+                            }
+                            _ => {
+                                return Err(Error::new(ErrorCode::CommandHeaderError)
+                                    .or_span(peek_span(tokens)))
+                            }
                         };
 
                         //std::println!("Branch:{mnemonic:?}");
@@ -417,7 +609,7 @@ where
                         for child in *sub {
                             if mnemonic.match_program_header(child.name()) {
                                 tokens.next(); // Consume mnemonic
-                                return child.exec(leaf, device, context, tokens, response);
+                                return child.exec(leaf, device, context, tokens, stream, response);
                             }
                         }
 
@@ -426,9 +618,9 @@ where
                             .iter()
                             .find(|child| matches!(child, Node::Branch { default: true, .. }))
                         {
-                            child.exec(leaf, device, context, tokens, response)
+                            child.exec(leaf, device, context, tokens, stream, response)
                         } else {
-                            Err(ErrorCode::UndefinedHeader.into())
+                            Err(Error::new(ErrorCode::UndefinedHeader).or_span(peek_span(tokens)))
                         }
                     }
                     // Branch .. | Branch\EOM | Branch;
@@ -443,20 +635,28 @@ where
                             .iter()
                             .find(|child| matches!(child, Node::Leaf { default: true, .. }))
                         {
-                            default_leaf.exec(leaf, device, context, tokens, response)
+                            default_leaf.exec(leaf, device, context, tokens, stream, response)
                         } else if let Some(default_branch) = sub
                             .iter()
                             .find(|child| matches!(child, Node::Branch { default: true, .. }))
                         {
-                            default_branch.exec(leaf, device, context, tokens, response)
+                            default_branch.exec(leaf, device, context, tokens, stream, response)
                         } else {
-                            Err(ErrorCode::UndefinedHeader.into())
+                            Err(Error::new(ErrorCode::UndefinedHeader).or_span(tokens.span()))
                         }
                     }
                     // Tokenizer shouldn't emit anything else...
-                    Some(_) => Err(ErrorCode::SyntaxError.into()),
+                    Some(_) => Err(Error::new(ErrorCode::SyntaxError).or_span(peek_span(tokens))),
                 }
             }
         }
     }
 }
+
+/// Location of the next token, or an empty span at the end of the last one if there are no more tokens.
+fn peek_span(tokens: &Tokenizer) -> Span {
+    tokens.peek_span().unwrap_or_else(|| {
+        let end = tokens.span().end;
+        Span::new(end, end)
+    })
+}
@@ -1,6 +1,7 @@
 mod util;
 
 use scpi::cmd_qonly;
+use scpi::parser::expression::numeric_expression::NumericExpression;
 use scpi::{error::Result, tree::prelude::*};
 use util::TestDevice;
 
@@ -53,6 +54,23 @@ impl Command<TestDevice> for StrEchoCommand {
     }
 }
 
+struct ScpiStrEchoCommand;
+
+impl Command<TestDevice> for ScpiStrEchoCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut TestDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let x: ScpiString = params.next_data()?;
+        response.data(x).finish()
+    }
+}
+
 struct ArbEchoCommand;
 
 impl Command<TestDevice> for ArbEchoCommand {
@@ -70,6 +88,24 @@ impl Command<TestDevice> for ArbEchoCommand {
     }
 }
 
+struct ExprCommand;
+
+impl Command<TestDevice> for ExprCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut TestDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let expr: NumericExpression = params.next_data()?;
+        let x = expr.evaluate(|name| name.eq_ignore_ascii_case(b"OFFSET").then_some(0.5))?;
+        response.data(x).finish()
+    }
+}
+
 struct ChrEchoCommand;
 
 impl Command<TestDevice> for ChrEchoCommand {
@@ -243,6 +279,17 @@ macro_rules! test_integer {
                 assert_eq!(res.as_slice(), b"0;1\n");
             }
             #[test]
+            fn test_exponent_ws() {
+                let mut dev = TestDevice::new();
+                let res = util::test_execute_str(
+                    &TEST_TREE,
+                    format!("{cmd} 4.2 E 1;{cmd} 420 E -1", cmd = $cmd).as_bytes(),
+                    &mut dev,
+                )
+                .unwrap();
+                assert_eq!(res.as_slice(), b"42;42\n");
+            }
+            #[test]
             fn test_max_min() {
                 let mut dev = TestDevice::new();
                 let res = util::test_execute_str(
@@ -318,6 +365,8 @@ macro_rules! test_real {
                     ("-1", "-1.0\n"),
                     ("1e10", "1.0e10\n"),
                     ("+1.3E-1", "0.13\n"),
+                    ("1.3 E -1", "0.13\n"),
+                    ("1.3e +1", "13.0\n"),
                 ];
                 for s in &valid {
                     let cmd = format!("{cmd} {value}", cmd = $cmd, value = s.0);
@@ -375,8 +424,10 @@ const TEST_TREE: &Node<TestDevice> = &Branch {
     default: false,
     sub: &[
         add_numeric_command!(b"*STR": &StrEchoCommand),
+        add_numeric_command!(b"*SSTR": &ScpiStrEchoCommand),
         add_numeric_command!(b"*ARB": &ArbEchoCommand),
         add_numeric_command!(b"*CHR": &ChrEchoCommand),
+        add_numeric_command!(b"*EXPR": &ExprCommand),
         add_numeric_command!(b"*UTF8": &Utf8Command::new()),
         add_numeric_command!(b"*F64": &EchoCommand::<f64>::new()),
         add_numeric_command!(b"*F64ISINF": &IsInf::<f64>::new()),
@@ -442,6 +493,23 @@ mod string {
         let res = util::test_execute_str(TEST_TREE, "*STR? 1.0".as_bytes(), &mut dev).unwrap_err();
         assert_eq!(res, Error::from(ErrorCode::DataTypeError));
     }
+
+    #[test]
+    fn test_escaped_str() {
+        let mut dev = TestDevice::new();
+
+        let res = util::test_execute_str(TEST_TREE, "*SSTR? 'it''s'".as_bytes(), &mut dev).unwrap();
+        assert_eq!(res.as_slice(), b"\"it's\"\n");
+
+        let res =
+            util::test_execute_str(TEST_TREE, "*SSTR? \"say \"\"hi\"\"\"".as_bytes(), &mut dev)
+                .unwrap();
+        assert_eq!(res.as_slice(), b"\"say \"\"hi\"\"\"\n");
+
+        let res =
+            util::test_execute_str(TEST_TREE, "*SSTR? 'say \"hi\"'".as_bytes(), &mut dev).unwrap();
+        assert_eq!(res.as_slice(), b"\"say \"\"hi\"\"\"\n");
+    }
 }
 
 mod arbitrary {
@@ -462,6 +530,34 @@ mod arbitrary {
     }
 }
 
+mod expression {
+    use super::*;
+    #[test]
+    fn test_expr() {
+        let mut dev = TestDevice::new();
+
+        let res = util::test_execute_str(TEST_TREE, "*EXPR? (3*OFFSET+2^4)".as_bytes(), &mut dev)
+            .unwrap();
+        assert_eq!(res.as_slice(), b"17.5\n");
+
+        let res =
+            util::test_execute_str(TEST_TREE, "*EXPR? ((1+2)*(12 AND 10))".as_bytes(), &mut dev)
+                .unwrap();
+        assert_eq!(res.as_slice(), b"24.0\n");
+
+        let res =
+            util::test_execute_str(TEST_TREE, "*EXPR? (1/0)".as_bytes(), &mut dev).unwrap_err();
+        assert_eq!(res.get_code(), ErrorCode::MathErrorInExpression.get_code());
+
+        let res =
+            util::test_execute_str(TEST_TREE, "*EXPR? (GAIN)".as_bytes(), &mut dev).unwrap_err();
+        assert_eq!(res.get_code(), ErrorCode::ExecExpressionError.get_code());
+
+        let res = util::test_execute_str(TEST_TREE, "*EXPR? 1".as_bytes(), &mut dev).unwrap_err();
+        assert_eq!(res, Error::from(ErrorCode::DataTypeError));
+    }
+}
+
 mod character {
     use super::*;
     #[test]
@@ -586,3 +682,55 @@ mod test_arrayvec {
         assert_eq!(res.as_slice(), b"1,2,3\n");
     }
 }
+
+mod span {
+    //! Test that errors point out the offending part of the program message
+
+    use super::*;
+    use scpi::error::Span;
+
+    fn error_span(s: &str) -> (Error, Option<Span>) {
+        let mut dev = TestDevice::new();
+        let err = util::test_execute_str(TEST_TREE, s.as_bytes(), &mut dev).unwrap_err();
+        (err, err.get_span())
+    }
+
+    #[test]
+    fn test_suffix_span() {
+        let (err, span) = error_span("*F32? 10XV");
+        assert_eq!(err, Error::from(ErrorCode::SuffixNotAllowed));
+        assert_eq!(span, Some(Span::new(8, 10)));
+    }
+
+    #[test]
+    fn test_data_span() {
+        let (err, span) = error_span("*I32? 'STR'");
+        assert_eq!(err, Error::from(ErrorCode::DataTypeError));
+        assert_eq!(span, Some(Span::new(6, 11)));
+
+        let (err, span) = error_span("*BOOL? 1;*I32? 1,2");
+        assert_eq!(err, Error::from(ErrorCode::ParameterNotAllowed));
+        assert_eq!(span, Some(Span::new(16, 17)));
+    }
+
+    #[test]
+    fn test_missing_span() {
+        let (err, span) = error_span("*I32?");
+        assert_eq!(err, Error::from(ErrorCode::MissingParameter));
+        assert_eq!(span, Some(Span::new(5, 5)));
+    }
+
+    #[test]
+    fn test_header_span() {
+        let (err, span) = error_span("*BOOL? 1;*POTATO");
+        assert_eq!(err, Error::from(ErrorCode::UndefinedHeader));
+        assert_eq!(span, Some(Span::new(9, 16)));
+    }
+
+    #[test]
+    fn test_syntax_span() {
+        let (err, span) = error_span("*I32? #Q9");
+        assert_eq!(err, Error::from(ErrorCode::NumericDataError));
+        assert_eq!(span, Some(Span::new(6, 8)));
+    }
+}
@@ -0,0 +1,270 @@
+//! Test streaming of block data too large for the input buffer
+mod util;
+
+use scpi::cmd_qonly;
+use scpi::parser::block::Read;
+use scpi::parser::message::Terminator;
+use scpi::parser::response::WriteFormatter;
+use scpi::{error::Result, tree::prelude::*, Leaf, Root};
+use util::TestDevice;
+
+/// Responds with the length and byte sum of a block
+struct BlockSumCommand;
+
+impl Command<TestDevice> for BlockSumCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut TestDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let mut block = params.next_block()?;
+        let mut buf = [0u8; 7];
+        let (mut len, mut sum) = (0usize, 0u32);
+        loop {
+            match block.read(&mut buf)? {
+                0 => break,
+                n => {
+                    len += n;
+                    sum += buf[..n].iter().map(|&b| b as u32).sum::<u32>();
+                }
+            }
+        }
+        response.data(len).data(sum).finish()
+    }
+}
+
+/// Reads only the first byte of a block
+struct BlockPeekCommand;
+
+impl Command<TestDevice> for BlockPeekCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut TestDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let mut block = params.next_block()?;
+        let mut buf = [0u8; 1];
+        block.read(&mut buf)?;
+        response.data(buf[0]).finish()
+    }
+}
+
+/// Takes a block as a token without reading its payload
+struct BlockTokenCommand;
+
+impl Command<TestDevice> for BlockTokenCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut TestDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let tok = params.next_token()?;
+        response
+            .data(matches!(tok, Token::ArbitraryBlockStream(..)))
+            .finish()
+    }
+}
+
+const TEST_TREE: &Node<TestDevice> = &Root![
+    Leaf!(b"*SUM" => &BlockSumCommand),
+    Leaf!(b"*PEEK" => &BlockPeekCommand),
+    Leaf!(b"*ECHO" => &BlockEchoCommand),
+    Leaf!(b"*DUMP" => &BlockDumpCommand),
+    Leaf!(b"*TOK" => &BlockTokenCommand)
+];
+
+/// Echoes a block which must fit in the input buffer
+struct BlockEchoCommand;
+
+impl Command<TestDevice> for BlockEchoCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut TestDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let x: Arbitrary = params.next_data()?;
+        response.data(x).finish()
+    }
+}
+
+/// Responds with an indefinite block written in chunks
+struct BlockDumpCommand;
+
+impl Command<TestDevice> for BlockDumpCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut TestDevice,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        response
+            .indefinite_block(|fmt| {
+                for chunk in [&b"AB"[..], b"\n", b"C"] {
+                    fmt.push_str(chunk)?;
+                }
+                Ok(())
+            })
+            .finish()
+    }
+}
+
+/// Execute a message where only the first `head` bytes are in the input buffer
+fn run_stream(message: &[u8], head: usize) -> Result<Vec<u8>> {
+    run_stream_buf(message, head, 32)
+}
+
+/// Same as [run_stream] with `buf_len` bytes for the rest of the message after a block
+fn run_stream_buf(message: &[u8], head: usize, buf_len: usize) -> Result<Vec<u8>> {
+    let mut dev = TestDevice::new();
+    let mut context = Context::default();
+    let mut response = Vec::new();
+    let (command, mut source) = message.split_at(head);
+    let mut buf = vec![0u8; buf_len];
+    TEST_TREE.run_stream(
+        command,
+        &mut source as &mut dyn Read,
+        &mut buf,
+        &mut dev,
+        &mut context,
+        &mut response,
+    )?;
+    assert!(source.is_empty(), "Message not consumed: {:?}", source);
+    Ok(response)
+}
+
+#[test]
+fn test_definite() {
+    let message = b"*SUM? #210ABCDEFGHIJ;*SUM? #13xyz\n";
+    // Buffer ends within or right after block data
+    for head in [10, 15, 20, 30, 32, 33, 34] {
+        assert_eq!(
+            run_stream(message, head),
+            Ok(b"10,695;3,363\n".to_vec()),
+            "head = {head}"
+        );
+    }
+}
+
+#[test]
+fn test_indefinite() {
+    let message = b"*SUM? #0AB\nC\n";
+    for head in 8..=message.len() {
+        assert_eq!(
+            run_stream(message, head),
+            Ok(b"4,208\n".to_vec()),
+            "head = {head}"
+        );
+    }
+    // Not terminated by NL^END
+    assert_eq!(
+        run_stream(b"*SUM? #0ABC", 8),
+        Err(ErrorCode::InvalidBlockData.into())
+    );
+}
+
+#[test]
+fn test_unread() {
+    // Rest of payload is skipped
+    assert_eq!(
+        run_stream(b"*PEEK? #15ABCDE;*PEEK? #0xyz\n", 10),
+        Ok(b"65;120\n".to_vec())
+    );
+    // Also when taken as a token, the payload is never executed
+    assert_eq!(
+        run_stream(b"*TOK? #15;*RST;*SUM? #11a\n", 9),
+        Ok(b"1;1,97\n".to_vec())
+    );
+    assert_eq!(run_stream(b"*TOK? #0;*RST\n", 9), Ok(b"1\n".to_vec()));
+}
+
+#[test]
+fn test_stream_errors() {
+    // Payload shorter than declared
+    assert_eq!(
+        run_stream(b"*SUM? #15ABC", 9),
+        Err(ErrorCode::InvalidBlockData.into())
+    );
+    // Block must be the last parameter
+    assert_eq!(
+        run_stream(b"*SUM? #15ABCDE,1", 9),
+        Err(ErrorCode::ParameterNotAllowed.into())
+    );
+    // Block must fit the buffer unless read with next_block
+    assert_eq!(
+        run_stream(b"*ECHO? #15ABCDE", 10),
+        Err(ErrorCode::TooMuchData.into())
+    );
+    assert_eq!(
+        run_stream(b"*ECHO? #15ABCDE", 15),
+        Ok(b"#15ABCDE\n".to_vec())
+    );
+    // Rest of the message after a block exactly filling the buffer
+    let message = b"*SUM? #13xyz;*SUM? #11a\n";
+    assert_eq!(run_stream_buf(message, 9, 12), Ok(b"3,363;1,97\n".to_vec()));
+    assert_eq!(
+        run_stream_buf(message, 9, 11),
+        Err(ErrorCode::InputBufferOverrun.into())
+    );
+}
+
+/// Execute a message with responses written to a [WriteFormatter], terminating the response
+/// even if the message fails.
+fn run_write(message: &[u8], terminator: Terminator) -> (Result<()>, Vec<u8>) {
+    let mut dev = TestDevice::new();
+    let mut context = Context {
+        terminator,
+        ..Default::default()
+    };
+    let mut response = WriteFormatter::new(Vec::new());
+    let res = TEST_TREE.run(message, &mut dev, &mut context, &mut response);
+    if res.is_err() {
+        response.message_end(terminator).unwrap();
+    }
+    (res, response.into_inner())
+}
+
+#[test]
+fn test_indefinite_response() {
+    assert_eq!(
+        run_write(b"*ECHO? #13xyz;*DUMP?", Terminator::CrNl),
+        (Ok(()), b"#13xyz;#0AB\nC\n".to_vec())
+    );
+    // Nothing may follow an indefinite response
+    assert_eq!(
+        run_write(b"*DUMP?;*ECHO? #13xyz", Terminator::Nl),
+        (
+            Err(ErrorCode::QueryUnterminatedAfterIndefiniteResponse.into()),
+            b"#0AB\nC\n".to_vec()
+        )
+    );
+    // Buffering formatters can't enforce this
+    let mut response = Vec::new();
+    assert_eq!(
+        TEST_TREE.run(
+            b"*DUMP?",
+            &mut TestDevice::new(),
+            &mut Context::default(),
+            &mut response
+        ),
+        Err(ErrorCode::ExecutionError.into())
+    );
+}
@@ -11,6 +11,33 @@ use scpi::cmd_qonly;
 
 struct ChannelListCommand;
 
+fn respond_channel_list(
+    list: channel_list::ChannelList,
+    response: &mut ResponseUnit,
+) -> Result<()> {
+    for item in list {
+        match item? {
+            channel_list::Token::ChannelSpec(t) => {
+                let ch: usize = t.try_into()?;
+                response.data(ch);
+            }
+            range @ channel_list::Token::ChannelRange(..) => {
+                for [ch] in range.expand(&[0..=100])? {
+                    response.data(ch);
+                }
+            }
+            channel_list::Token::PathName(s) => {
+                response.data(s);
+            }
+            channel_list::Token::ModuleChannel(module, list) => {
+                response.data(module);
+                respond_channel_list(channel_list::ChannelList::from_module(list), response)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 impl Command<util::TestDevice> for ChannelListCommand {
     cmd_qonly!();
 
@@ -21,26 +48,8 @@ impl Command<util::TestDevice> for ChannelListCommand {
         mut params: Parameters,
         mut response: ResponseUnit,
     ) -> Result<()> {
-        let numbers: channel_list::ChannelList = params.next_data()?;
-        for item in numbers {
-            match item? {
-                channel_list::Token::ChannelSpec(t) => {
-                    let ch: usize = t.try_into()?;
-                    response.data(ch);
-                }
-                channel_list::Token::ChannelRange(a, b) => {
-                    let a: usize = a.try_into()?;
-                    let b: usize = b.try_into()?;
-                    for i in a..=b {
-                        response.data(i);
-                    }
-                }
-                channel_list::Token::PathName(s) => {
-                    response.data(s);
-                }
-                channel_list::Token::ModuleChannel(_, _) => todo!(),
-            }
-        }
+        let list: channel_list::ChannelList = params.next_data()?;
+        respond_channel_list(list, &mut response)?;
         response.finish()
     }
 }
@@ -0,0 +1,231 @@
+//! Test the IEEE 488.2 message exchange protocol
+mod util;
+
+use scpi::{
+    cmd_both, cmd_qonly,
+    error::Result,
+    exchange::{MessageExchange, State},
+    parser::message::Terminator,
+    tree::prelude::*,
+    Leaf, Root,
+};
+use util::ErrorDevice;
+
+/// Sets or returns a value
+struct ValueCommand;
+
+impl Command<ErrorDevice> for ValueCommand {
+    cmd_both!();
+
+    fn event(
+        &self,
+        device: &mut ErrorDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+    ) -> Result<()> {
+        device.value = params.next_data()?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        device: &mut ErrorDevice,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        response.data(device.value).finish()
+    }
+}
+
+/// Responds with MAV as seen by the command
+struct MavCommand;
+
+impl Command<ErrorDevice> for MavCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut ErrorDevice,
+        context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        response.data(context.mav()).finish()
+    }
+}
+
+const TEST_TREE: &Node<ErrorDevice> = &Root![
+    Leaf!(b"VALue" => &ValueCommand),
+    Leaf!(b"*MAV" => &MavCommand)
+];
+
+struct Fixture<'a> {
+    exchange: MessageExchange<'a>,
+    device: ErrorDevice,
+    context: Context<'static>,
+}
+
+impl<'a> Fixture<'a> {
+    fn new(input: &'a mut [u8], output: &'a mut [u8]) -> Self {
+        Self {
+            exchange: MessageExchange::new(input, output),
+            device: ErrorDevice::new(),
+            context: Context::default(),
+        }
+    }
+
+    fn receive(&mut self, data: &[u8], end: bool) {
+        self.exchange
+            .receive(TEST_TREE, &mut self.device, &mut self.context, data, end);
+    }
+
+    fn send(&mut self, len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        let n = self
+            .exchange
+            .send(&mut self.device, &mut self.context, &mut buf);
+        buf.truncate(n);
+        buf
+    }
+}
+
+#[test]
+fn test_exchange() {
+    let (mut input, mut output) = ([0u8; 32], [0u8; 32]);
+    let mut fix = Fixture::new(&mut input, &mut output);
+    assert_eq!(fix.exchange.state(), State::Idle);
+
+    // Message split across receives
+    fix.receive(b"VAL 4", false);
+    assert_eq!(fix.exchange.state(), State::Read);
+    fix.receive(b"2;VAL?", false);
+    assert!(!fix.context.mav());
+    fix.receive(b"\n", false);
+    assert_eq!(fix.exchange.state(), State::Send);
+    assert!(fix.context.mav());
+
+    // Response read in pieces
+    assert_eq!(fix.send(1), b"4");
+    assert!(fix.exchange.mav());
+    assert_eq!(fix.send(8), b"2\n");
+    assert_eq!(fix.exchange.state(), State::Idle);
+    assert!(!fix.context.mav());
+
+    // Several messages at once, responses read in between
+    fix.receive(b"VAL 1\nVAL?\n", false);
+    assert_eq!(fix.send(8), b"1\n");
+
+    // Terminated by END
+    fix.receive(b"VAL?", true);
+    assert_eq!(fix.send(8), b"1\n");
+    assert!(fix.device.errors.is_empty());
+}
+
+#[test]
+fn test_interrupted() {
+    let (mut input, mut output) = ([0u8; 32], [0u8; 32]);
+    let mut fix = Fixture::new(&mut input, &mut output);
+
+    // Second query interrupts the unread response of the first
+    fix.receive(b"VAL?\n*MAV?\n", false);
+    assert_eq!(fix.device.errors, [-410]);
+    assert_eq!(fix.send(8), b"0\n");
+
+    // Partly read
+    fix.receive(b"VAL?\n", false);
+    assert_eq!(fix.send(1), b"0");
+    fix.receive(b"VAL 2\n", false);
+    assert_eq!(fix.device.errors, [-410, -410]);
+    assert_eq!(fix.exchange.state(), State::Idle);
+    assert!(!fix.context.mav());
+}
+
+#[test]
+fn test_unterminated() {
+    let (mut input, mut output) = ([0u8; 32], [0u8; 32]);
+    let mut fix = Fixture::new(&mut input, &mut output);
+
+    // Nothing to read
+    assert_eq!(fix.send(8), b"");
+    // Incomplete message
+    fix.receive(b"VAL 1", false);
+    assert_eq!(fix.send(8), b"");
+    // Command without response
+    fix.receive(b"\n", false);
+    assert_eq!(fix.send(8), b"");
+    assert_eq!(fix.device.errors, [-420, -420, -420]);
+    assert_eq!(fix.device.value, 1);
+}
+
+#[test]
+fn test_deadlocked() {
+    let (mut input, mut output) = ([0u8; 16], [0u8; 4]);
+    let mut fix = Fixture::new(&mut input, &mut output);
+
+    // Response doesn't fit the output queue
+    fix.receive(b"VAL 12345\nVAL?\n", false);
+    assert_eq!(fix.device.errors, [-430]);
+    assert_eq!(fix.exchange.state(), State::Idle);
+    assert!(!fix.context.mav());
+
+    // Message doesn't fit the input buffer
+    fix.receive(b"VAL 1\nVAL 2;VAL 3;VAL 4;VAL 5\nVAL?\n", false);
+    assert_eq!(fix.device.errors, [-430, -363]);
+    assert_eq!(fix.send(8), b"1\n");
+}
+
+#[test]
+fn test_clear() {
+    let (mut input, mut output) = ([0u8; 32], [0u8; 32]);
+    let mut fix = Fixture::new(&mut input, &mut output);
+    fix.receive(b"VAL?\n", false);
+    fix.exchange.clear(&mut fix.context);
+    assert_eq!(fix.exchange.state(), State::Idle);
+    assert!(!fix.context.mav());
+    fix.receive(b"VAL", false);
+    fix.exchange.clear(&mut fix.context);
+    assert_eq!(fix.exchange.state(), State::Idle);
+    fix.receive(b"VAL?\n", false);
+    assert_eq!(fix.send(8), b"0\n");
+    assert!(fix.device.errors.is_empty());
+}
+
+#[test]
+fn test_full_input() {
+    // Message exactly filling the input buffer, completed by END
+    let (mut input, mut output) = ([0u8; 5], [0u8; 8]);
+    let mut fix = Fixture::new(&mut input, &mut output);
+    fix.receive(b"*MAV?", true);
+    assert_eq!(fix.send(8), b"0\n");
+
+    // Or by the terminator
+    fix.receive(b"VAL?", false);
+    fix.receive(b"\n", false);
+    assert_eq!(fix.send(8), b"0\n");
+    assert!(fix.device.errors.is_empty());
+
+    // More data is an overrun
+    fix.receive(b"*MAV?;", true);
+    assert_eq!(fix.device.errors, [-363]);
+    assert_eq!(fix.exchange.state(), State::Idle);
+}
+
+#[test]
+fn test_overrun_split_terminator() {
+    let (mut input, mut output) = ([0u8; 8], [0u8; 8]);
+    let mut fix = Fixture::new(&mut input, &mut output);
+    fix.context.terminator = Terminator::CrNl;
+
+    // Terminator split across receives while discarding
+    fix.receive(b"VAL 1;VAL 2;VAL 3\r", false);
+    fix.receive(b"\nVAL?\r\n", false);
+    assert_eq!(fix.device.errors, [-363]);
+    assert_eq!(fix.send(8), b"0\r\n");
+
+    // Overrun right after the first terminator byte
+    fix.receive(b"VAL 1;V\r", false);
+    fix.receive(b"\nVAL?\r\n", false);
+    assert_eq!(fix.device.errors, [-363, -363]);
+    assert_eq!(fix.send(8), b"0\r\n");
+}
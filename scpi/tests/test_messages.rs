@@ -0,0 +1,233 @@
+//! Test executing several program messages received at once
+mod util;
+
+use scpi::{
+    cmd_both, cmd_qonly, error::Result, parser::ParserConfig, tree::prelude::*, Leaf, Root,
+};
+use util::ErrorDevice;
+
+/// Echoes string data
+struct EchoCommand;
+
+impl Command<ErrorDevice> for EchoCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut ErrorDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let x: &[u8] = params.next_data()?;
+        response.data(x).finish()
+    }
+}
+
+/// Responds with the length of string data
+struct LenCommand;
+
+impl Command<ErrorDevice> for LenCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut ErrorDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let x: &[u8] = params.next_data()?;
+        response.data(x.len()).finish()
+    }
+}
+
+/// Echoes block data
+struct BlockCommand;
+
+impl Command<ErrorDevice> for BlockCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut ErrorDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let x: Arbitrary = params.next_data()?;
+        response.data(x).finish()
+    }
+}
+
+/// Sets or queries a value
+struct ValueCommand;
+
+impl Command<ErrorDevice> for ValueCommand {
+    cmd_both!();
+
+    fn event(
+        &self,
+        device: &mut ErrorDevice,
+        _context: &mut Context,
+        mut params: Parameters,
+    ) -> Result<()> {
+        device.value = params.next_data()?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        device: &mut ErrorDevice,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        response.data(device.value).finish()
+    }
+}
+
+/// Responds with no data
+struct EmptyCommand;
+
+impl Command<ErrorDevice> for EmptyCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut ErrorDevice,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        response.data(Vec::<f32>::new()).finish()
+    }
+}
+
+const TEST_TREE: &Node<ErrorDevice> = &Root![
+    Leaf!(b"*ECHO" => &EchoCommand),
+    Leaf!(b"*BLK" => &BlockCommand),
+    Leaf!(b"*LEN" => &LenCommand),
+    Leaf!(b"*VAL" => &ValueCommand),
+    Leaf!(b"*EMPTY" => &EmptyCommand)
+];
+
+fn run_messages(messages: &[u8]) -> (Vec<Vec<u8>>, ErrorDevice) {
+    run_messages_in(messages, Context::default())
+}
+
+fn run_messages_terminated(messages: &[u8], terminator: Terminator) -> (Vec<Vec<u8>>, ErrorDevice) {
+    let context = Context {
+        terminator,
+        ..Default::default()
+    };
+    run_messages_in(messages, context)
+}
+
+fn run_messages_in(messages: &[u8], mut context: Context) -> (Vec<Vec<u8>>, ErrorDevice) {
+    let mut dev = ErrorDevice::new();
+    let mut buf = Vec::new();
+    let mut responses = Vec::new();
+    TEST_TREE.run_messages(messages, &mut dev, &mut context, &mut buf, |response| {
+        responses.push(response.to_vec())
+    });
+    (responses, dev)
+}
+
+#[test]
+fn test_messages() {
+    let (responses, dev) =
+        run_messages(b"*VAL 1\n*VAL?;*ECHO? \"a\nb\"\n*VAL 2;*VAL?\n*BLK? #13\n\n\n\n*VAL?");
+    assert_eq!(
+        responses,
+        vec![
+            b"1;\"a\nb\"\n".to_vec(),
+            b"2\n".to_vec(),
+            b"#13\n\n\n\n".to_vec(),
+            b"2\n".to_vec()
+        ]
+    );
+    assert!(dev.errors.is_empty());
+}
+
+#[test]
+fn test_messages_error() {
+    // Failing message is skipped but following messages are executed
+    let (responses, dev) = run_messages(b"*VAL?;*VAL 'x';*VAL 3\n*VAL?\n");
+    assert_eq!(responses, vec![b"0\n".to_vec()]);
+    assert_eq!(dev.errors, vec![ErrorCode::DataTypeError.get_code()]);
+}
+
+#[test]
+fn test_messages_terminator() {
+    let (responses, dev) = run_messages_terminated(
+        b"*VAL 1\r\n*VAL?;*ECHO? '\r\n'\r\n*BLK? #0\n\r\n",
+        Terminator::CrNl,
+    );
+    assert_eq!(
+        responses,
+        vec![b"1;\"\r\n\"\r\n".to_vec(), b"#11\n\r\n".to_vec()]
+    );
+    assert!(dev.errors.is_empty());
+
+    // Whole buffer is a single message
+    let (responses, dev) = run_messages_terminated(b"*VAL 2;*VAL?", Terminator::End);
+    assert_eq!(responses, vec![b"2".to_vec()]);
+    assert!(dev.errors.is_empty());
+}
+
+#[test]
+fn test_messages_config() {
+    let messages = b"+*VAL 4\n*VAL?\n*LEN?'AB'\n*LEN? '\xc2\xb0C'\n";
+    // Strict by default
+    let (responses, dev) = run_messages(messages);
+    assert_eq!(responses, vec![b"0\n".to_vec()]);
+    assert_eq!(
+        dev.errors,
+        vec![
+            ErrorCode::CommandHeaderError.get_code(),
+            ErrorCode::SyntaxError.get_code(),
+            ErrorCode::InvalidCharacter.get_code()
+        ]
+    );
+
+    let context = Context {
+        config: ParserConfig::LENIENT,
+        ..Default::default()
+    };
+    let (responses, dev) = run_messages_in(messages, context);
+    assert_eq!(
+        responses,
+        vec![b"4\n".to_vec(), b"2\n".to_vec(), b"3\n".to_vec()]
+    );
+    assert!(dev.errors.is_empty());
+}
+
+#[test]
+fn test_messages_empty_response() {
+    // A query must respond, the message fails instead of sending "0;\n" or nothing
+    let (responses, dev) = run_messages(b"*VAL?;*EMPTY?\n*EMPTY?\n*VAL?\n");
+    assert_eq!(responses, vec![b"0\n".to_vec()]);
+    assert_eq!(
+        dev.errors,
+        vec![
+            ErrorCode::ExecutionError.get_code(),
+            ErrorCode::ExecutionError.get_code()
+        ]
+    );
+
+    // No separator is left behind
+    let mut dev = ErrorDevice::new();
+    let mut buf = Vec::new();
+    let res = TEST_TREE.run(
+        b"*VAL?;*EMPTY?",
+        &mut dev,
+        &mut Context::default(),
+        &mut buf,
+    );
+    assert_eq!(
+        res.map_err(|err| err.get_code()),
+        Err(ErrorCode::ExecutionError.get_code())
+    );
+    assert_eq!(buf, b"0");
+}
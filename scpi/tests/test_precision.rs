@@ -0,0 +1,71 @@
+//! Test the default precision of real response data
+use scpi::{
+    cmd_qonly,
+    error::Result,
+    parser::{format::Nr3, response::Precision},
+    tree::prelude::*,
+    Leaf, Root,
+};
+
+/// Responds with reals in a precision of its own
+struct PrecisionDevice(Precision);
+
+impl Device for PrecisionDevice {
+    fn handle_error(&mut self, _err: Error) {}
+
+    fn precision(&self) -> Precision {
+        self.0
+    }
+}
+
+/// Responds with `1/3` in several forms
+struct ThirdCommand;
+
+impl Command<PrecisionDevice> for ThirdCommand {
+    cmd_qonly!();
+
+    fn query(
+        &self,
+        _device: &mut PrecisionDevice,
+        _context: &mut Context,
+        _params: Parameters,
+        mut response: ResponseUnit,
+    ) -> Result<()> {
+        let x = 1.0f32 / 3.0;
+        response
+            .data(x)
+            .data([x as f64, -x as f64])
+            .data(Nr3(x, 1))
+            .data(Precision::Shortest.data(x))
+            .data(f32::NAN)
+            .data(3u8)
+            .finish()
+    }
+}
+
+const TREE: Node<PrecisionDevice> = Root![Leaf!(b"THIRd" => &ThirdCommand)];
+
+fn run(precision: Precision) -> Vec<u8> {
+    let mut device = PrecisionDevice(precision);
+    let mut buf = Vec::new();
+    TREE.run(b"THIR?", &mut device, &mut Context::default(), &mut buf)
+        .unwrap();
+    buf
+}
+
+#[test]
+fn test_device_precision() {
+    assert_eq!(
+        run(Precision::Shortest),
+        b"0.33333334,0.3333333432674408,-0.3333333432674408,3E-01,0.33333334,9.91E+37,3\n"
+            .as_slice()
+    );
+    assert_eq!(
+        run(Precision::Decimals(2)),
+        b"0.33,0.33,-0.33,3E-01,0.33333334,9.91E+37,3\n".as_slice()
+    );
+    assert_eq!(
+        run(Precision::Significant(3)),
+        b"3.33E-01,3.33E-01,-3.33E-01,3E-01,0.33333334,9.91E+37,3\n".as_slice()
+    );
+}
@@ -3,8 +3,10 @@ use std::path::Path;
 use scpi::{error::Result, tree::prelude::*};
 use serde::Deserialize;
 
+#[allow(dead_code)]
 pub(crate) struct TestDevice;
 
+#[allow(dead_code)]
 impl TestDevice {
     pub(crate) fn new() -> Self {
         TestDevice
@@ -15,6 +17,29 @@ impl Device for TestDevice {
     fn handle_error(&mut self, _err: Error) {}
 }
 
+/// Records reported errors
+#[allow(dead_code)]
+pub(crate) struct ErrorDevice {
+    pub errors: Vec<i16>,
+    pub value: i32,
+}
+
+#[allow(dead_code)]
+impl ErrorDevice {
+    pub(crate) fn new() -> Self {
+        ErrorDevice {
+            errors: Vec::new(),
+            value: 0,
+        }
+    }
+}
+
+impl Device for ErrorDevice {
+    fn handle_error(&mut self, err: Error) {
+        self.errors.push(err.get_code());
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 struct Record {